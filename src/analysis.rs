@@ -0,0 +1,654 @@
+use pest::error::InputLocation;
+use pest::iterators::Pair;
+
+use crate::build_task::SrcFile;
+use crate::compilation_error::CompilationError;
+use crate::core::Of;
+use crate::palel::*;
+use crate::parser::{
+    Rule, get_identifier, parse, parse_checked, parse_procedure, parse_program, parse_statement,
+};
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::{CTranspile, context, transpile, transpile_statement};
+use crate::transpiler_context::{Context, Returns};
+
+impl Span {
+    pub fn of(pair: &Pair<'_, Rule>) -> Span {
+        Span {
+            start: pair.as_span().start(),
+            end: pair.as_span().end(),
+        }
+    }
+
+    /// A cursor right behind the last character is still on it.
+    fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
+
+/// `content` with `edits` made, an edit overlapping one made before it is
+/// left out.
+pub fn apply_edits(content: &str, edits: &[Edit]) -> String {
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| (edit.span.start, edit.span.end));
+    let mut output = String::new();
+    let mut position = 0;
+    for edit in sorted {
+        if edit.span.start < position || edit.span.end > content.len() {
+            continue;
+        }
+        output.push_str(&content[position..edit.span.start]);
+        output.push_str(&edit.replacement);
+        position = edit.span.end;
+    }
+    output.push_str(&content[position..]);
+    output
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+    /// The edits of the suggestions of the error that can be made without
+    /// asking
+    pub fix: Vec<Edit>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub detail: String,
+}
+
+/// The source files of a project as an editor sees them, including changes
+/// that are not saved yet. Files are named relative to the source directory
+/// like the build names them, so they map to the same modules.
+#[derive(Default)]
+pub struct Workspace {
+    pub files: Vec<SrcFile>,
+}
+
+/// A program or procedure of a file with its parameters and statements.
+pub struct Block<'i> {
+    /// The identifier of each parameter with its type
+    pub parameters: Vec<(Pair<'i, Rule>, Type)>,
+    pub statements: Vec<Scoped<'i>>,
+}
+
+/// A statement with what is in scope before and after it, and the error the
+/// C backend reports for it.
+pub struct Scoped<'i> {
+    pub pair: Pair<'i, Rule>,
+    pub before: Context,
+    pub after: Context,
+    pub error: Option<Box<dyn CompilationError>>,
+}
+
+impl Workspace {
+    /// Adds `file` or replaces its content.
+    pub fn set(&mut self, file: &str, content: String) {
+        match self.files.iter_mut().find(|f| f.file == file) {
+            Some(existing) => existing.content = content,
+            None => self.files.push(SrcFile {
+                file: file.to_string(),
+                content,
+            }),
+        }
+    }
+
+    pub fn remove(&mut self, file: &str) {
+        self.files.retain(|f| f.file != file);
+    }
+
+    pub fn content(&self, file: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|f| f.file == file)
+            .map(|f| f.content.as_str())
+    }
+
+    /// The syntax errors of `file`, or the errors the C backend reports for
+    /// its statements. Errors of the project that cannot be placed on a
+    /// statement are reported at the start of the file.
+    pub fn diagnostics(&self, file: &str) -> Vec<Diagnostic> {
+        let content = match self.content(file) {
+            Some(content) => content,
+            None => return vec![],
+        };
+        let root = match parse_checked(Rule::src, content) {
+            Ok(mut pairs) => match pairs.next() {
+                Some(root) => root,
+                None => return vec![],
+            },
+            Err(err) => {
+                let span = match err.location {
+                    InputLocation::Pos(pos) => Span {
+                        start: pos,
+                        end: pos,
+                    },
+                    InputLocation::Span((start, end)) => Span { start, end },
+                };
+                return vec![Diagnostic {
+                    span,
+                    message: err.variant.message().to_string(),
+                    fix: vec![],
+                }];
+            }
+        };
+        let src = self.src();
+        let mut diagnostics = vec![];
+        for block in blocks(&src, &module_name_from_file(file), root) {
+            for scoped in block.statements {
+                if let Some(err) = scoped.error {
+                    diagnostics.push(Diagnostic {
+                        span: Span::of(&scoped.pair),
+                        message: err.report(),
+                        fix: err
+                            .suggestions()
+                            .into_iter()
+                            .filter_map(|suggestion| suggestion.edit)
+                            .collect(),
+                    });
+                }
+            }
+        }
+        // errors of other files are reported on those files
+        let other_files_fail =
+            self.files
+                .iter()
+                .any(|other| match parse_checked(Rule::src, &other.content) {
+                    Ok(mut pairs) => pairs.next().is_some_and(|root| {
+                        blocks(&src, &module_name_from_file(&other.file), root)
+                            .iter()
+                            .any(|block| block.statements.iter().any(|s| s.error.is_some()))
+                    }),
+                    Err(_) => true,
+                });
+        if diagnostics.is_empty()
+            && !other_files_fail
+            && let Of::Error(err) = transpile(&src, &CToolKit {})
+        {
+            diagnostics.push(Diagnostic {
+                span: Span { start: 0, end: 0 },
+                message: err.report(),
+                fix: vec![],
+            });
+        }
+        diagnostics
+    }
+
+    /// The type of the variable or the signature of the procedure at `offset`.
+    pub fn hover(&self, file: &str, offset: usize) -> Option<(Span, String)> {
+        let content = self.content(file)?;
+        let root = parse_checked(Rule::src, content).ok()?.next()?;
+        let src = self.src();
+        for block in blocks(&src, &module_name_from_file(file), root) {
+            for (identifier, typ) in &block.parameters {
+                if Span::of(identifier).contains(offset) {
+                    let text = format!("{}: {}", identifier.as_str(), typ.to_string());
+                    return Some((Span::of(identifier), text));
+                }
+            }
+            for scoped in &block.statements {
+                if !Span::of(&scoped.pair).contains(offset) {
+                    continue;
+                }
+                if let Some((span, name)) = call_at(&scoped.pair, offset) {
+                    let signature = scoped.before.lookup_procedure(&name)?;
+                    return Some((span, describe_signature(&name, signature)));
+                }
+                let variable = find(&scoped.pair, offset, Rule::variable_identifier)?;
+                let name = variable.as_str();
+                let ctx = match declared_identifier(&scoped.pair) {
+                    Some(declared) if declared.as_span() == variable.as_span() => &scoped.after,
+                    _ => &scoped.before,
+                };
+                let typ = ctx.lookup(name)?;
+                return Some((
+                    Span::of(&variable),
+                    format!("{}: {}", name, typ.to_string()),
+                ));
+            }
+        }
+        None
+    }
+
+    /// Where the variable or procedure at `offset` is declared, as the file
+    /// and the span of its identifier.
+    pub fn definition(&self, file: &str, offset: usize) -> Option<(String, Span)> {
+        let content = self.content(file)?;
+        let root = parse_checked(Rule::src, content).ok()?.next()?;
+        let src = self.src();
+        for block in blocks(&src, &module_name_from_file(file), root) {
+            for (identifier, _) in &block.parameters {
+                if Span::of(identifier).contains(offset) {
+                    return Some((file.to_string(), Span::of(identifier)));
+                }
+            }
+            for (idx, scoped) in block.statements.iter().enumerate() {
+                if !Span::of(&scoped.pair).contains(offset) {
+                    continue;
+                }
+                if let Some((_, name)) = call_at(&scoped.pair, offset) {
+                    let signature = scoped.before.lookup_procedure(&name)?;
+                    return self.find_procedure(signature);
+                }
+                let variable = find(&scoped.pair, offset, Rule::variable_identifier)?;
+                if let Some(declared) = declared_identifier(&scoped.pair)
+                    && declared.as_span() == variable.as_span()
+                {
+                    return Some((file.to_string(), Span::of(&declared)));
+                }
+                let declaration = block.statements[..idx]
+                    .iter()
+                    .rev()
+                    .filter_map(|s| declared_identifier(&s.pair))
+                    .chain(block.parameters.iter().map(|(p, _)| p.clone()))
+                    .find(|declared| declared.as_str() == variable.as_str())?;
+                return Some((file.to_string(), Span::of(&declaration)));
+            }
+        }
+        None
+    }
+
+    /// The procedures of the interface named before the colon the identifier
+    /// at `offset` follows, such as the members of `debug:`.
+    pub fn completion(&self, file: &str, offset: usize) -> Vec<Completion> {
+        let content = match self.content(file) {
+            Some(content) if content.is_char_boundary(offset) => content,
+            _ => return vec![],
+        };
+        let line_start = content[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let line = &content[line_start..offset];
+        let is_identifier_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let before_typed = line.trim_end_matches(is_identifier_char);
+        let typed = &line[before_typed.len()..];
+        let interface = match before_typed.strip_suffix(':') {
+            Some(before_colon) => {
+                let start = before_colon.trim_end_matches(is_identifier_char).len();
+                &before_colon[start..]
+            }
+            None => return vec![],
+        };
+        if !interface.starts_with(|c: char| c.is_ascii_lowercase()) {
+            return vec![];
+        }
+        let src = self.src();
+        let ctx = context(&src, &module_name_from_file(file), &CToolKit {});
+        ctx.procedures()
+            .filter(|(_, s)| s.interface == interface && s.identifier.starts_with(typed))
+            .map(|(name, s)| Completion {
+                label: s.identifier.clone(),
+                detail: describe_signature(name, s),
+            })
+            .collect()
+    }
+
+    /// Every file that parses, the others are left out so the rest of the
+    /// project still resolves while a file is being edited.
+    fn src(&self) -> Src {
+        let mut src = Src::default();
+        for file in &self.files {
+            if parse_checked(Rule::src, &file.content).is_ok() {
+                let _ = parse(&mut src, file);
+            }
+        }
+        src
+    }
+
+    /// The identifier of the procedure implementing `signature`, or of its
+    /// binding or declaration in an interface.
+    fn find_procedure(&self, signature: &ProcedureSignature) -> Option<(String, Span)> {
+        let mut declaration = None;
+        for file in &self.files {
+            let root = match parse_checked(Rule::src, &file.content) {
+                Ok(mut pairs) => match pairs.next() {
+                    Some(root) => root,
+                    None => continue,
+                },
+                Err(_) => continue,
+            };
+            let module = module_name_from_file(&file.file);
+            for pair in root.into_inner() {
+                let interface = child(&pair, Rule::interface_identifier)
+                    .map(get_identifier)
+                    .unwrap_or_default();
+                match pair.as_rule() {
+                    Rule::procedure | Rule::binding => {
+                        let identifier = match child(&pair, Rule::procedure_identifier) {
+                            Some(identifier) => identifier,
+                            None => continue,
+                        };
+                        if get_identifier(identifier.clone()) == signature.identifier
+                            && interface == signature.interface
+                            && (!interface.is_empty() || module == signature.module)
+                        {
+                            return Some((file.file.clone(), Span::of(&identifier)));
+                        }
+                    }
+                    Rule::interface | Rule::extern_interface => {
+                        let name = match child(&pair, Rule::c_header) {
+                            Some(header) if interface.is_empty() => {
+                                extern_interface_name(header.as_str())
+                            }
+                            _ => interface,
+                        };
+                        if name != signature.interface {
+                            continue;
+                        }
+                        let identifier = pair
+                            .clone()
+                            .into_inner()
+                            .filter(|p| p.as_rule() == Rule::interface_proc)
+                            .filter_map(|p| child(&p, Rule::procedure_identifier))
+                            .find(|p| get_identifier(p.clone()) == signature.identifier);
+                        if let Some(identifier) = identifier {
+                            declaration = Some((file.file.clone(), Span::of(&identifier)));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        declaration
+    }
+}
+
+/// Checks the statements of every program and procedure in `root` one by
+/// one, as the C backend checks them, so errors can be placed on a statement.
+pub fn blocks<'i>(src: &Src, module: &str, root: Pair<'i, Rule>) -> Vec<Block<'i>> {
+    let toolkit = CToolKit {};
+    let globals = context(src, module, &toolkit);
+    let mut blocks = vec![];
+    for pair in root.into_inner() {
+        let mut ctx = globals.clone();
+        let parameters = match pair.as_rule() {
+            Rule::program => {
+                ctx.returns = Returns::ExitStatus;
+                parse_program(pair.clone(), module, None).parameters
+            }
+            Rule::procedure => {
+                let procedure = parse_procedure(pair.clone(), module, None);
+                ctx.returns = match procedure.return_type {
+                    Some(typ) => Returns::Value(Type::Dim(typ)),
+                    None => Returns::Nothing,
+                };
+                procedure.parameters
+            }
+            Rule::test_block | Rule::bench_block => {
+                ctx.returns = Returns::Nothing;
+                vec![]
+            }
+            _ => continue,
+        };
+        let mut block = Block {
+            parameters: vec![],
+            statements: vec![],
+        };
+        let identifiers = child(&pair, Rule::parameter_list)
+            .into_iter()
+            .flat_map(|list| list.into_inner())
+            .filter_map(|parameter| child(&parameter, Rule::variable_identifier));
+        for (identifier, parameter) in identifiers.zip(parameters) {
+            let typ = Type::Dim(parameter.schema_type);
+            ctx.declare(&parameter.identifier, typ.clone());
+            block.parameters.push((identifier, typ));
+        }
+        let statements = child(&pair, Rule::do_block)
+            .into_iter()
+            .flat_map(|do_block| do_block.into_inner())
+            .filter(|p| p.as_rule() == Rule::statement);
+        for statement_pair in statements {
+            let before = ctx.clone();
+            let error = match parse_statement(statement_pair.clone()) {
+                Some(statement) => match transpile_statement(&statement, &mut ctx, &toolkit) {
+                    CTranspile::Ok(..) => None,
+                    CTranspile::Error(err) => Some(err),
+                },
+                None => None,
+            };
+            block.statements.push(Scoped {
+                pair: statement_pair,
+                before,
+                after: ctx.clone(),
+                error,
+            });
+        }
+        blocks.push(block);
+    }
+    blocks
+}
+
+pub fn child<'i>(pair: &Pair<'i, Rule>, rule: Rule) -> Option<Pair<'i, Rule>> {
+    pair.clone().into_inner().find(|p| p.as_rule() == rule)
+}
+
+/// The innermost pair of `rule` below `pair` at `offset`.
+fn find<'i>(pair: &Pair<'i, Rule>, offset: usize, rule: Rule) -> Option<Pair<'i, Rule>> {
+    for inner in pair.clone().into_inner() {
+        if Span::of(&inner).contains(offset)
+            && let Some(found) = find(&inner, offset, rule)
+        {
+            return Some(found);
+        }
+    }
+    match pair.as_rule() == rule && Span::of(pair).contains(offset) {
+        true => Some(pair.clone()),
+        false => None,
+    }
+}
+
+/// The name of the call at `offset` with its span when `offset` is on the
+/// name and not on the arguments.
+fn call_at(pair: &Pair<'_, Rule>, offset: usize) -> Option<(Span, String)> {
+    let call = find(pair, offset, Rule::procedure_call)?;
+    let path = child(&call, Rule::procedure_path)?;
+    let span = Span {
+        start: call.as_span().start(),
+        end: path.as_span().end(),
+    };
+    if !span.contains(offset) {
+        return None;
+    }
+    let interface = child(&call, Rule::interface_identifier)
+        .map(get_identifier)
+        .unwrap_or_default();
+    Some((span, call_name(&interface, path.as_str())))
+}
+
+/// The identifier `statement` declares when it is a variable declaration.
+fn declared_identifier<'i>(statement: &Pair<'i, Rule>) -> Option<Pair<'i, Rule>> {
+    let declaration = child(statement, Rule::variable_statement)?;
+    child(&declaration, Rule::variable_identifier)
+}
+
+/// `proc name(Int64, String?) Bool` for a procedure called by `name`.
+fn describe_signature(name: &str, signature: &ProcedureSignature) -> String {
+    let describe_type = |typ: &Type| match typ {
+        Type::Dim(schema) => schema.to_string(),
+        other => other.to_string(),
+    };
+    let parameters = match signature.implementation {
+        // built-in procedures such as `debug:print` take any arguments
+        Implementation::Builtin if signature.parameters.is_empty() => "...".to_string(),
+        _ => signature
+            .parameters
+            .iter()
+            .map(describe_type)
+            .collect::<Vec<String>>()
+            .join(", "),
+    };
+    let mut description = format!("proc {}({})", name, parameters);
+    if let Some(return_type) = &signature.return_type {
+        description.push(' ');
+        description.push_str(&describe_type(return_type));
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const MAIN: &str = concat!(
+        "use text\n",
+        "\n",
+        "program(name String) do\n",
+        "    dim n Int32 = text.size(name)\n",
+        "    debug:print(n, text.size(\"x\"))\n",
+        "    dim b Bool = n\n",
+        "end\n",
+    );
+    const TEXT: &str = concat!(
+        "pub proc size(value String) Int32 do\n",
+        "    return 1\n",
+        "end\n",
+    );
+
+    fn workspace() -> Workspace {
+        let mut workspace = Workspace::default();
+        workspace.set("main.palel", MAIN.to_string());
+        workspace.set("text.palel", TEXT.to_string());
+        workspace
+    }
+
+    fn offset(content: &str, needle: &str) -> usize {
+        content.find(needle).unwrap()
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut workspace = workspace();
+        let diagnostics = workspace.diagnostics("main.palel");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            &MAIN[diagnostics[0].span.start..diagnostics[0].span.end],
+            "dim b Bool = n"
+        );
+        assert_eq!(workspace.diagnostics("text.palel"), vec![]);
+
+        workspace.set(
+            "text.palel",
+            "pub proc size(value String) Int32 do\n".to_string(),
+        );
+        let diagnostics = workspace.diagnostics("text.palel");
+        assert_eq!(diagnostics[0].span.start, TEXT.find('\n').unwrap() + 1);
+    }
+
+    #[test]
+    fn test_fixes() {
+        let content = concat!(
+            "proc half(x Int32) Int16 do\n",
+            "    return x\n",
+            "end\n",
+            "\n",
+            "program do\n",
+            "    dim n Int32 = null\n",
+            "    dim small = half(7)\n",
+            "    dim q Int8 = small\n",
+            "    dim b Bool = small\n",
+            "end\n",
+        );
+        let mut workspace = Workspace::default();
+        workspace.set("main.palel", content.to_string());
+        let diagnostics = workspace.diagnostics("main.palel");
+        assert_eq!(
+            diagnostics[0].message,
+            concat!(
+                "incompatible types, expected dim Int16, received dim Int32\n",
+                "help: cast the value with `as! Int16`, which clamps the value to the range of the type"
+            )
+        );
+        let narrowing = diagnostics
+            .iter()
+            .find(|d| d.message.contains("`as! Int8`"))
+            .unwrap();
+        assert!(narrowing.fix.is_empty());
+        let edits: Vec<Edit> = diagnostics.into_iter().flat_map(|d| d.fix).collect();
+        let start = offset(content, "Int32 = null");
+        assert_eq!(
+            edits,
+            vec![Edit {
+                span: Span {
+                    start,
+                    end: start + "Int32".len(),
+                },
+                replacement: "Int32?".to_string(),
+            }]
+        );
+        assert_eq!(
+            apply_edits(content, &edits),
+            content.replace("Int32 = null", "Int32? = null")
+        );
+    }
+
+    #[test]
+    fn test_hover_and_definition() {
+        let workspace = workspace();
+        let use_of_n = offset(MAIN, "n, text");
+        assert_eq!(
+            workspace.hover("main.palel", use_of_n),
+            Some((
+                Span {
+                    start: use_of_n,
+                    end: use_of_n + 1
+                },
+                "n: dim Int32".to_string()
+            ))
+        );
+        let call = offset(MAIN, "size(\"x\")");
+        assert_eq!(
+            workspace.hover("main.palel", call).map(|(_, text)| text),
+            Some("proc text.size(String) Int32".to_string())
+        );
+        assert_eq!(
+            workspace.hover("main.palel", offset(MAIN, "name)")),
+            Some((
+                Span {
+                    start: offset(MAIN, "name)"),
+                    end: offset(MAIN, "name)") + 4
+                },
+                "name: dim String".to_string()
+            ))
+        );
+
+        let declaration = offset(MAIN, "n Int32");
+        assert_eq!(
+            workspace.definition("main.palel", use_of_n),
+            Some((
+                "main.palel".to_string(),
+                Span {
+                    start: declaration,
+                    end: declaration + 1
+                }
+            ))
+        );
+        assert_eq!(
+            workspace.definition("main.palel", call),
+            Some((
+                "text.palel".to_string(),
+                Span {
+                    start: offset(TEXT, "size"),
+                    end: offset(TEXT, "size") + 4
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut workspace = workspace();
+        let content = MAIN.replace("    debug:print", "    debug:pr");
+        let at = offset(&content, "debug:pr") + "debug:pr".len();
+        workspace.set("main.palel", content);
+        assert_eq!(
+            workspace.completion("main.palel", at),
+            vec![Completion {
+                label: "print".to_string(),
+                detail: "proc debug:print(...)".to_string(),
+            }]
+        );
+        assert_eq!(workspace.completion("main.palel", at - 3), vec![]);
+    }
+}
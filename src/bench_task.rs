@@ -0,0 +1,251 @@
+use std::fs;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::build_task::{BuildTask, build_benches, load_src, write};
+use crate::compilation_error::{BenchesFailed, CompilationError, FailedToRunProgram};
+use crate::core::Of;
+
+pub struct BenchTaskConfig {
+    /// Only run the benches whose name contains it
+    pub filter: Option<String>,
+    /// Untimed iterations before the timed ones
+    pub warmup: usize,
+    /// Timed iterations of every bench
+    pub iterations: usize,
+    /// How many percent slower than the last run a bench may get before it
+    /// is reported as a regression
+    pub threshold: f64,
+}
+
+/// How many samples the timed iterations of a bench are split into. Each
+/// one times a batch of iterations, so the clock is not read around every
+/// iteration.
+const SAMPLES: usize = 10;
+
+/// The results of the last run, kept in the bench folder of the build
+/// directory to compare the next run with.
+#[derive(Debug, PartialEq, Default, Serialize, Deserialize)]
+struct BenchResults {
+    benches: Vec<BenchResult>,
+}
+
+/// The time per iteration of one bench in nanoseconds.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct BenchResult {
+    name: String,
+    iterations: usize,
+    mean_ns: f64,
+    median_ns: f64,
+    stddev_ns: f64,
+}
+
+/// Builds the bench harness with optimizations, runs every selected bench in
+/// a process of its own and prints the time per iteration with the change
+/// since the last run. The results replace those of the last run.
+pub fn run_bench_task(
+    task: &mut BuildTask,
+    config: &BenchTaskConfig,
+) -> Option<Box<dyn CompilationError>> {
+    let src = match load_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Some(err),
+    };
+    let names: Vec<String> = src
+        .benches
+        .iter()
+        .map(|bench| format!("{}: {}", bench.module, bench.name))
+        .collect();
+    let harness = match build_benches(task) {
+        Of::Ok(Some(harness)) => harness,
+        Of::Ok(None) => {
+            println!("running 0 benches");
+            return None;
+        }
+        Of::Error(err) => return Some(err),
+    };
+
+    let bench_dir = format!("{}/bench", task.config.dest_dir);
+    let results_file = format!("{}/results.json", bench_dir);
+    let samples_file = format!("{}/samples.txt", bench_dir);
+    let previous = fs::read_to_string(&results_file)
+        .ok()
+        .and_then(|content| serde_json::from_str::<BenchResults>(&content).ok())
+        .unwrap_or_default();
+    if let Some(err) = write(&samples_file, &String::new()) {
+        return Some(err);
+    }
+
+    let selected: Vec<usize> = (0..names.len())
+        .filter(|idx| match &config.filter {
+            Some(filter) => names[*idx].contains(filter.as_str()),
+            None => true,
+        })
+        .collect();
+    println!("running {} bench(es)", selected.len());
+    let sample_count = config.iterations.clamp(1, SAMPLES);
+    let batch = (config.iterations / sample_count).max(1);
+    let mut results = vec![];
+    let mut failed = vec![];
+    for idx in selected {
+        let output = Command::new(&harness)
+            .arg(idx.to_string())
+            .arg(config.warmup.to_string())
+            .arg(sample_count.to_string())
+            .arg(batch.to_string())
+            .arg(&samples_file)
+            .output();
+        let output = match output {
+            Ok(output) => output,
+            Err(_) => return Some(Box::new(FailedToRunProgram { file: harness })),
+        };
+        let samples: Vec<f64> = fs::read_to_string(&samples_file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect();
+        if !output.status.success() || samples.is_empty() {
+            println!("bench {} ... FAILED", names[idx]);
+            print!("{}", String::from_utf8_lossy(&output.stdout));
+            print!("{}", String::from_utf8_lossy(&output.stderr));
+            failed.push(names[idx].clone());
+            continue;
+        }
+        let result = summarize(&names[idx], &samples, batch);
+        let last = previous.benches.iter().find(|b| b.name == result.name);
+        println!(
+            "bench {} ... {}{}",
+            result.name,
+            describe(&result),
+            match last {
+                Some(last) => format!(", {}", compare(last, &result, config.threshold)),
+                None => String::new(),
+            }
+        );
+        results.push(result);
+    }
+
+    // benches that were filtered out or failed keep their last results
+    let mut saved = BenchResults { benches: results };
+    for last in previous.benches {
+        if !saved.benches.iter().any(|b| b.name == last.name) {
+            saved.benches.push(last);
+        }
+    }
+    let content = serde_json::to_string_pretty(&saved).unwrap_or_default();
+    if let Some(err) = write(&results_file, &format!("{}\n", content)) {
+        return Some(err);
+    }
+    println!("results saved to {}", results_file);
+    match failed.is_empty() {
+        true => None,
+        false => Some(Box::new(BenchesFailed { failed })),
+    }
+}
+
+/// Mean, median and sample standard deviation of the nanoseconds per
+/// iteration in `samples`, which may not be empty. Each sample is the mean
+/// of a batch of `batch` iterations.
+fn summarize(name: &str, samples: &[f64], batch: usize) -> BenchResult {
+    let count = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / count;
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    let median = match sorted.len() % 2 {
+        0 => (sorted[middle - 1] + sorted[middle]) / 2.0,
+        _ => sorted[middle],
+    };
+    let variance = match samples.len() {
+        1 => 0.0,
+        _ => samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (count - 1.0),
+    };
+    BenchResult {
+        name: name.to_string(),
+        iterations: samples.len() * batch,
+        mean_ns: mean,
+        median_ns: median,
+        stddev_ns: variance.sqrt(),
+    }
+}
+
+fn describe(result: &BenchResult) -> String {
+    format!(
+        "mean {}, median {}, stddev {} ({} iterations)",
+        duration(result.mean_ns),
+        duration(result.median_ns),
+        duration(result.stddev_ns),
+        result.iterations
+    )
+}
+
+/// The change of the mean since the `last` run, a regression when it is
+/// more than `threshold` percent slower.
+fn compare(last: &BenchResult, result: &BenchResult, threshold: f64) -> String {
+    if last.mean_ns <= 0.0 {
+        return "no earlier time to compare with".to_string();
+    }
+    let change = (result.mean_ns - last.mean_ns) / last.mean_ns * 100.0;
+    let verdict = if change > threshold {
+        "regressed"
+    } else if change < -threshold {
+        "improved"
+    } else {
+        "no change"
+    };
+    format!("{:+.1}% since the last run, {}", change, verdict)
+}
+
+/// `nanoseconds` in the largest unit that keeps it at least 1.
+fn duration(nanoseconds: f64) -> String {
+    if nanoseconds >= 1e9 {
+        format!("{:.2} s", nanoseconds / 1e9)
+    } else if nanoseconds >= 1e6 {
+        format!("{:.2} ms", nanoseconds / 1e6)
+    } else if nanoseconds >= 1e3 {
+        format!("{:.2} µs", nanoseconds / 1e3)
+    } else {
+        format!("{:.1} ns", nanoseconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_summarize() {
+        let result = summarize("main: sum", &[40.0, 10.0, 30.0, 20.0], 1);
+        assert_eq!(result.iterations, 4);
+        assert_eq!(result.mean_ns, 25.0);
+        assert_eq!(result.median_ns, 25.0);
+        assert_eq!(format!("{:.3}", result.stddev_ns), "12.910");
+        assert_eq!(summarize("one", &[7.0], 1).stddev_ns, 0.0);
+        assert_eq!(summarize("batched", &[7.0, 9.0], 10).iterations, 20);
+        assert_eq!(
+            describe(&summarize("main: sum", &[1500.0, 2500.0, 2000.0], 1)),
+            "mean 2.00 µs, median 2.00 µs, stddev 500.0 ns (3 iterations)"
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let last = summarize("main: sum", &[100.0], 1);
+        assert_eq!(
+            compare(&last, &summarize("main: sum", &[112.0], 1), 5.0),
+            "+12.0% since the last run, regressed"
+        );
+        assert_eq!(
+            compare(&last, &summarize("main: sum", &[97.0], 1), 5.0),
+            "-3.0% since the last run, no change"
+        );
+        assert_eq!(
+            compare(&last, &summarize("main: sum", &[50.0], 1), 5.0),
+            "-50.0% since the last run, improved"
+        );
+        assert_eq!(duration(3.2e9), "3.20 s");
+        assert_eq!(duration(4.5e6), "4.50 ms");
+    }
+}
@@ -0,0 +1,623 @@
+use crate::c::CType;
+use crate::palel::{SchemaIdentifier, Type, extern_interface_name, schema_type};
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::CTranspile;
+
+use SchemaIdentifier::*;
+
+/// What `palel bindgen` found in a C header.
+#[derive(Debug, PartialEq, Default)]
+pub struct Header {
+    pub functions: Vec<Function>,
+    pub constants: Vec<(String, i128)>,
+    pub enums: Vec<Enum>,
+    pub structs: Vec<String>,
+    /// `typedef <type> name;` of anything but a struct or enum
+    pub typedefs: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub return_type: String,
+    /// Type and, when the prototype names it, name of every parameter
+    pub parameters: Vec<(String, Option<String>)>,
+    pub variadic: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Enum {
+    pub name: String,
+    pub values: Vec<(String, i128)>,
+}
+
+/// Renders an extern interface for the functions of `source`, a C header
+/// included as `include`. Declarations without a palel equivalent are listed
+/// in comments at the end so nothing is dropped silently.
+pub fn bindgen(source: &str, include: &str, links: &[String], toolkit: &CToolKit) -> String {
+    let header = parse_header(source);
+    let types = type_table(&header, toolkit);
+
+    let mut procedures: Vec<String> = vec![];
+    let mut skipped: Vec<String> = vec![];
+    for function in &header.functions {
+        match bind_function(function, &types) {
+            Ok(procedure) => procedures.push(procedure),
+            Err(reason) => skipped.push(format!("{}: {}", function.name, reason)),
+        }
+    }
+    for name in &header.structs {
+        skipped.push(format!("struct {}: structs have no palel equivalent", name));
+    }
+
+    let mut output = String::new();
+    output.push_str(&format!(
+        "# Generated by palel bindgen from {}\n\n",
+        include
+    ));
+    output.push_str(&format!("## Bindings for the C header `{}`.\n", include));
+    output.push_str(&format!("extern \"{}\"", include));
+    if !is_identifier(&extern_interface_name(include)) {
+        output.push_str(&format!(
+            " as {}",
+            identifier_from(&extern_interface_name(include))
+        ));
+    }
+    for link in links {
+        output.push_str(&format!(" link \"{}\"", link));
+    }
+    output.push_str(" do\n");
+    for procedure in &procedures {
+        output.push_str(&format!("    {}\n", procedure));
+    }
+    output.push_str("end\n");
+
+    if !header.constants.is_empty() {
+        output.push_str("\n# constants\n");
+        for (name, value) in &header.constants {
+            output.push_str(&format!("# {} = {}\n", name, value));
+        }
+    }
+    for enumeration in &header.enums {
+        output.push_str(&format!("\n# enum {}, passed as Int32\n", enumeration.name));
+        for (name, value) in &enumeration.values {
+            output.push_str(&format!("# {} = {}\n", name, value));
+        }
+    }
+    if !skipped.is_empty() {
+        output.push_str("\n# skipped\n");
+        for reason in &skipped {
+            output.push_str(&format!("# {}\n", reason));
+        }
+    }
+    output
+}
+
+/// C type names and the palel type each one is read as, derived from
+/// `CToolKit::transpile_type` so bindings and transpiled code agree.
+fn type_table(header: &Header, toolkit: &CToolKit) -> Vec<(String, SchemaIdentifier)> {
+    let mut types: Vec<(String, SchemaIdentifier)> = vec![];
+    let identifiers = [
+        Int8, Int16, Int32, Int64, UInt8, UInt16, UInt32, UInt64, Size, Float32, Float64, Char,
+        CharSeq,
+    ];
+    for identifier in identifiers {
+        let typ = Type::Dim(schema_type(identifier.clone()));
+        if let CTranspile::Ok(Some(ctype), _) = toolkit.transpile_type(&typ) {
+            types.push((c_type_name(&ctype), identifier));
+        }
+    }
+    // C's own integer types, `long` as on LP64 platforms. A plain `int` is
+    // read as Int32 even though Bool is transpiled to it.
+    let builtin = [
+        ("signed char", Int8),
+        ("unsigned char", UInt8),
+        ("short", Int16),
+        ("unsigned short", UInt16),
+        ("int", Int32),
+        ("signed int", Int32),
+        ("unsigned", UInt32),
+        ("unsigned int", UInt32),
+        ("long", Int64),
+        ("unsigned long", UInt64),
+        ("long long", Int64),
+        ("unsigned long long", UInt64),
+        ("bool", Bool),
+        ("_Bool", Bool),
+    ];
+    for (name, identifier) in builtin {
+        types.push((name.to_string(), identifier));
+    }
+    for enumeration in &header.enums {
+        types.push((enumeration.name.clone(), Int32));
+    }
+    for (name, target) in &header.typedefs {
+        if let Some(identifier) = lookup_type(&types, target) {
+            types.push((name.clone(), identifier));
+        }
+    }
+    types
+}
+
+fn c_type_name(ctype: &CType) -> String {
+    if ctype.is_pointer {
+        return format!("{} *", ctype.name);
+    }
+    ctype.name.clone()
+}
+
+fn lookup_type(types: &[(String, SchemaIdentifier)], c_type: &str) -> Option<SchemaIdentifier> {
+    let normalized = normalize_type(c_type);
+    types
+        .iter()
+        .find(|(name, _)| *name == normalized)
+        .map(|(_, identifier)| identifier.clone())
+}
+
+/// Drops qualifiers and writes pointers as ` *` so `const char*` and
+/// `char const *` both become `char *`.
+fn normalize_type(c_type: &str) -> String {
+    let spaced = c_type.replace('*', " * ");
+    let words: Vec<&str> = spaced
+        .split_whitespace()
+        .filter(|w| !matches!(*w, "const" | "volatile" | "restrict" | "extern" | "static"))
+        .filter(|w| !matches!(*w, "inline" | "__restrict" | "__inline"))
+        .collect();
+    let mut output = String::new();
+    for word in words {
+        if word == "*" {
+            output.push_str(" *");
+        } else {
+            if !output.is_empty() {
+                output.push(' ');
+            }
+            output.push_str(word);
+        }
+    }
+    output.replace("enum ", "")
+}
+
+fn bind_function(
+    function: &Function,
+    types: &[(String, SchemaIdentifier)],
+) -> Result<String, String> {
+    if !is_identifier(&function.name) {
+        return Err("the name is not a palel identifier".to_string());
+    }
+    if function.variadic {
+        return Err("variadic functions are not supported".to_string());
+    }
+    let mut parameters: Vec<String> = vec![];
+    for (idx, (c_type, name)) in function.parameters.iter().enumerate() {
+        let typ = match lookup_type(types, c_type) {
+            Some(typ) => typ,
+            None => {
+                return Err(format!(
+                    "parameter type {} has no palel equivalent",
+                    normalize_type(c_type)
+                ));
+            }
+        };
+        let name = match name {
+            Some(name) => identifier_from(name),
+            None => format!("arg{}", idx),
+        };
+        parameters.push(format!("{} {}", name, typ.to_string()));
+    }
+    let mut procedure = format!("proc {}({})", function.name, parameters.join(", "));
+    if normalize_type(&function.return_type) != "void" {
+        match lookup_type(types, &function.return_type) {
+            Some(typ) => procedure.push_str(&format!(" {}", typ.to_string())),
+            None => {
+                return Err(format!(
+                    "return type {} has no palel equivalent",
+                    normalize_type(&function.return_type)
+                ));
+            }
+        }
+    }
+    Ok(procedure)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A palel identifier for a C name, `Width` becomes `width`.
+fn identifier_from(name: &str) -> String {
+    let mut output: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if let Some(first) = output.get(0..1) {
+        output.replace_range(0..1, &first.to_ascii_lowercase());
+    }
+    if !is_identifier(&output) {
+        output = format!("c{}", output);
+    }
+    output
+}
+
+pub fn parse_header(source: &str) -> Header {
+    let mut header = Header::default();
+    let mut code = String::new();
+    let mut cplusplus = 0;
+    let mut depth = 0;
+    for line in strip_comments(source).replace("\\\n", " ").lines() {
+        let line = line.trim();
+        if let Some(directive) = line.strip_prefix('#') {
+            let words: Vec<&str> = directive.split_whitespace().collect();
+            match words.as_slice() {
+                ["ifdef", "__cplusplus", ..] if cplusplus == 0 => cplusplus = 1,
+                ["if" | "ifdef" | "ifndef", ..] if cplusplus > 0 => depth += 1,
+                ["endif", ..] if cplusplus > 0 && depth > 0 => depth -= 1,
+                ["endif", ..] if cplusplus > 0 => cplusplus = 0,
+                ["define", name, value @ ..] if !name.contains('(') => {
+                    if let Some(value) = parse_integer(&value.join(" ")) {
+                        header.constants.push((name.to_string(), value));
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if cplusplus == 0 {
+            code.push_str(line);
+            code.push('\n');
+        }
+    }
+    for declaration in split_declarations(&code) {
+        parse_declaration(&declaration, &mut header);
+    }
+    header
+}
+
+fn strip_comments(source: &str) -> String {
+    let mut output = String::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("/*") {
+            rest = match after.find("*/") {
+                Some(end) => &after[end + 2..],
+                None => "",
+            };
+            output.push(' ');
+        } else if rest.starts_with("//") {
+            rest = match rest.find('\n') {
+                Some(end) => &rest[end..],
+                None => "",
+            };
+        } else {
+            let c = rest.chars().next().unwrap_or(' ');
+            output.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    output
+}
+
+/// Splits top level declarations on `;`, a function definition ends with its
+/// closing brace instead.
+fn split_declarations(code: &str) -> Vec<String> {
+    let mut declarations = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in code.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        current.push(c);
+        let definition = c == '}' && depth == 0 && is_function_definition(&current);
+        if (c == ';' && depth == 0) || definition {
+            declarations.push(current.split_whitespace().collect::<Vec<_>>().join(" "));
+            current.clear();
+        }
+    }
+    declarations
+}
+
+fn is_function_definition(declaration: &str) -> bool {
+    match (declaration.find(')'), declaration.find('{')) {
+        (Some(paren), Some(brace)) => paren < brace && !declaration.starts_with("typedef"),
+        _ => false,
+    }
+}
+
+fn parse_declaration(declaration: &str, header: &mut Header) {
+    let declaration = declaration.trim_end_matches(';').trim();
+    if declaration.is_empty() || is_function_definition(declaration) {
+        return;
+    }
+    let typedef = declaration.strip_prefix("typedef ");
+    let body = typedef.unwrap_or(declaration);
+    if body.starts_with("enum") && body.contains('{') {
+        let name = aggregate_name(body, typedef.is_some(), "enum");
+        let values = parse_enumerators(body);
+        header.enums.push(Enum { name, values });
+        return;
+    }
+    if body.starts_with("struct") || body.starts_with("union") {
+        let keyword = if body.starts_with("struct") {
+            "struct"
+        } else {
+            "union"
+        };
+        let name = aggregate_name(body, typedef.is_some(), keyword);
+        if !header.structs.contains(&name) && (body.contains('{') || typedef.is_some()) {
+            header.structs.push(name);
+        }
+        return;
+    }
+    if let Some(body) = typedef {
+        // function pointer typedefs are left out like other unmappable types
+        if let Some((target, name)) = split_name(body).filter(|_| !body.contains('(')) {
+            header.typedefs.push((name, target));
+        }
+        return;
+    }
+    if let Some(function) = parse_function(declaration) {
+        header.functions.push(function);
+    }
+}
+
+/// `typedef struct point { ... } point_t` is named `point_t`, `struct point
+/// { ... }` is named `point`.
+fn aggregate_name(body: &str, typedef: bool, keyword: &str) -> String {
+    if typedef {
+        if let Some(end) = body.rfind('}') {
+            return body[end + 1..].trim().to_string();
+        }
+        return body.split_whitespace().last().unwrap_or("").to_string();
+    }
+    body.trim_start_matches(keyword)
+        .split('{')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+fn parse_enumerators(body: &str) -> Vec<(String, i128)> {
+    let start = body.find('{').map(|i| i + 1).unwrap_or(0);
+    let end = body.rfind('}').unwrap_or(body.len());
+    let mut values = vec![];
+    let mut next = 0;
+    for enumerator in body[start..end].split(',') {
+        let mut parts = enumerator.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        if name.is_empty() {
+            continue;
+        }
+        if let Some(value) = parts.next() {
+            let resolved = parse_integer(value).or_else(|| {
+                let value = value.trim();
+                values
+                    .iter()
+                    .find(|(other, _)| other == value)
+                    .map(|(_, v)| *v)
+            });
+            if let Some(value) = resolved {
+                next = value;
+            }
+        }
+        values.push((name.to_string(), next));
+        next += 1;
+    }
+    values
+}
+
+fn parse_function(declaration: &str) -> Option<Function> {
+    let open = declaration.find('(')?;
+    let close = declaration.rfind(')')?;
+    let (return_type, name) = split_name(&declaration[..open])?;
+    let mut function = Function {
+        name,
+        return_type,
+        parameters: vec![],
+        variadic: false,
+    };
+    let parameters = declaration[open + 1..close].trim();
+    if parameters.is_empty() || parameters == "void" {
+        return Some(function);
+    }
+    for parameter in parameters.split(',') {
+        let parameter = parameter.trim();
+        if parameter == "..." {
+            function.variadic = true;
+            continue;
+        }
+        if parameter.contains('(') || parameter.contains('[') {
+            // function pointers and arrays get a type no lookup will match
+            function.parameters.push((parameter.to_string(), None));
+            continue;
+        }
+        match split_name(parameter) {
+            Some((typ, name)) if is_type_name(&typ) => {
+                function.parameters.push((typ, Some(name)));
+            }
+            _ => function.parameters.push((parameter.to_string(), None)),
+        }
+    }
+    Some(function)
+}
+
+/// Splits `const char *name` into the type `const char *` and `name`.
+fn split_name(declaration: &str) -> Option<(String, String)> {
+    let declaration = declaration.trim();
+    let start = declaration
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let name = &declaration[start..];
+    let typ = declaration[..start].trim();
+    if name.is_empty() || typ.is_empty() {
+        return None;
+    }
+    Some((typ.to_string(), name.to_string()))
+}
+
+/// Whether `typ` still names a type once qualifiers are dropped, so that in
+/// `unsigned int` the `int` is not taken for the parameter name.
+fn is_type_name(typ: &str) -> bool {
+    let normalized = normalize_type(typ);
+    !normalized.is_empty() && !matches!(normalized.as_str(), "unsigned" | "signed" | "struct")
+}
+
+fn parse_integer(value: &str) -> Option<i128> {
+    let value = value
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, value),
+    };
+    let digits = digits.trim_end_matches(['u', 'U', 'l', 'L']);
+    let parsed = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+    Some(if negative { -parsed } else { parsed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_task::SrcFile;
+    use crate::palel::Src;
+    use crate::parser::parse;
+    use pretty_assertions::assert_eq;
+
+    const HEADER: &str = r#"
+#ifndef SHAPES_H
+#define SHAPES_H
+#include <stdint.h>
+
+#define SHAPES_MAX 64
+#define SHAPES_MASK 0xFFu
+#define SHAPES_NAME "shapes"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* a point in the plane */
+typedef struct point {
+    int32_t x;
+    int32_t y;
+} point_t;
+
+typedef enum { RED, GREEN = 4, BLUE } color;
+typedef uint32_t shape_id;
+
+// creates a shape and returns its id
+shape_id shapes_create(const char *name, color fill, double scale);
+void shapes_clear(void);
+size_t shapes_count();
+int shapes_move(point_t *point, int dx, int dy);
+int shapes_log(const char *format, ...);
+unsigned int shapes_flags(unsigned int);
+static inline int shapes_twice(int x) { return x * 2; }
+
+#ifdef __cplusplus
+}
+#endif
+#endif
+"#;
+
+    #[test]
+    fn test_parse_header() {
+        let header = parse_header(HEADER);
+        assert_eq!(
+            header.constants,
+            vec![
+                ("SHAPES_MAX".to_string(), 64),
+                ("SHAPES_MASK".to_string(), 255)
+            ]
+        );
+        assert_eq!(
+            header.enums,
+            vec![Enum {
+                name: "color".to_string(),
+                values: vec![
+                    ("RED".to_string(), 0),
+                    ("GREEN".to_string(), 4),
+                    ("BLUE".to_string(), 5)
+                ],
+            }]
+        );
+        assert_eq!(header.structs, vec!["point_t".to_string()]);
+        assert_eq!(
+            header.typedefs,
+            vec![("shape_id".to_string(), "uint32_t".to_string())]
+        );
+        let names: Vec<&str> = header.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "shapes_create",
+                "shapes_clear",
+                "shapes_count",
+                "shapes_move",
+                "shapes_log",
+                "shapes_flags"
+            ]
+        );
+        assert_eq!(
+            header.functions[5].parameters,
+            vec![("unsigned int".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn test_bindgen() {
+        let actual = bindgen(HEADER, "shapes.h", &["shapes".to_string()], &CToolKit {});
+        let expected = r#"# Generated by palel bindgen from shapes.h
+
+## Bindings for the C header `shapes.h`.
+extern "shapes.h" link "shapes" do
+    proc shapes_create(name String, fill Int32, scale Float64) UInt32
+    proc shapes_clear()
+    proc shapes_count() Size
+    proc shapes_flags(arg0 UInt32) UInt32
+end
+
+# constants
+# SHAPES_MAX = 64
+# SHAPES_MASK = 255
+
+# enum color, passed as Int32
+# RED = 0
+# GREEN = 4
+# BLUE = 5
+
+# skipped
+# shapes_move: parameter type point_t * has no palel equivalent
+# shapes_log: variadic functions are not supported
+# struct point_t: structs have no palel equivalent
+"#;
+        assert_eq!(actual, expected);
+
+        let mut src = Src::default();
+        let file = SrcFile {
+            file: "shapes.palel".to_string(),
+            content: actual,
+        };
+        if let Some(err) = parse(&mut src, &file) {
+            panic!("{}", err.message())
+        }
+        assert_eq!(src.interfaces[0].identifier, "shapes");
+        assert_eq!(src.interfaces[0].procedures.len(), 4);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::Path;
+
+use crate::bindgen::bindgen;
+use crate::build_task::write;
+use crate::compilation_error::{CompilationError, FailedToReadSrcFile};
+use crate::toolkit_c::CToolKit;
+
+pub struct BindgenTaskConfig {
+    pub header: String,
+    /// How the generated extern includes the header, the file name of
+    /// `header` when not given
+    pub include: Option<String>,
+    pub links: Vec<String>,
+    /// Written to standard output when not given
+    pub output: Option<String>,
+}
+
+pub fn run_bindgen_task(config: &BindgenTaskConfig) -> Option<Box<dyn CompilationError>> {
+    let source = match fs::read_to_string(&config.header) {
+        Ok(source) => source,
+        Err(_) => {
+            return Some(Box::new(FailedToReadSrcFile {
+                file: config.header.clone(),
+            }));
+        }
+    };
+    let include = match &config.include {
+        Some(include) => include.clone(),
+        None => Path::new(&config.header)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(config.header.clone()),
+    };
+    let output = bindgen(&source, &include, &config.links, &CToolKit {});
+    match &config.output {
+        Some(file) => write(file, &output),
+        None => {
+            print!("{}", output);
+            None
+        }
+    }
+}
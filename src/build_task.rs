@@ -4,27 +4,44 @@ use std::io::Write;
 use std::{fs, path::Path};
 use walkdir::WalkDir;
 
-use crate::c::CSrc;
+use crate::c::{CProgram, CProject, CSrc};
 use crate::compilation_error::{
-    CompilationError, FailedToReadSrcFile, FailedToWriteToFile, NoSourceFiles,
+    CompilationError, DuplicateProgram, FailedToReadSrcFile, FailedToWriteToFile, NoSourceFiles,
+    NothingToExport, UnknownProgram,
 };
 use crate::core::Of;
-use crate::downstream_compiler_c::downstream_compile;
-use crate::palel::Src;
+use crate::downstream_compiler_c::{
+    CCompilerConfig, downstream_archive, downstream_compile, downstream_compile_object,
+    downstream_link_shared,
+};
+use crate::palel::{Program, Src};
 use crate::parser::parse;
-use crate::renderer_c::render;
+use crate::renderer_c::{render, render_exporting, render_header};
 use crate::toolkit_c::CToolKit;
-use crate::transpiler_c::transpile;
+use crate::transpiler_c::{SHARED_HEADER, transpile};
 
 pub struct BuildTaskConfig {
     pub bin_name: String,
+    /// Only build the program whose executable has this name
+    pub bin: Option<String>,
+    /// Build `lib<bin_name>.a` and `lib<bin_name>.so` with a header declaring
+    /// the exported procedures instead of the executables
+    pub lib: bool,
+    pub compiler: CCompilerConfig,
     pub src_dir: String,
+    /// Directories of hand-written C besides `src_dir`, their `.c` files are
+    /// linked into every executable and their headers can be included by
+    /// extern interfaces
+    pub c_dirs: Vec<String>,
     pub dest_dir: String,
 }
 
 pub struct BuildTask {
     pub config: BuildTaskConfig,
     src_files: Vec<SrcFile>,
+    c_files: Vec<String>,
+    /// Every directory with a `.h` file, searched by the C compiler
+    c_include_dirs: Vec<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -44,7 +61,11 @@ pub fn default_build_task_config() -> BuildTaskConfig {
     };
     BuildTaskConfig {
         bin_name: bin_name.unwrap_or(default_bin_name).to_string(),
+        bin: None,
+        lib: false,
+        compiler: CCompilerConfig::default(),
         src_dir: "./src".to_string(),
+        c_dirs: vec![],
         dest_dir: "./build".to_string(),
     }
 }
@@ -53,6 +74,8 @@ pub fn create_build_task(config: BuildTaskConfig) -> BuildTask {
     return BuildTask {
         config: config,
         src_files: Vec::new(),
+        c_files: Vec::new(),
+        c_include_dirs: Vec::new(),
     };
 }
 
@@ -67,7 +90,8 @@ pub fn run_build_task(task: &mut BuildTask) -> Option<Box<dyn CompilationError>>
 }
 
 fn load(task: &mut BuildTask) -> Option<Box<dyn CompilationError>> {
-    let src_dir = Path::new(task.config.src_dir.as_str());
+    let src_root = task.config.src_dir.clone();
+    let src_dir = Path::new(src_root.as_str());
     for entry in WalkDir::new(src_dir)
         .into_iter()
         .filter_map(Result::ok)
@@ -80,6 +104,7 @@ fn load(task: &mut BuildTask) -> Option<Box<dyn CompilationError>> {
             .to_string_lossy()
             .into_owned();
         if !file_name.ends_with(".palel") {
+            add_c_file(task, entry.path());
             continue;
         }
         let file_contents = match fs::read_to_string(entry.path()) {
@@ -96,6 +121,19 @@ fn load(task: &mut BuildTask) -> Option<Box<dyn CompilationError>> {
         });
     }
 
+    for dir in task.config.c_dirs.clone() {
+        if !Path::new(&dir).is_dir() {
+            return Some(Box::new(FailedToReadSrcFile { file: dir }));
+        }
+        for entry in WalkDir::new(&dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+        {
+            add_c_file(task, entry.path());
+        }
+    }
+
     if task.src_files.is_empty() {
         Some(Box::new(NoSourceFiles {
             dir: task.config.src_dir.clone(),
@@ -105,26 +143,293 @@ fn load(task: &mut BuildTask) -> Option<Box<dyn CompilationError>> {
     }
 }
 
-fn execute(task: &BuildTask) -> Option<Box<dyn CompilationError>> {
+fn add_c_file(task: &mut BuildTask, path: &Path) {
+    let file = path.to_string_lossy().to_string();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("c") if !task.c_files.contains(&file) => task.c_files.push(file),
+        Some("h") => {
+            let dir = match path.parent() {
+                Some(parent) => parent.to_string_lossy().to_string(),
+                None => ".".to_string(),
+            };
+            if !task.c_include_dirs.contains(&dir) {
+                task.c_include_dirs.push(dir);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Loads and parses every source file of the task without transpiling it.
+pub fn load_src(task: &mut BuildTask) -> Of<Src> {
+    if let Some(err) = load(task) {
+        return Of::Error(err);
+    }
+    parse_src(task)
+}
+
+/// The source files `load_src` read into `task`.
+pub fn src_files(task: &BuildTask) -> &Vec<SrcFile> {
+    &task.src_files
+}
+
+fn parse_src(task: &BuildTask) -> Of<Src> {
     let mut src = Src::default();
     for file in &task.src_files {
         if let Some(err) = parse(&mut src, &file) {
-            return Some(err);
+            return Of::Error(err);
         }
     }
+    Of::Ok(src)
+}
+
+/// Builds the executables, or the library, of the sources already loaded into
+/// `task`.
+pub fn execute(task: &BuildTask) -> Option<Box<dyn CompilationError>> {
+    let src = match parse_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Some(err),
+    };
     let toolkit = CToolKit {};
-    let result = match transpile(&src, &toolkit) {
+    let project = match transpile(&src, &toolkit) {
         Of::Ok(tp) => tp,
         Of::Error(err) => return Some(err),
     };
-    let output_file = format!("{}/code/main.c", task.config.dest_dir);
-    if let Some(err) = write(&output_file, &result) {
+    if task.config.lib && project.library.prototypes.is_empty() {
+        return Some(Box::new(NothingToExport {}));
+    }
+    let code_dir = format!("{}/code", task.config.dest_dir);
+    let names = match binary_names(&task.config, &src.programs) {
+        Of::Ok(names) => names,
+        Of::Error(err) => return Some(err),
+    };
+    let programs: Vec<(String, &CProgram)> = names
+        .into_iter()
+        .zip(project.programs.iter())
+        .filter(|(bin, _)| match &task.config.bin {
+            Some(selected) => selected == bin,
+            None => true,
+        })
+        .collect();
+    if let Some(selected) = &task.config.bin
+        && programs.is_empty()
+    {
+        return Some(Box::new(UnknownProgram {
+            name: selected.clone(),
+        }));
+    }
+
+    let libraries = link_libraries(&src);
+    let mut compiler = c_compiler(task);
+    if task.config.lib {
+        // only the procedures of the generated header leave the library
+        compiler.flags.push("-fvisibility=hidden".to_string());
+    }
+    let objects = match compile_objects(task, &compiler, &project) {
+        Of::Ok(objects) => objects,
+        Of::Error(err) => return Some(err),
+    };
+
+    if task.config.lib {
+        return build_library(
+            &task.config,
+            &compiler,
+            &project.library,
+            &objects,
+            &libraries,
+        );
+    }
+
+    for (bin, program) in programs {
+        let output_file = format!("{}/{}.c", code_dir, bin);
+        if let Some(err) = write(&output_file, &render(&program.src)) {
+            return Some(err);
+        }
+        let mut files = vec![output_file];
+        files.extend(objects.iter().cloned());
+        let bin_file = format!("{}/{}", task.config.dest_dir, bin);
+        if let Some(err) = downstream_compile(&compiler, &files, &bin_file, &libraries) {
+            return Some(err);
+        }
+    }
+    None
+}
+
+/// Builds the test harness of the sources already loaded into `task` and
+/// returns the path of its executable, `None` when there are no tests.
+pub fn build_tests(task: &BuildTask) -> Of<Option<String>> {
+    build_harness(task, "tests", |project| &project.tests)
+}
+
+/// Builds the bench harness of the sources already loaded into `task` and
+/// returns the path of its executable, `None` when there are no benches.
+pub fn build_benches(task: &BuildTask) -> Of<Option<String>> {
+    build_harness(task, "benches", |project| &project.benches)
+}
+
+/// Builds `<bin_name>.<kind>` from the harness `harness` selects of the
+/// transpiled project.
+fn build_harness(
+    task: &BuildTask,
+    kind: &str,
+    harness: fn(&CProject) -> &Option<CSrc>,
+) -> Of<Option<String>> {
+    let src = match parse_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Of::Error(err),
+    };
+    let project = match transpile(&src, &CToolKit {}) {
+        Of::Ok(tp) => tp,
+        Of::Error(err) => return Of::Error(err),
+    };
+    let harness = match harness(&project) {
+        Some(harness) => harness,
+        None => return Of::Ok(None),
+    };
+    let compiler = c_compiler(task);
+    let objects = match compile_objects(task, &compiler, &project) {
+        Of::Ok(objects) => objects,
+        Of::Error(err) => return Of::Error(err),
+    };
+    let name = format!("{}.{}", task.config.bin_name, kind);
+    let output_file = format!("{}/code/{}.c", task.config.dest_dir, name);
+    if let Some(err) = write(&output_file, &render(harness)) {
+        return Of::Error(err);
+    }
+    let mut files = vec![output_file];
+    files.extend(objects);
+    let bin_file = format!("{}/{}", task.config.dest_dir, name);
+    match downstream_compile(&compiler, &files, &bin_file, &link_libraries(&src)) {
+        Some(err) => Of::Error(err),
+        None => Of::Ok(Some(bin_file)),
+    }
+}
+
+/// The libraries the extern interfaces link, each once.
+fn link_libraries(src: &Src) -> Vec<String> {
+    let mut libraries: Vec<String> = vec![];
+    for library in src.interfaces.iter().flat_map(|i| i.links.iter()) {
+        if !libraries.contains(library) {
+            libraries.push(library.clone());
+        }
+    }
+    libraries
+}
+
+/// The configured compiler, also searching the directories of the C headers
+/// of the project.
+fn c_compiler(task: &BuildTask) -> CCompilerConfig {
+    let mut compiler = task.config.compiler.clone();
+    compiler
+        .include_dirs
+        .extend(task.c_include_dirs.iter().cloned());
+    compiler
+}
+
+/// Compiles the hand-written C files and the shared procedures into the
+/// object files every executable is linked with. The procedures exported by
+/// the library of `project` stay visible when everything else is hidden.
+fn compile_objects(
+    task: &BuildTask,
+    compiler: &CCompilerConfig,
+    project: &CProject,
+) -> Of<Vec<String>> {
+    let shared = &project.shared;
+    let code_dir = format!("{}/code", task.config.dest_dir);
+    let mut objects: Vec<String> = vec![];
+    for file in &task.c_files {
+        let object_file = format!("{}/c/{}", code_dir, c_object_name(file));
+        if let Some(parent) = Path::new(&object_file).parent()
+            && fs::create_dir_all(parent).is_err()
+        {
+            return Of::Error(Box::new(FailedToWriteToFile { file: object_file }));
+        }
+        if let Some(err) = downstream_compile_object(compiler, file, &object_file) {
+            return Of::Error(err);
+        }
+        objects.push(object_file);
+    }
+    if !shared.functions.is_empty() {
+        let header_file = format!("{}/{}", code_dir, SHARED_HEADER);
+        let header = render_header(shared, "PALEL_SHARED_H");
+        if let Some(err) = write(&header_file, &header) {
+            return Of::Error(err);
+        }
+        let shared_file = format!("{}/palel.shared.c", code_dir);
+        let content = render_exporting(shared, &project.library.prototypes);
+        if let Some(err) = write(&shared_file, &content) {
+            return Of::Error(err);
+        }
+        let object_file = format!("{}/palel.shared.o", code_dir);
+        if let Some(err) = downstream_compile_object(compiler, &shared_file, &object_file) {
+            return Of::Error(err);
+        }
+        objects.push(object_file);
+    }
+    Of::Ok(objects)
+}
+
+fn build_library(
+    config: &BuildTaskConfig,
+    compiler: &CCompilerConfig,
+    library: &CSrc,
+    objects: &[String],
+    libraries: &[String],
+) -> Option<Box<dyn CompilationError>> {
+    let header_file = format!("{}/{}.h", config.dest_dir, config.bin_name);
+    let guard = format!("{}_H", header_guard(&config.bin_name));
+    if let Some(err) = write(&header_file, &render_header(library, &guard)) {
+        return Some(err);
+    }
+    let archive_file = format!("{}/lib{}.a", config.dest_dir, config.bin_name);
+    if let Some(err) = downstream_archive(compiler, objects, &archive_file) {
         return Some(err);
     }
-    downstream_compile(&output_file, &task.config.bin_name)
+    let shared_file = format!("{}/lib{}.so", config.dest_dir, config.bin_name);
+    downstream_link_shared(compiler, objects, &shared_file, libraries)
+}
+
+/// `./csrc/fast/loop.c` is compiled into `csrc_fast_loop.o`.
+fn c_object_name(file: &str) -> String {
+    let file = file.trim_start_matches("./");
+    let stem = file.strip_suffix(".c").unwrap_or(file);
+    format!("{}.o", stem.replace(['/', '\\', '.'], "_"))
+}
+
+/// `my-lib` becomes `MY_LIB`.
+fn header_guard(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+/// Unnamed programs are built into an executable named after the project.
+fn binary_name(config: &BuildTaskConfig, name: &Option<String>) -> String {
+    match name {
+        Some(name) => name.clone(),
+        None => config.bin_name.clone(),
+    }
 }
 
-fn write(output_file: &String, src: &CSrc) -> Option<Box<dyn CompilationError>> {
+/// The executable of each of `programs`. An unnamed program and one named
+/// after the project would be built into the same file, so that is an error
+/// just like two programs of the same name.
+pub fn binary_names(config: &BuildTaskConfig, programs: &[Program]) -> Of<Vec<String>> {
+    let mut names: Vec<String> = vec![];
+    for program in programs {
+        let name = binary_name(config, &program.name);
+        if names.contains(&name) {
+            return Of::Error(Box::new(DuplicateProgram { name: Some(name) }));
+        }
+        names.push(name);
+    }
+    Of::Ok(names)
+}
+
+pub fn write(output_file: &String, content: &String) -> Option<Box<dyn CompilationError>> {
     let err = FailedToWriteToFile {
         file: output_file.to_string(),
     };
@@ -145,9 +450,42 @@ fn write(output_file: &String, src: &CSrc) -> Option<Box<dyn CompilationError>>
         Err(_) => return Some(Box::new(err)),
     };
 
-    if let Err(_) = file.write_all(render(src).as_bytes()) {
+    if file.write_all(content.as_bytes()).is_err() {
         return Some(Box::new(err));
     } else {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_output_names() {
+        assert_eq!(c_object_name("./csrc/fast/loop.c"), "csrc_fast_loop.o");
+        assert_eq!(c_object_name("src/v1.2.c"), "src_v1_2.o");
+        assert_eq!(header_guard("my-lib"), "MY_LIB");
+    }
+
+    #[test]
+    fn test_binary_names() {
+        let mut config = default_build_task_config();
+        config.bin_name = "rv16".to_string();
+        let program = |name: Option<&str>| Program {
+            name: name.map(|n| n.to_string()),
+            module: "main".to_string(),
+            ..test_support::program(vec![], vec![])
+        };
+        match binary_names(&config, &[program(None), program(Some("server"))]) {
+            Of::Ok(names) => assert_eq!(names, vec!["rv16", "server"]),
+            Of::Error(err) => panic!("{}", err.message()),
+        }
+        match binary_names(&config, &[program(None), program(Some("rv16"))]) {
+            Of::Ok(_) => panic!("both programs would be built into build/rv16"),
+            Of::Error(err) => assert_eq!(err.message(), "program 'rv16' is defined more than once"),
+        }
+    }
+}
@@ -1,45 +1,154 @@
 #[derive(Debug, PartialEq)]
 pub struct CSrc {
     pub includes: Vec<CInclude>,
+    pub prototypes: Vec<CPrototype>,
+    /// Functions of the toolkit the code calls, defined `static` in every
+    /// file that needs them
+    pub helpers: Vec<CFunction>,
     pub functions: Vec<CFunction>,
 }
 
+/// The procedures shared by every program and one source per program, each
+/// program is compiled into its own executable.
+#[derive(Debug, PartialEq)]
+pub struct CProject {
+    pub shared: CSrc,
+    /// The header of a library build, prototypes of the exported procedures
+    /// and the includes their types need
+    pub library: CSrc,
+    pub programs: Vec<CProgram>,
+    /// The test harness, `None` when the project has no tests
+    pub tests: Option<CSrc>,
+    /// The benchmark harness, `None` when the project has no benches
+    pub benches: Option<CSrc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CProgram {
+    pub name: Option<String>,
+    pub src: CSrc,
+}
+
 #[derive(Debug, PartialEq, Default)]
 pub struct CSrcPatch {
     pub includes: Vec<CInclude>,
+    pub prototypes: Vec<CPrototype>,
+    pub helpers: Vec<CFunction>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CInclude {
     pub file: String,
+    /// `#include "file"` relative to the including file instead of `<file>`
+    pub local: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct CFunction {
     pub name: String,
+    pub parameters: Vec<CParameter>,
     pub return_type: CType,
     pub block: CBlock,
 }
 
-#[derive(Debug, PartialEq)]
+/// Declares a function defined outside of the generated code.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CPrototype {
+    pub name: String,
+    pub parameters: Vec<CParameter>,
+    pub return_type: CType,
+}
+
+impl CFunction {
+    pub fn prototype(&self) -> CPrototype {
+        CPrototype {
+            name: self.name.clone(),
+            parameters: self.parameters.clone(),
+            return_type: self.return_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CParameter {
+    pub name: String,
+    pub typ: CType,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct CType {
     pub name: String,
     pub is_pointer: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct CBlock {
     pub statements: Vec<CStatement>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum CStatement {
     FunctionCall(CFunctionCall),
     Return(CReturn),
     Variable(CVariableDeclaration),
+    If(CIf),
+    Line(CLine),
+    While(CWhile),
+    Assignment(CAssignment),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CWhile {
+    pub condition: CExpression,
+    pub block: CBlock,
+}
+
+impl CWhile {
+    pub fn to_statement(self) -> CStatement {
+        CStatement::While(self)
+    }
+}
+
+/// Stores `value` in the variable `name` declared before.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CAssignment {
+    pub name: String,
+    pub value: CExpression,
+}
+
+impl CAssignment {
+    pub fn to_statement(self) -> CStatement {
+        CStatement::Assignment(self)
+    }
+}
+
+/// `#line` attributes the statements after it to a line of a palel source
+/// file, for the diagnostics, debuggers and coverage of the C compiler.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CLine {
+    pub line: usize,
+    pub file: String,
+}
+
+impl CLine {
+    pub fn to_statement(self) -> CStatement {
+        CStatement::Line(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CIf {
+    pub condition: CExpression,
+    pub block: CBlock,
+}
+
+impl CIf {
+    pub fn to_statement(self) -> CStatement {
+        CStatement::If(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct CReturn {
     pub value: Option<CExpression>,
 }
@@ -50,7 +159,7 @@ impl CReturn {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct CFunctionCall {
     pub function_name: String,
     pub arguments: Vec<CExpression>,
@@ -60,9 +169,13 @@ impl CFunctionCall {
     pub fn to_statement(self) -> CStatement {
         CStatement::FunctionCall(self)
     }
+
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Call(self)
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum CLiteral {
     String(String),
     Number(String),
@@ -75,13 +188,98 @@ impl CLiteral {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum CExpression {
     Literal(CLiteral),
     Variable(String),
+    Cast(CCast),
+    Conditional(CConditional),
+    Binary(CBinary),
+    Call(CFunctionCall),
+    Index(CIndex),
+    Member(CMember),
+    Unary(CUnary),
+    /// `{0}`, the values of a struct or array in declaration order
+    Initializer(Vec<CExpression>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CCast {
+    pub typ: CType,
+    pub expression: Box<CExpression>,
+}
+
+impl CCast {
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Cast(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CConditional {
+    pub condition: Box<CExpression>,
+    pub then: Box<CExpression>,
+    pub otherwise: Box<CExpression>,
+}
+
+impl CConditional {
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Conditional(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CBinary {
+    pub left: Box<CExpression>,
+    pub operator: String,
+    pub right: Box<CExpression>,
+}
+
+impl CBinary {
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Binary(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CIndex {
+    pub expression: Box<CExpression>,
+    pub index: Box<CExpression>,
+}
+
+impl CIndex {
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Index(self)
+    }
+}
+
+/// A field of a struct value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CMember {
+    pub expression: Box<CExpression>,
+    pub member: String,
+}
+
+impl CMember {
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Member(self)
+    }
+}
+
+/// A prefix operator, `&` for the address of a variable.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CUnary {
+    pub operator: String,
+    pub expression: Box<CExpression>,
+}
+
+impl CUnary {
+    pub fn to_expression(self) -> CExpression {
+        CExpression::Unary(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct CVariableDeclaration {
     pub name: String,
     pub var_type: CType,
@@ -100,3 +298,10 @@ pub fn int_type() -> CType {
         is_pointer: false,
     }
 }
+
+pub fn void_type() -> CType {
+    CType {
+        name: "void".to_string(),
+        is_pointer: false,
+    }
+}
@@ -1,4 +1,5 @@
-use crate::palel::{ExpressionType, Type};
+use crate::palel::{Edit, ExpressionType, SchemaType, Span, Type, TypePostfix, schema_type};
+use crate::type_checking::{can_implicitly_convert, is_valid_cast};
 
 const NOOP_ERROR: i32 = 1;
 const DISK_ERROR: i32 = 2;
@@ -13,6 +14,42 @@ pub trait CompilationError {
     fn exit_code(&self) -> i32 {
         TRANSPILE_ERROR
     }
+
+    /// Changes of the source that would fix the error.
+    fn suggestions(&self) -> Vec<Suggestion> {
+        vec![]
+    }
+
+    /// The message followed by a `help:` note for every suggestion.
+    fn report(&self) -> String {
+        let mut report = self.message();
+        for suggestion in self.suggestions() {
+            report.push_str(&format!("\nhelp: {}", suggestion.message));
+        }
+        report
+    }
+}
+
+/// A change of the source that would fix an error. It comes with an edit
+/// of the file of the error when it can be made without asking, `palel fix`
+/// makes those.
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    pub message: String,
+    pub edit: Option<Edit>,
+}
+
+/// Where a value is checked against the type it must have.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ValueSite {
+    /// A variable declaration, with where its type is written when it has one
+    Declaration(Option<Span>),
+    Return,
+    /// The value a program returns as its exit status
+    ExitStatus,
+    Argument,
+    /// The condition of an `assert`
+    Condition,
 }
 
 #[derive(Debug, PartialEq)]
@@ -68,6 +105,20 @@ impl CompilationError for FailedToWriteToFile {
     }
 }
 
+pub struct FailedToReadInput {
+    pub reason: String,
+}
+
+impl CompilationError for FailedToReadInput {
+    fn message(&self) -> String {
+        format!("failed to read the input: {}", self.reason)
+    }
+
+    fn exit_code(&self) -> i32 {
+        DISK_ERROR
+    }
+}
+
 pub struct FailedToParseSrcFile {
     pub file: String,
 }
@@ -82,11 +133,106 @@ impl CompilationError for FailedToParseSrcFile {
     }
 }
 
-pub struct DownstreamCompileFailed {}
+pub struct FailedToParseProjectConfig {
+    pub file: String,
+    pub reason: String,
+}
+
+impl CompilationError for FailedToParseProjectConfig {
+    fn message(&self) -> String {
+        format!("failed to read {}: {}", self.file, self.reason.trim())
+    }
+
+    fn exit_code(&self) -> i32 {
+        PARSE_ERROR
+    }
+}
+
+/// The C compiler, or the archiver bundling its objects, ran but failed, its
+/// output is kept to be shown to the user.
+pub struct DownstreamCompileFailed {
+    /// `C compiler` or `archiver`
+    pub tool: String,
+    pub command: String,
+    /// `exit status 1`, or how the compiler was stopped otherwise
+    pub status: String,
+    pub stdout: String,
+    pub stderr: String,
+}
 
 impl CompilationError for DownstreamCompileFailed {
     fn message(&self) -> String {
-        format!("downstream compiler failed")
+        let mut message = String::new();
+        message.push_str(&format!(
+            "the {} failed on the code generated from your palel sources\n",
+            self.tool
+        ));
+        message.push_str(&format!("command: {}\n", self.command));
+        message.push_str(&format!("status: {}\n", self.status));
+        for (name, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !output.trim().is_empty() {
+                message.push_str(&format!("--- {} {} ---\n", self.tool, name));
+                message.push_str(output.trim_end());
+                message.push('\n');
+            }
+        }
+        message
+    }
+
+    fn exit_code(&self) -> i32 {
+        DOWNSTREAM_ERROR
+    }
+}
+
+pub struct DownstreamCompilerNotFound {
+    pub compiler: String,
+}
+
+impl CompilationError for DownstreamCompilerNotFound {
+    fn message(&self) -> String {
+        format!(
+            "C compiler '{}' was not found, install it or choose another one with --cc or CC",
+            self.compiler
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        DOWNSTREAM_ERROR
+    }
+}
+
+/// `ar`, which bundles the objects of `palel build --lib` into the static
+/// library, is not on the PATH.
+pub struct DownstreamArchiverNotFound {
+    pub archiver: String,
+}
+
+impl CompilationError for DownstreamArchiverNotFound {
+    fn message(&self) -> String {
+        format!(
+            "archiver '{}' was not found, install it to build a static library",
+            self.archiver
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        DOWNSTREAM_ERROR
+    }
+}
+
+/// gcov could not read the coverage data of `palel test --coverage`.
+pub struct CoverageToolFailed {
+    pub command: String,
+    pub reason: String,
+}
+
+impl CompilationError for CoverageToolFailed {
+    fn message(&self) -> String {
+        format!(
+            "failed to read the coverage data with '{}': {}",
+            self.command,
+            self.reason.trim()
+        )
     }
 
     fn exit_code(&self) -> i32 {
@@ -121,6 +267,7 @@ impl CompilationError for CouldNotTranspileType {
 pub struct IncompatibleTypes {
     pub expected: Type,
     pub actual: ExpressionType,
+    pub site: ValueSite,
 }
 
 impl CompilationError for IncompatibleTypes {
@@ -135,6 +282,60 @@ impl CompilationError for IncompatibleTypes {
     fn exit_code(&self) -> i32 {
         TYPE_ERROR
     }
+
+    /// Allowing null in a declaration or return type, or a cast of a value
+    /// that would narrow it implicitly.
+    fn suggestions(&self) -> Vec<Suggestion> {
+        let expected = match &self.expected {
+            Type::Dim(schema) | Type::Ref(schema) => schema,
+            Type::Addr(_) => return vec![],
+        };
+        let mut nullable = expected.clone();
+        nullable.postfix = TypePostfix::Opt;
+        let allows_null = match &self.actual {
+            ExpressionType::Null => true,
+            ExpressionType::Dim(actual) | ExpressionType::Ref(actual) => {
+                actual.postfix == TypePostfix::Opt && can_implicitly_convert(&nullable, actual)
+            }
+            ExpressionType::Addr(_) => false,
+        };
+        if allows_null && expected.postfix == TypePostfix::None {
+            return match self.site {
+                ValueSite::Declaration(declared_type) => vec![Suggestion {
+                    message: "add ? to the type of the variable to allow null".to_string(),
+                    edit: declared_type.map(|span| Edit {
+                        span,
+                        replacement: nullable.to_string(),
+                    }),
+                }],
+                ValueSite::Return => vec![Suggestion {
+                    message: "add ? to the return type of the procedure to allow null".to_string(),
+                    edit: None,
+                }],
+                ValueSite::ExitStatus | ValueSite::Argument | ValueSite::Condition => vec![],
+            };
+        }
+
+        let actual = match &self.actual {
+            ExpressionType::Dim(actual) | ExpressionType::Ref(actual) => actual,
+            _ => return vec![],
+        };
+        let target = schema_type(expected.identifier.clone());
+        if !is_valid_cast(&target, actual) {
+            return vec![];
+        }
+        // a nullable type takes the null of a value that does not fit
+        let (cast, effect) = match expected.postfix {
+            TypePostfix::Opt => ("as?", "is null when the value does not fit"),
+            _ => ("as!", "clamps the value to the range of the type"),
+        };
+        // a cast changes the value, so it is only suggested and never applied
+        let cast = format!("{} {}", cast, target.to_string());
+        vec![Suggestion {
+            message: format!("cast the value with `{}`, which {}", cast, effect),
+            edit: None,
+        }]
+    }
 }
 
 pub struct TypeNotNullable {
@@ -149,3 +350,737 @@ impl CompilationError for TypeNotNullable {
         TYPE_ERROR
     }
 }
+
+/// `debug:print` was given a value it has no format for.
+pub struct TypeNotPrintable {
+    pub received_type: SchemaType,
+}
+
+impl CompilationError for TypeNotPrintable {
+    fn message(&self) -> String {
+        format!(
+            "debug:print cannot print a {}",
+            self.received_type.to_string()
+        )
+    }
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct UnknownVariable {
+    pub name: String,
+}
+
+impl CompilationError for UnknownVariable {
+    fn message(&self) -> String {
+        format!("could not find variable '{}'", self.name)
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct VariableRedeclared {
+    pub name: String,
+}
+
+impl CompilationError for VariableRedeclared {
+    fn message(&self) -> String {
+        format!(
+            "'{}' is already declared, give the new variable another name",
+            self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct InvalidCast {
+    pub from: ExpressionType,
+    pub to: SchemaType,
+}
+
+impl CompilationError for InvalidCast {
+    fn message(&self) -> String {
+        format!(
+            "cannot cast {} to {}",
+            self.from.to_string(),
+            self.to.to_string()
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+/// An `as` from a floating point type to an integer type. C leaves the result
+/// undefined when the value does not fit, so only the checked and saturating
+/// casts may do it.
+pub struct UncheckedFloatCast {
+    pub from: SchemaType,
+    pub to: SchemaType,
+}
+
+impl CompilationError for UncheckedFloatCast {
+    fn message(&self) -> String {
+        format!(
+            "cannot cast {} to {} with `as`, the value may not fit",
+            self.from.to_string(),
+            self.to.to_string()
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+
+    fn suggestions(&self) -> Vec<Suggestion> {
+        let to = self.to.to_string();
+        vec![
+            Suggestion {
+                message: format!(
+                    "use `as? {}`, which is null when the value does not fit",
+                    to
+                ),
+                edit: None,
+            },
+            Suggestion {
+                message: format!(
+                    "use `as! {}`, which clamps the value to the range of the type",
+                    to
+                ),
+                edit: None,
+            },
+        ]
+    }
+}
+
+pub struct CharNotAscii {
+    pub value: char,
+}
+
+impl CompilationError for CharNotAscii {
+    fn message(&self) -> String {
+        format!(
+            "character literal {:?} does not fit in a Char, use a String instead",
+            self.value
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct IntegerLiteralOutOfRange {
+    pub value: String,
+    pub expected: SchemaType,
+}
+
+impl CompilationError for IntegerLiteralOutOfRange {
+    fn message(&self) -> String {
+        let (min, max) = self.expected.value_range().unwrap_or_default();
+        format!(
+            "integer literal {} does not fit in {}, which holds {} to {}",
+            self.value,
+            self.expected.to_string(),
+            min,
+            max
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct UnknownProcedure {
+    pub name: String,
+}
+
+impl CompilationError for UnknownProcedure {
+    fn message(&self) -> String {
+        format!("could not find procedure '{}'", self.name)
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct DuplicateProcedure {
+    pub module: String,
+    pub name: String,
+}
+
+impl CompilationError for DuplicateProcedure {
+    fn message(&self) -> String {
+        format!(
+            "procedure '{}' is defined more than once in module '{}'",
+            self.name, self.module
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct ArgumentCountMismatch {
+    pub procedure: String,
+    pub expected: usize,
+    pub received: usize,
+}
+
+impl CompilationError for ArgumentCountMismatch {
+    fn message(&self) -> String {
+        format!(
+            "procedure '{}' takes {} arguments, received {}",
+            self.procedure, self.expected, self.received
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct ProcedureHasNoValue {
+    pub procedure: String,
+}
+
+impl CompilationError for ProcedureHasNoValue {
+    fn message(&self) -> String {
+        format!("procedure '{}' does not return a value", self.procedure)
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct InvalidReturn {
+    pub expected: Option<Type>,
+}
+
+impl CompilationError for InvalidReturn {
+    fn message(&self) -> String {
+        match &self.expected {
+            Some(typ) => format!("expected a return value of type {}", typ.to_string()),
+            None => "cannot return a value here".to_string(),
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct MissingReturn {
+    pub procedure: String,
+}
+
+impl CompilationError for MissingReturn {
+    fn message(&self) -> String {
+        format!("procedure '{}' must end with a return", self.procedure)
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct InvalidModuleFile {
+    pub file: String,
+    pub segment: String,
+}
+
+impl CompilationError for InvalidModuleFile {
+    fn message(&self) -> String {
+        format!(
+            "source file '{}' cannot be used as a module, '{}' must start with a lowercase letter followed by letters, digits or _",
+            self.file, self.segment
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct DuplicateModule {
+    pub module: String,
+    pub files: (String, String),
+}
+
+impl CompilationError for DuplicateModule {
+    fn message(&self) -> String {
+        format!(
+            "source files '{}' and '{}' are both module '{}'",
+            self.files.0, self.files.1, self.module
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct UnknownModule {
+    pub module: String,
+    pub path: String,
+}
+
+impl CompilationError for UnknownModule {
+    fn message(&self) -> String {
+        format!(
+            "module '{}' uses '{}' which does not exist",
+            self.module, self.path
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct DuplicateImport {
+    pub module: String,
+    pub alias: String,
+}
+
+impl CompilationError for DuplicateImport {
+    fn message(&self) -> String {
+        format!(
+            "module '{}' imports more than one module named '{}'",
+            self.module, self.alias
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct CyclicImport {
+    pub cycle: Vec<String>,
+}
+
+impl CompilationError for CyclicImport {
+    fn message(&self) -> String {
+        format!("modules import each other: {}", self.cycle.join(" -> "))
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct ProcedureNotPublic {
+    pub name: String,
+}
+
+impl CompilationError for ProcedureNotPublic {
+    fn message(&self) -> String {
+        format!(
+            "procedure '{}' is not public, mark it with pub to use it from other modules",
+            self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct DuplicateProgram {
+    pub name: Option<String>,
+}
+
+impl CompilationError for DuplicateProgram {
+    fn message(&self) -> String {
+        match &self.name {
+            Some(name) => format!("program '{}' is defined more than once", name),
+            None => {
+                "more than one program has no name, name them with program <name> do".to_string()
+            }
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct UnknownProgram {
+    pub name: String,
+}
+
+impl CompilationError for UnknownProgram {
+    fn message(&self) -> String {
+        format!("no program is named '{}'", self.name)
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct DuplicateTest {
+    pub module: String,
+    pub name: String,
+}
+
+impl CompilationError for DuplicateTest {
+    fn message(&self) -> String {
+        format!(
+            "test '{}' is defined more than once in module '{}'",
+            self.name, self.module
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct DuplicateBench {
+    pub module: String,
+    pub name: String,
+}
+
+impl CompilationError for DuplicateBench {
+    fn message(&self) -> String {
+        format!(
+            "bench '{}' is defined more than once in module '{}'",
+            self.name, self.module
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+/// `palel run` without `--bin` in a project of several programs.
+pub struct AmbiguousProgram {
+    pub names: Vec<String>,
+}
+
+impl CompilationError for AmbiguousProgram {
+    fn message(&self) -> String {
+        if self.names.is_empty() {
+            return "there is no program to run".to_string();
+        }
+        format!(
+            "more than one program could be run, choose one with --bin: {}",
+            self.names.join(", ")
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct FailedToRunProgram {
+    pub file: String,
+}
+
+impl CompilationError for FailedToRunProgram {
+    fn message(&self) -> String {
+        format!("failed to run '{}'", self.file)
+    }
+
+    fn exit_code(&self) -> i32 {
+        DISK_ERROR
+    }
+}
+
+pub struct InvalidProgramParameter {
+    pub name: String,
+}
+
+impl CompilationError for InvalidProgramParameter {
+    fn message(&self) -> String {
+        format!(
+            "program parameter '{}' must be a String, a String? placed after the required ones, or a StringList placed last",
+            self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct DuplicateInterface {
+    pub name: String,
+}
+
+impl CompilationError for DuplicateInterface {
+    fn message(&self) -> String {
+        format!("interface '{}' is declared more than once", self.name)
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct DuplicateImplementation {
+    pub name: String,
+}
+
+impl CompilationError for DuplicateImplementation {
+    fn message(&self) -> String {
+        format!("'{}' is implemented more than once", self.name)
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct MissingImplementation {
+    pub name: String,
+}
+
+impl CompilationError for MissingImplementation {
+    fn message(&self) -> String {
+        format!(
+            "'{}' has no implementation, define it with proc {}(...) do or bind it to a C function",
+            self.name, self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct ImplementationMismatch {
+    pub name: String,
+}
+
+impl CompilationError for ImplementationMismatch {
+    fn message(&self) -> String {
+        format!(
+            "the implementation of '{}' does not match the parameters and return type declared by its interface",
+            self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TYPE_ERROR
+    }
+}
+
+pub struct DuplicateExport {
+    pub name: String,
+}
+
+impl CompilationError for DuplicateExport {
+    fn message(&self) -> String {
+        format!(
+            "more than one procedure is exported to C as '{}'",
+            self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct InvalidExport {
+    pub name: String,
+}
+
+impl CompilationError for InvalidExport {
+    fn message(&self) -> String {
+        format!(
+            "'{}' implements an interface and cannot be exported with extern",
+            self.name
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct NothingToExport {}
+
+impl CompilationError for NothingToExport {
+    fn message(&self) -> String {
+        "a library needs at least one procedure marked with pub extern".to_string()
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct InterpreterUnsupported {
+    pub feature: String,
+}
+
+impl CompilationError for InterpreterUnsupported {
+    fn message(&self) -> String {
+        format!(
+            "the interpreter does not support {}, build the program with the C backend instead",
+            self.feature
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        TRANSPILE_ERROR
+    }
+}
+
+pub struct CallDepthExceeded {
+    pub procedure: String,
+}
+
+impl CompilationError for CallDepthExceeded {
+    fn message(&self) -> String {
+        format!(
+            "calls nested too deeply when calling '{}', a procedure calls itself without end",
+            self.procedure
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        LOGIC_ERROR
+    }
+}
+
+pub struct FailedToParseReplEntry {
+    pub reason: String,
+}
+
+impl CompilationError for FailedToParseReplEntry {
+    fn message(&self) -> String {
+        format!("could not parse the entry\n{}", self.reason.trim_end())
+    }
+
+    fn exit_code(&self) -> i32 {
+        PARSE_ERROR
+    }
+}
+
+pub struct LanguageServerFailed {
+    pub reason: String,
+}
+
+impl CompilationError for LanguageServerFailed {
+    fn message(&self) -> String {
+        format!("the language server stopped: {}", self.reason)
+    }
+
+    fn exit_code(&self) -> i32 {
+        DISK_ERROR
+    }
+}
+
+pub struct FilesNotFormatted {
+    pub files: Vec<String>,
+}
+
+impl CompilationError for FilesNotFormatted {
+    fn message(&self) -> String {
+        format!(
+            "{} file(s) are not formatted, run palel fmt: {}",
+            self.files.len(),
+            self.files.join(", ")
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct UnknownLint {
+    pub name: String,
+    pub known: Vec<String>,
+}
+
+impl CompilationError for UnknownLint {
+    fn message(&self) -> String {
+        format!(
+            "there is no lint named '{}', the lints are: {}",
+            self.name,
+            self.known.join(", ")
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct LintWarningsFound {
+    pub count: usize,
+    /// How many of the warnings `palel lint --fix` can fix
+    pub fixable: usize,
+}
+
+impl CompilationError for LintWarningsFound {
+    fn message(&self) -> String {
+        let mut message = format!("lint found {} warning(s)", self.count);
+        if self.fixable > 0 {
+            message.push_str(&format!(
+                ", {} can be fixed with palel lint --fix",
+                self.fixable
+            ));
+        }
+        message
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct TestsFailed {
+    pub failed: Vec<String>,
+}
+
+impl CompilationError for TestsFailed {
+    fn message(&self) -> String {
+        format!(
+            "{} test(s) failed: {}",
+            self.failed.len(),
+            self.failed.join(", ")
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
+
+pub struct BenchesFailed {
+    pub failed: Vec<String>,
+}
+
+impl CompilationError for BenchesFailed {
+    fn message(&self) -> String {
+        format!(
+            "{} bench(es) failed: {}",
+            self.failed.len(),
+            self.failed.join(", ")
+        )
+    }
+
+    fn exit_code(&self) -> i32 {
+        NOOP_ERROR
+    }
+}
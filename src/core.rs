@@ -4,3 +4,13 @@ pub enum Of<T> {
     Ok(T),
     Error(Box<dyn CompilationError>),
 }
+
+impl<T> Of<T> {
+    /// The error, none when there is a value.
+    pub fn err(self) -> Option<Box<dyn CompilationError>> {
+        match self {
+            Of::Ok(_) => None,
+            Of::Error(err) => Some(err),
+        }
+    }
+}
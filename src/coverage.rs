@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use serde_json::{Deserializer, Value};
+
+use crate::build_task::SrcFile;
+use crate::palel::{Src, file_from_module_name, module_name_from_file};
+
+/// How often the statements of a source file ran.
+#[derive(Debug, PartialEq)]
+pub struct FileCoverage {
+    pub file: String,
+    pub content: String,
+    /// The lines statements start on and how often each ran, in order
+    pub lines: Vec<(usize, u64)>,
+}
+
+impl FileCoverage {
+    pub fn covered(&self) -> usize {
+        self.lines.iter().filter(|(_, count)| *count > 0).count()
+    }
+}
+
+/// How often each line ran by the file its `#line` names, summed over the
+/// JSON documents of gcov in `report`.
+pub fn line_counts(report: &str) -> HashMap<(String, usize), u64> {
+    let mut counts = HashMap::new();
+    for document in Deserializer::from_str(report)
+        .into_iter::<Value>()
+        .flatten()
+    {
+        let files = document["files"].as_array().cloned().unwrap_or_default();
+        for file in files {
+            let name = file["file"].as_str().unwrap_or_default().to_string();
+            for line in file["lines"].as_array().cloned().unwrap_or_default() {
+                let (Some(number), Some(count)) =
+                    (line["line_number"].as_u64(), line["count"].as_u64())
+                else {
+                    continue;
+                };
+                *counts.entry((name.clone(), number as usize)).or_insert(0) += count;
+            }
+        }
+    }
+    counts
+}
+
+/// The coverage of the procedures of `src` for each file of `files` that has
+/// any, named by its path below `src_dir`. Programs are not part of the test
+/// harness and the tests themselves are not what is measured, so only the
+/// statements of procedures count.
+pub fn file_coverage(
+    src: &Src,
+    files: &[SrcFile],
+    src_dir: &str,
+    counts: &HashMap<(String, usize), u64>,
+) -> Vec<FileCoverage> {
+    let mut coverage = vec![];
+    for file in files {
+        let module = module_name_from_file(&file.file);
+        let key = file_from_module_name(&module);
+        let mut lines: Vec<(usize, u64)> = src
+            .procedures
+            .iter()
+            .filter(|procedure| procedure.module == module)
+            .flat_map(|procedure| procedure.do_block.lines.iter())
+            .map(|line| {
+                let count = counts.get(&(key.clone(), *line)).copied();
+                (*line, count.unwrap_or(0))
+            })
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+        lines.sort();
+        lines.dedup_by_key(|(line, _)| *line);
+        coverage.push(FileCoverage {
+            file: format!("{}/{}", src_dir, file.file),
+            content: file.content.clone(),
+            lines,
+        });
+    }
+    coverage
+}
+
+/// One line for every file and the total, `covered of lines (percent)`.
+pub fn render_summary(coverage: &[FileCoverage]) -> String {
+    let mut output = String::new();
+    for file in coverage {
+        output.push_str(&format!(
+            "{}: {}\n",
+            file.file,
+            ratio(file.covered(), file.lines.len())
+        ));
+    }
+    let covered = coverage.iter().map(|file| file.covered()).sum();
+    let lines = coverage.iter().map(|file| file.lines.len()).sum();
+    output.push_str(&format!("total: {}\n", ratio(covered, lines)));
+    output
+}
+
+/// The summary of every file followed by its source, each line prefixed with
+/// how often it ran the way gcov does: `-` where no statement starts and
+/// `#####` for a statement that never ran.
+pub fn render_text(coverage: &[FileCoverage]) -> String {
+    let mut output = String::new();
+    for file in coverage {
+        output.push_str(&format!(
+            "{}: {}\n",
+            file.file,
+            ratio(file.covered(), file.lines.len())
+        ));
+        let counts: HashMap<usize, u64> = file.lines.iter().copied().collect();
+        for (idx, text) in file.content.lines().enumerate() {
+            let count = match counts.get(&(idx + 1)) {
+                Some(0) => "#####".to_string(),
+                Some(count) => count.to_string(),
+                None => "-".to_string(),
+            };
+            output.push_str(&format!("{:>9}:{:>5}:{}\n", count, idx + 1, text));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// The coverage in the tracefile format of lcov, read by genhtml and most
+/// coverage services.
+pub fn render_lcov(coverage: &[FileCoverage]) -> String {
+    let mut output = String::new();
+    for file in coverage {
+        output.push_str("TN:\n");
+        output.push_str(&format!("SF:{}\n", file.file));
+        for (line, count) in &file.lines {
+            output.push_str(&format!("DA:{},{}\n", line, count));
+        }
+        output.push_str(&format!("LF:{}\n", file.lines.len()));
+        output.push_str(&format!("LH:{}\n", file.covered()));
+        output.push_str("end_of_record\n");
+    }
+    output
+}
+
+fn ratio(covered: usize, lines: usize) -> String {
+    let percent = match lines {
+        0 => 100.0,
+        _ => covered as f64 * 100.0 / lines as f64,
+    };
+    format!("{} of {} lines covered ({:.1}%)", covered, lines, percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use pretty_assertions::assert_eq;
+
+    const MAIN: &str = concat!(
+        "proc yes() Bool do\n",
+        "    return true\n",
+        "end\n",
+        "\n",
+        "proc no() Bool do\n",
+        "    debug:print(\"no\")\n",
+        "    return false\n",
+        "end\n",
+        "\n",
+        "test \"yes\" do\n",
+        "    assert yes()\n",
+        "end\n",
+    );
+
+    // the lines after a statement belong to the C code that follows it
+    const REPORT: &str = concat!(
+        r#"{"files": [{"file": "main.palel", "lines": ["#,
+        r#"{"line_number": 2, "count": 1}, {"line_number": 3, "count": 1}, "#,
+        r#"{"line_number": 6, "count": 0}, {"line_number": 11, "count": 1}]}]}"#,
+        "\n",
+        r#"{"files": [{"file": "main.palel", "lines": [{"line_number": 2, "count": 2}]}]}"#,
+        "\n",
+    );
+
+    fn main_coverage() -> Vec<FileCoverage> {
+        let file = SrcFile {
+            file: "main.palel".to_string(),
+            content: MAIN.to_string(),
+        };
+        let mut src = Src::default();
+        assert!(parse(&mut src, &file).is_none());
+        file_coverage(&src, &[file], "./src", &line_counts(REPORT))
+    }
+
+    #[test]
+    fn test_file_coverage() {
+        let coverage = main_coverage();
+        assert_eq!(coverage.len(), 1);
+        assert_eq!(coverage[0].file, "./src/main.palel");
+        assert_eq!(coverage[0].lines, vec![(2, 3), (6, 0), (7, 0)]);
+        assert_eq!(
+            render_summary(&coverage),
+            concat!(
+                "./src/main.palel: 1 of 3 lines covered (33.3%)\n",
+                "total: 1 of 3 lines covered (33.3%)\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let coverage = main_coverage();
+        let text = render_text(&coverage);
+        assert_eq!(
+            text.lines().take(8).collect::<Vec<&str>>(),
+            vec![
+                "./src/main.palel: 1 of 3 lines covered (33.3%)",
+                "        -:    1:proc yes() Bool do",
+                "        3:    2:    return true",
+                "        -:    3:end",
+                "        -:    4:",
+                "        -:    5:proc no() Bool do",
+                "    #####:    6:    debug:print(\"no\")",
+                "    #####:    7:    return false",
+            ]
+        );
+        assert_eq!(
+            render_lcov(&coverage),
+            concat!(
+                "TN:\n",
+                "SF:./src/main.palel\n",
+                "DA:2,3\n",
+                "DA:6,0\n",
+                "DA:7,0\n",
+                "LF:3\n",
+                "LH:1\n",
+                "end_of_record\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use crate::build_task::{BuildTask, load_src, write};
+use crate::compilation_error::CompilationError;
+use crate::core::Of;
+use crate::renderer_doc::render_doc;
+
+pub fn run_doc_task(task: &mut BuildTask) -> Option<Box<dyn CompilationError>> {
+    let src = match load_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Some(err),
+    };
+    let output_file = format!("{}/doc/index.html", task.config.dest_dir);
+    write(&output_file, &render_doc(&src))
+}
@@ -1,28 +1,387 @@
+use std::io::{ErrorKind, Write};
 use std::process::Command;
 
-use crate::compilation_error::{CompilationError, DownstreamCompileFailed};
+use crate::compilation_error::{
+    CompilationError, CoverageToolFailed, DownstreamArchiverNotFound, DownstreamCompileFailed,
+    DownstreamCompilerNotFound,
+};
+use crate::core::Of;
 
-pub fn downstream_compile(
+/// Debug builds carry debug information and are not optimized, release builds
+/// are optimized and compile out assertions with `NDEBUG`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Profile {
+    #[default]
+    Debug,
+    Release,
+}
+
+/// How the generated C code is compiled and linked.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CCompilerConfig {
+    /// The compiler program followed by any leading arguments, split on
+    /// whitespace as make does with `CC`: `ccache gcc` or `gcc -m32`
+    pub compiler: String,
+    pub profile: Profile,
+    /// `-O<level>`, the level of the profile when not set
+    pub optimization: Option<String>,
+    /// `-W<warning>`, `all` for `-Wall`
+    pub warnings: Vec<String>,
+    pub include_dirs: Vec<String>,
+    pub library_dirs: Vec<String>,
+    /// `-D<define>`, `NAME` or `NAME=value`
+    pub defines: Vec<String>,
+    /// Passed to the compiler as they are
+    pub flags: Vec<String>,
+    /// Print every command before running it and the warnings of the
+    /// compiler, which are only shown on failure otherwise
+    pub verbose: bool,
+}
+
+impl Default for CCompilerConfig {
+    fn default() -> Self {
+        CCompilerConfig {
+            compiler: "gcc".to_string(),
+            profile: Profile::Debug,
+            optimization: None,
+            warnings: vec![],
+            include_dirs: vec![],
+            library_dirs: vec![],
+            defines: vec![],
+            flags: vec![],
+            verbose: false,
+        }
+    }
+}
+
+impl CCompilerConfig {
+    /// A command running the compiler with its leading arguments.
+    fn compiler_command(&self) -> Command {
+        let mut words = self.compiler.split_whitespace();
+        let mut command = Command::new(words.next().unwrap_or("gcc"));
+        command.args(words);
+        command
+    }
+
+    fn compile_flags(&self) -> Vec<String> {
+        let mut flags = vec![];
+        let default_optimization = match self.profile {
+            Profile::Debug => {
+                flags.push("-g".to_string());
+                "0"
+            }
+            Profile::Release => {
+                flags.push("-DNDEBUG".to_string());
+                "2"
+            }
+        };
+        let optimization = self.optimization.as_deref().unwrap_or(default_optimization);
+        flags.push(format!("-O{}", optimization));
+        for warning in &self.warnings {
+            flags.push(format!("-W{}", warning.trim_start_matches("-W")));
+        }
+        for dir in &self.include_dirs {
+            flags.push(format!("-I{}", dir));
+        }
+        for define in &self.defines {
+            flags.push(format!("-D{}", define));
+        }
+        flags.extend(self.flags.iter().cloned());
+        flags
+    }
+
+    fn link_flags(&self, libraries: &[String]) -> Vec<String> {
+        let mut flags = vec![];
+        for dir in &self.library_dirs {
+            flags.push(format!("-L{}", dir));
+        }
+        for library in libraries {
+            flags.push(format!("-l{}", library));
+        }
+        flags
+    }
+}
+
+/// Compiles `file` into the object file `output_name` without linking it so
+/// it can be shared by several executables. The code is position independent
+/// so the same object also goes into a shared library.
+pub fn downstream_compile_object(
+    config: &CCompilerConfig,
     file: &String,
     output_name: &String,
 ) -> Option<Box<dyn CompilationError>> {
-    let exc = Command::new("gcc")
-        .arg(file.clone())
+    let mut command = config.compiler_command();
+    command
+        .args(config.compile_flags())
+        .arg("-c")
+        .arg("-fPIC")
+        .arg(file)
+        .arg("-o")
+        .arg(output_name);
+    run(config, &mut command, Tool::Compiler)
+}
+
+/// Compiles and links `files` into the executable `output_name`, `libraries`
+/// are passed to the linker as `-l<library>`.
+pub fn downstream_compile(
+    config: &CCompilerConfig,
+    files: &[String],
+    output_name: &String,
+    libraries: &[String],
+) -> Option<Box<dyn CompilationError>> {
+    let mut command = config.compiler_command();
+    command
+        .args(config.compile_flags())
+        .args(files)
         .arg("-o")
         .arg(output_name)
-        .status();
+        .args(config.link_flags(libraries));
+    run(config, &mut command, Tool::Compiler)
+}
 
-    let err = DownstreamCompileFailed {};
+/// Bundles `objects` into the static library `output_name`.
+pub fn downstream_archive(
+    config: &CCompilerConfig,
+    objects: &[String],
+    output_name: &String,
+) -> Option<Box<dyn CompilationError>> {
+    let mut command = Command::new("ar");
+    command.arg("rcs").arg(output_name).args(objects);
+    run(config, &mut command, Tool::Archiver)
+}
+
+/// Links `objects` into the shared library `output_name`.
+pub fn downstream_link_shared(
+    config: &CCompilerConfig,
+    objects: &[String],
+    output_name: &String,
+    libraries: &[String],
+) -> Option<Box<dyn CompilationError>> {
+    let mut command = config.compiler_command();
+    command
+        .arg("-shared")
+        .args(objects)
+        .arg("-o")
+        .arg(output_name)
+        .args(config.link_flags(libraries));
+    run(config, &mut command, Tool::Compiler)
+}
 
-    let status = match exc {
-        Err(_) => {
-            return Some(Box::new(err));
+/// Reads the coverage `data_files` the instrumented executables wrote with
+/// the gcov of the compiler and returns its JSON report, one document per
+/// line.
+pub fn downstream_coverage(config: &CCompilerConfig, data_files: &[String]) -> Of<String> {
+    let mut command = Command::new(gcov_tool(&config.compiler));
+    command
+        .arg("--json-format")
+        .arg("--stdout")
+        .args(data_files);
+    let rendered = render_command(&command);
+    if config.verbose {
+        println!("{}", rendered);
+    }
+    match command.output() {
+        Ok(output) if output.status.success() => {
+            Of::Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+        Ok(output) => Of::Error(Box::new(CoverageToolFailed {
+            command: rendered,
+            reason: String::from_utf8_lossy(&output.stderr).to_string(),
+        })),
+        Err(err) => Of::Error(Box::new(CoverageToolFailed {
+            command: rendered,
+            reason: err.to_string(),
+        })),
+    }
+}
+
+/// The gcov that reads the data of `compiler`, `gcov-12` for `gcc-12`. A
+/// launcher such as `ccache` and the arguments are skipped, the compiler is
+/// the last word that is not a flag.
+fn gcov_tool(compiler: &str) -> String {
+    let compiler = compiler
+        .split_whitespace()
+        .rfind(|word| !word.starts_with('-'))
+        .unwrap_or("");
+    let (dir, name) = match compiler.rfind('/') {
+        Some(idx) => compiler.split_at(idx + 1),
+        None => ("", compiler),
+    };
+    match name.strip_prefix("gcc") {
+        Some(version) => format!("{}gcov{}", dir, version),
+        None => "gcov".to_string(),
+    }
+}
+
+/// The programs `run` starts, named in the errors it reports.
+enum Tool {
+    Compiler,
+    Archiver,
+}
+
+impl Tool {
+    fn name(&self) -> &'static str {
+        match self {
+            Tool::Compiler => "C compiler",
+            Tool::Archiver => "archiver",
+        }
+    }
+
+    fn not_found(&self, program: String) -> Box<dyn CompilationError> {
+        match self {
+            Tool::Compiler => Box::new(DownstreamCompilerNotFound { compiler: program }),
+            Tool::Archiver => Box::new(DownstreamArchiverNotFound { archiver: program }),
+        }
+    }
+}
+
+fn run(
+    config: &CCompilerConfig,
+    command: &mut Command,
+    tool: Tool,
+) -> Option<Box<dyn CompilationError>> {
+    let rendered = render_command(command);
+    if config.verbose {
+        println!("{}", rendered);
+    }
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return Some(tool.not_found(command.get_program().to_string_lossy().to_string()));
+        }
+        Err(err) => {
+            return Some(Box::new(DownstreamCompileFailed {
+                tool: tool.name().to_string(),
+                command: rendered,
+                status: format!("could not be started, {}", err),
+                stdout: String::new(),
+                stderr: String::new(),
+            }));
         }
-        Ok(value) => value,
     };
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
 
-    if !status.success() {
-        return Some(Box::new(err));
+    if !output.status.success() {
+        let status = match output.status.code() {
+            Some(code) => format!("exit status {}", code),
+            None => "terminated by a signal".to_string(),
+        };
+        return Some(Box::new(DownstreamCompileFailed {
+            tool: tool.name().to_string(),
+            command: rendered,
+            status,
+            stdout,
+            stderr,
+        }));
+    }
+    if config.verbose {
+        print!("{}", stdout);
+        let _ = std::io::stdout().flush();
+        eprint!("{}", stderr);
     }
     None
 }
+
+/// The command as it would be typed into a shell.
+fn render_command(command: &Command) -> String {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut parts = vec![program];
+    for arg in command.get_args() {
+        let arg = arg.to_string_lossy();
+        if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+            parts.push(format!("'{}'", arg.replace('\'', "'\\''")));
+        } else {
+            parts.push(arg.to_string());
+        }
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_compile_command() {
+        let config = CCompilerConfig {
+            compiler: "clang".to_string(),
+            profile: Profile::Release,
+            optimization: Some("s".to_string()),
+            warnings: vec!["all".to_string(), "-Wextra".to_string()],
+            include_dirs: vec!["vendor/include".to_string()],
+            library_dirs: vec!["vendor/lib".to_string()],
+            defines: vec!["LEVEL=2".to_string()],
+            flags: vec!["-march=native".to_string()],
+            verbose: true,
+        };
+        let mut command = config.compiler_command();
+        command
+            .args(config.compile_flags())
+            .arg("my file.c")
+            .args(config.link_flags(&["m".to_string()]));
+        assert_eq!(
+            render_command(&command),
+            concat!(
+                "clang -DNDEBUG -Os -Wall -Wextra -Ivendor/include -DLEVEL=2 -march=native ",
+                "'my file.c' -Lvendor/lib -lm"
+            )
+        );
+        let wrapped = CCompilerConfig {
+            compiler: " ccache  gcc -m32 ".to_string(),
+            ..CCompilerConfig::default()
+        };
+        let mut command = wrapped.compiler_command();
+        command.arg("-c").arg("x.c");
+        assert_eq!(render_command(&command), "ccache gcc -m32 -c x.c");
+        assert_eq!(
+            CCompilerConfig::default().compile_flags(),
+            vec!["-g".to_string(), "-O0".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_compiler_failures() {
+        let config = CCompilerConfig::default();
+        let mut missing = Command::new("palel-no-such-compiler");
+        let err = run(&config, &mut missing, Tool::Compiler).unwrap();
+        assert_eq!(
+            err.message(),
+            concat!(
+                "C compiler 'palel-no-such-compiler' was not found, ",
+                "install it or choose another one with --cc or CC"
+            )
+        );
+
+        let mut archiver = Command::new("palel-no-such-ar");
+        let err = run(&config, &mut archiver, Tool::Archiver).unwrap();
+        assert_eq!(
+            err.message(),
+            "archiver 'palel-no-such-ar' was not found, install it to build a static library"
+        );
+
+        let mut failing = Command::new("sh");
+        failing.args(["-c", "echo 'x.c:1: error' >&2; exit 3"]);
+        let err = run(&config, &mut failing, Tool::Compiler).unwrap();
+        assert_eq!(
+            err.message(),
+            concat!(
+                "the C compiler failed on the code generated from your palel sources\n",
+                "command: sh -c 'echo '\\''x.c:1: error'\\'' >&2; exit 3'\n",
+                "status: exit status 3\n",
+                "--- C compiler stderr ---\n",
+                "x.c:1: error\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_gcov_tool() {
+        assert_eq!(gcov_tool("gcc"), "gcov");
+        assert_eq!(gcov_tool("/usr/bin/gcc-12"), "/usr/bin/gcov-12");
+        assert_eq!(gcov_tool("clang"), "gcov");
+        assert_eq!(gcov_tool("ccache gcc-12"), "gcov-12");
+        assert_eq!(gcov_tool("gcc -m32"), "gcov");
+    }
+}
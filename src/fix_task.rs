@@ -0,0 +1,66 @@
+use crate::analysis::{Workspace, apply_edits};
+use crate::build_task::{BuildTask, load_src, src_files, write};
+use crate::compilation_error::CompilationError;
+use crate::core::Of;
+use crate::palel::{Edit, Src};
+use crate::parser::parse;
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::transpile;
+
+/// How often the sources are checked again after fixing them. A fix can
+/// uncover the next error of a statement, which may have a fix of its own.
+const MAX_ROUNDS: usize = 8;
+
+/// Makes the edits the errors of the source files suggest and can be made
+/// without asking, then reports the first error that is left.
+pub fn run_fix_task(task: &mut BuildTask) -> Option<Box<dyn CompilationError>> {
+    if let Of::Error(err) = load_src(task) {
+        return Some(err);
+    }
+    let mut workspace = Workspace::default();
+    for file in src_files(task) {
+        workspace.set(&file.file, file.content.clone());
+    }
+    let mut fixed: Vec<usize> = vec![0; workspace.files.len()];
+    for _ in 0..MAX_ROUNDS {
+        let mut changed = false;
+        for (idx, count) in fixed.iter_mut().enumerate() {
+            let file = workspace.files[idx].file.clone();
+            let edits: Vec<Edit> = workspace
+                .diagnostics(&file)
+                .into_iter()
+                .filter(|diagnostic| !diagnostic.fix.is_empty())
+                .inspect(|_| *count += 1)
+                .flat_map(|diagnostic| diagnostic.fix)
+                .collect();
+            if edits.is_empty() {
+                continue;
+            }
+            let content = apply_edits(&workspace.files[idx].content, &edits);
+            workspace.set(&file, content);
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    for (file, count) in workspace.files.iter().zip(fixed) {
+        if count == 0 {
+            continue;
+        }
+        let output_file = format!("{}/{}", task.config.src_dir, file.file);
+        if let Some(err) = write(&output_file, &file.content) {
+            return Some(err);
+        }
+        println!("fixed {} error(s) in {}", count, output_file);
+    }
+
+    let mut src = Src::default();
+    for file in &workspace.files {
+        if let Some(err) = parse(&mut src, file) {
+            return Some(err);
+        }
+    }
+    transpile(&src, &CToolKit {}).err()
+}
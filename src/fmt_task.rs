@@ -0,0 +1,154 @@
+use crate::build_task::{BuildTask, load_src, src_files, write};
+use crate::compilation_error::{CompilationError, FailedToParseSrcFile, FilesNotFormatted};
+use crate::core::Of;
+use crate::formatter::format;
+
+/// Lines of context shown around the changes of a diff
+const CONTEXT: usize = 3;
+
+pub struct FmtTaskConfig {
+    /// Print the changes formatting would make instead of making them
+    pub check: bool,
+}
+
+/// Formats every source file in place. With `check` the files are left
+/// untouched, a diff is printed for each one that is not formatted and
+/// their names are the error.
+pub fn run_fmt_task(
+    task: &mut BuildTask,
+    config: &FmtTaskConfig,
+) -> Option<Box<dyn CompilationError>> {
+    // parsing all sources first reports syntax errors the usual way
+    if let Of::Error(err) = load_src(task) {
+        return Some(err);
+    }
+    let mut unformatted = vec![];
+    for file in src_files(task) {
+        let formatted = match format(&file.content) {
+            Some(formatted) => formatted,
+            None => {
+                return Some(Box::new(FailedToParseSrcFile {
+                    file: file.file.clone(),
+                }));
+            }
+        };
+        if formatted == file.content {
+            continue;
+        }
+        if config.check {
+            print!("{}", diff(&file.file, &file.content, &formatted));
+            unformatted.push(file.file.clone());
+            continue;
+        }
+        let output_file = format!("{}/{}", task.config.src_dir, file.file);
+        if let Some(err) = write(&output_file, &formatted) {
+            return Some(err);
+        }
+    }
+    match unformatted.is_empty() {
+        true => None,
+        false => Some(Box::new(FilesNotFormatted { files: unformatted })),
+    }
+}
+
+/// A unified diff of the lines of `before` and `after`.
+fn diff(file: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // longest common subsequence of the lines, from the back
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let mut edits: Vec<(char, usize, usize)> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(('-', i, j));
+            i += 1;
+        } else {
+            edits.push(('+', i, j));
+            j += 1;
+        }
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", file, file);
+    let changed: Vec<usize> = (0..edits.len()).filter(|&k| edits[k].0 != ' ').collect();
+    let mut k = 0;
+    while k < changed.len() {
+        let start = changed[k].saturating_sub(CONTEXT);
+        // changes closer than twice the context share a hunk
+        let mut last = changed[k];
+        while k + 1 < changed.len() && changed[k + 1] - last <= 2 * CONTEXT {
+            k += 1;
+            last = changed[k];
+        }
+        let end = (last + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+        let old_lines = hunk.iter().filter(|e| e.0 != '+').count();
+        let new_lines = hunk.iter().filter(|e| e.0 != '-').count();
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk[0].1 + 1,
+            old_lines,
+            hunk[0].2 + 1,
+            new_lines
+        ));
+        for (kind, i, j) in hunk {
+            let line = match kind {
+                '+' => new[*j],
+                _ => old[*i],
+            };
+            output.push_str(&format!("{}{}\n", kind, line));
+        }
+        k += 1;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_diff() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        let after = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nm\nn\n";
+        assert_eq!(
+            diff("main.palel", before, after),
+            concat!(
+                "--- main.palel\n",
+                "+++ main.palel\n",
+                "@@ -1,5 +1,5 @@\n",
+                " a\n",
+                "-b\n",
+                "+B\n",
+                " c\n",
+                " d\n",
+                " e\n",
+                "@@ -9,5 +9,5 @@\n",
+                " i\n",
+                " j\n",
+                " k\n",
+                "-l\n",
+                " m\n",
+                "+n\n",
+            )
+        );
+        assert_eq!(
+            diff("main.palel", before, before),
+            "--- main.palel\n+++ main.palel\n"
+        );
+    }
+}
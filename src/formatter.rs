@@ -0,0 +1,552 @@
+use pest::iterators::Pair;
+
+use crate::palel::*;
+use crate::parser::{
+    Rule, parse_bench, parse_binding, parse_checked, parse_interface, parse_procedure,
+    parse_program, parse_statement, parse_test, parse_use_declaration,
+};
+
+const INDENT: &str = "    ";
+
+/// `content` printed the canonical way, `None` when it does not parse.
+///
+/// Declarations and statements are printed from their AST, four spaces for
+/// every block they are in. Comments stay where they are relative to them:
+/// on a line of their own or behind the line they follow. Single blank lines
+/// are kept and every block declaration is set off by one.
+pub fn format(content: &str) -> Option<String> {
+    let root = parse_checked(Rule::src, content).ok()?.next()?;
+    let mut printer = Printer::new(content, &root);
+    let mut pairs = root.into_inner().peekable();
+    let mut first = true;
+    let mut documented = false;
+    while let Some(pair) = pairs.next() {
+        // a doc comment belongs to the declaration after it
+        let declaration = match pair.as_rule() {
+            Rule::doc_comment => pairs.peek().map(|p| p.as_rule()),
+            rule => Some(rule),
+        };
+        if !first && !documented && matches!(declaration, Some(rule) if is_block(rule)) {
+            printer.blank = true;
+        }
+        first = false;
+        documented = pair.as_rule() == Rule::doc_comment;
+        match pair.as_rule() {
+            Rule::doc_comment => {
+                let mut start = pair.as_span().start();
+                for line in pair.as_str().split_inclusive('\n') {
+                    let text = line.trim();
+                    printer.line(0, text, start, start + line.trim_end().len(), true);
+                    start += line.len();
+                }
+            }
+            Rule::use_declaration => {
+                let text = format!("use {}", parse_use_declaration(pair.clone()));
+                printer.pair(0, &text, &pair);
+            }
+            Rule::binding => {
+                let binding = parse_binding(pair.clone(), "");
+                let text = format!(
+                    "bind {}:{} \"{}\"",
+                    binding.interface, binding.identifier, binding.symbol
+                );
+                printer.pair(0, &text, &pair);
+            }
+            Rule::program => {
+                let program = parse_program(pair.clone(), "", None);
+                let mut header = "program".to_string();
+                if let Some(name) = &program.name {
+                    header.push(' ');
+                    header.push_str(name);
+                }
+                if !program.parameters.is_empty() {
+                    header.push_str(&format!("({})", print_parameters(&program.parameters)));
+                }
+                printer.do_block(&header, &pair);
+            }
+            Rule::test_block => {
+                let test = parse_test(pair.clone(), "");
+                let header = format!("test \"{}\"", escape(&test.name, '"'));
+                printer.do_block(&header, &pair);
+            }
+            Rule::bench_block => {
+                let bench = parse_bench(pair.clone(), "");
+                let header = format!("bench \"{}\"", escape(&bench.name, '"'));
+                printer.do_block(&header, &pair);
+            }
+            Rule::procedure => {
+                let procedure = parse_procedure(pair.clone(), "", None);
+                let mut header = String::new();
+                if procedure.export {
+                    header.push_str("pub extern ");
+                } else if procedure.public {
+                    header.push_str("pub ");
+                }
+                header.push_str(&format!(
+                    "proc {}({})",
+                    call_name(&procedure.interface, &procedure.identifier),
+                    print_parameters(&procedure.parameters)
+                ));
+                if let Some(return_type) = &procedure.return_type {
+                    header.push(' ');
+                    header.push_str(&return_type.to_string());
+                }
+                printer.do_block(&header, &pair);
+            }
+            Rule::interface | Rule::extern_interface => {
+                let interface = parse_interface(pair.clone(), "", None);
+                let header = match &interface.header {
+                    Some(header) => {
+                        let mut text = format!("extern \"{}\"", header);
+                        if interface.identifier != extern_interface_name(header) {
+                            text.push_str(&format!(" as {}", interface.identifier));
+                        }
+                        for link in &interface.links {
+                            text.push_str(&format!(" link \"{}\"", link));
+                        }
+                        text
+                    }
+                    None => format!("interface {}", interface.identifier),
+                };
+                printer.interface(&header, &pair, &interface);
+            }
+            _ => {}
+        }
+    }
+    printer.flush(0, content.len());
+    Some(printer.output)
+}
+
+fn is_block(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::program
+            | Rule::procedure
+            | Rule::interface
+            | Rule::extern_interface
+            | Rule::test_block
+            | Rule::bench_block
+    )
+}
+
+/// A comment of the source, `##` doc comments are part of the declarations.
+struct Comment {
+    start: usize,
+    end: usize,
+}
+
+struct Printer<'s> {
+    source: &'s str,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    output: String,
+    /// Where the last printed line ends in the source, the source between it
+    /// and the next line tells whether a blank line separates them
+    last_end: usize,
+    /// Put a blank line before the next line
+    blank: bool,
+}
+
+impl<'s> Printer<'s> {
+    fn new(source: &'s str, root: &Pair<'_, Rule>) -> Printer<'s> {
+        let docs: Vec<(usize, usize)> = root
+            .clone()
+            .into_inner()
+            .filter(|p| p.as_rule() == Rule::doc_comment)
+            .map(|p| (p.as_span().start(), p.as_span().end()))
+            .collect();
+        let comments = find_comments(source)
+            .into_iter()
+            .filter(|c| {
+                !docs
+                    .iter()
+                    .any(|(start, end)| *start <= c.start && c.end <= *end)
+            })
+            .collect();
+        Printer {
+            source,
+            comments,
+            next_comment: 0,
+            output: String::new(),
+            last_end: 0,
+            blank: false,
+        }
+    }
+
+    /// Prints `text` for the source from `start` to `end` at `depth`, after
+    /// the comments before it. Comments within it or behind it on the same
+    /// line follow it on its line.
+    fn line(&mut self, depth: usize, text: &str, start: usize, end: usize, keep_blank: bool) {
+        self.flush(depth, start);
+        let blank_in_source = self.blank_in_source(start);
+        if !self.output.is_empty() && (self.blank || keep_blank && blank_in_source) {
+            self.output.push('\n');
+        }
+        self.blank = false;
+        for _ in 0..depth {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(text);
+        let mut end = end;
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.start >= end && self.source[end..comment.start].contains('\n') {
+                break;
+            }
+            self.output.push(' ');
+            self.output
+                .push_str(&self.source[comment.start..comment.end]);
+            end = end.max(comment.end);
+            self.next_comment += 1;
+        }
+        self.output.push('\n');
+        self.last_end = end;
+    }
+
+    fn pair(&mut self, depth: usize, text: &str, pair: &Pair<'_, Rule>) {
+        let span = pair.as_span();
+        self.line(depth, text, span.start(), span.end(), true);
+    }
+
+    /// Prints the comments before `position` on lines of their own.
+    fn flush(&mut self, depth: usize, position: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.start >= position {
+                break;
+            }
+            let blank_in_source = self.blank_in_source(comment.start);
+            if !self.output.is_empty() && (self.blank || blank_in_source) {
+                self.output.push('\n');
+            }
+            self.blank = false;
+            for _ in 0..depth {
+                self.output.push_str(INDENT);
+            }
+            self.output
+                .push_str(&self.source[comment.start..comment.end]);
+            self.output.push('\n');
+            self.last_end = comment.end;
+            self.next_comment += 1;
+        }
+    }
+
+    /// Whether a blank line separates the last printed line from `position`.
+    fn blank_in_source(&self, position: usize) -> bool {
+        let between = self.source.get(self.last_end..position).unwrap_or("");
+        between.matches('\n').count() > 1
+    }
+
+    /// Prints a program or procedure: `header do`, its statements and `end`.
+    fn do_block(&mut self, header: &str, pair: &Pair<'_, Rule>) {
+        let do_block = match pair
+            .clone()
+            .into_inner()
+            .find(|p| p.as_rule() == Rule::do_block)
+        {
+            Some(do_block) => do_block,
+            None => return,
+        };
+        let start = pair.as_span().start();
+        let do_start = do_block.as_span().start();
+        self.line(0, &format!("{} do", header), start, do_start + 2, true);
+        let mut opened = true;
+        for statement in do_block.clone().into_inner() {
+            if let Some(parsed) = parse_statement(statement.clone()) {
+                let span = statement.as_span();
+                let text = print_statement(&parsed);
+                self.line(1, &text, span.start(), span.end(), !opened);
+                opened = false;
+            }
+        }
+        self.end(&do_block);
+    }
+
+    /// Prints an interface or extern interface with its procedures.
+    fn interface(&mut self, header: &str, pair: &Pair<'_, Rule>, interface: &Interface) {
+        let start = pair.as_span().start();
+        let procedures: Vec<Pair<'_, Rule>> = pair
+            .clone()
+            .into_inner()
+            .filter(|p| p.as_rule() == Rule::interface_proc)
+            .collect();
+        // `do` follows the last part of the header
+        let header_end = pair
+            .clone()
+            .into_inner()
+            .filter(|p| p.as_rule() != Rule::interface_proc)
+            .map(|p| p.as_span().end())
+            .max()
+            .unwrap_or(start);
+        let do_end = self.source[header_end..]
+            .find("do")
+            .map_or(header_end, |idx| header_end + idx + 2);
+        self.line(0, &format!("{} do", header), start, do_end, true);
+        for (idx, (procedure_pair, procedure)) in
+            procedures.iter().zip(&interface.procedures).enumerate()
+        {
+            let mut text = format!(
+                "proc {}({})",
+                procedure.identifier,
+                print_parameters(&procedure.parameters)
+            );
+            if let Some(return_type) = &procedure.return_type {
+                text.push(' ');
+                text.push_str(&return_type.to_string());
+            }
+            let span = procedure_pair.as_span();
+            self.line(1, &text, span.start(), span.end(), idx > 0);
+        }
+        self.end(pair);
+    }
+
+    /// Prints the comments left in the block `pair` and the `end` it ends
+    /// with, and sets off what follows.
+    fn end(&mut self, pair: &Pair<'_, Rule>) {
+        let end = pair.as_span().end();
+        self.flush(1, end - 3);
+        self.line(0, "end", end - 3, end, false);
+        self.blank = true;
+    }
+}
+
+/// The comments of `source` outside of string and character literals.
+fn find_comments(source: &str) -> Vec<Comment> {
+    let mut comments = vec![];
+    let mut chars = source.char_indices().peekable();
+    let mut quote = None;
+    while let Some((idx, c)) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(open), _) if open == c => quote = None,
+            (Some(_), '\n') => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') if matches!(chars.peek(), Some((_, '['))) => {
+                chars.next();
+                let mut depth = 1;
+                let mut end = source.len();
+                while let Some((inner, c)) = chars.next() {
+                    match (c, chars.peek()) {
+                        ('#', Some((_, '['))) => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        (']', Some((_, '#'))) => {
+                            chars.next();
+                            depth -= 1;
+                            if depth == 0 {
+                                end = inner + 2;
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                comments.push(Comment { start: idx, end });
+            }
+            (None, '#') => {
+                let end = source[idx..].find('\n').map_or(source.len(), |n| idx + n);
+                let end = source[idx..end].trim_end_matches('\r').len() + idx;
+                while matches!(chars.peek(), Some((next, _)) if *next < end) {
+                    chars.next();
+                }
+                comments.push(Comment { start: idx, end });
+            }
+            (None, _) => {}
+        }
+    }
+    comments
+}
+
+fn print_parameters(parameters: &[Parameter]) -> String {
+    parameters
+        .iter()
+        .map(|p| format!("{} {}", p.identifier, p.schema_type.to_string()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn print_statement(statement: &Statement) -> String {
+    match statement {
+        Statement::ProcedureCall(call) => print_call(call),
+        Statement::Return(ret) => match &ret.value {
+            Some(value) => format!("return {}", print_expression(value)),
+            None => "return".to_string(),
+        },
+        Statement::Variable(declaration) => {
+            let memory = match declaration.memory {
+                MemoryModifier::Dim => "dim",
+                MemoryModifier::Var => "var",
+                MemoryModifier::Ref => "ref",
+                MemoryModifier::Addr => "addr",
+            };
+            let mut text = format!("{} {}", memory, declaration.identifier);
+            if let Some(schema) = &declaration.schema_type {
+                text.push(' ');
+                text.push_str(&schema.to_string());
+            }
+            text.push_str(" = ");
+            text.push_str(&print_expression(&declaration.expression));
+            text
+        }
+        Statement::Assert(assert) => {
+            let mut text = format!("assert {}", print_expression(&assert.condition));
+            if let Some(message) = &assert.message {
+                text.push_str(&format!(", \"{}\"", escape(message, '"')));
+            }
+            text
+        }
+    }
+}
+
+/// What is reported when the assertion fails, the condition itself when no
+/// message is given.
+pub fn assertion_failure(assert: &Assert) -> String {
+    match &assert.message {
+        Some(message) => format!("assertion failed: {}", message),
+        None => format!("assertion failed: {}", print_expression(&assert.condition)),
+    }
+}
+
+pub fn print_expression(expression: &Expression) -> String {
+    match expression {
+        Expression::Literal(literal) => print_literal(literal),
+        Expression::Variable(name) => name.clone(),
+        Expression::Cast(cast) => {
+            let operator = match cast.mode {
+                CastMode::Wrapping => "as",
+                CastMode::Checked => "as?",
+                CastMode::Saturating => "as!",
+            };
+            format!(
+                "{} {} {}",
+                print_expression(&cast.expression),
+                operator,
+                cast.target.to_string()
+            )
+        }
+        Expression::Call(call) => print_call(call),
+    }
+}
+
+fn print_call(call: &ProcedureCall) -> String {
+    let arguments: Vec<String> = call.arguments.iter().map(print_expression).collect();
+    format!(
+        "{}({})",
+        call_name(&call.interface, &call.identifier),
+        arguments.join(", ")
+    )
+}
+
+fn print_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::String(value) => format!("\"{}\"", escape(value, '"')),
+        Literal::Char(value) => format!("'{}'", escape(&value.to_string(), '\'')),
+        Literal::Number(value) => value.clone(),
+        Literal::Boolean(value) => value.to_string(),
+        Literal::Null => "null".to_string(),
+    }
+}
+
+/// `value` with the escapes the grammar reads back into it. Characters outside
+/// of printable ASCII are written as `\u{...}` so none of them turn invisible
+/// or change how the source file is encoded.
+fn escape(value: &str, quote: char) -> String {
+    let mut output = String::new();
+    for c in value.chars() {
+        match c {
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            '\0' => output.push_str("\\0"),
+            '\\' => output.push_str("\\\\"),
+            c if c == quote => {
+                output.push('\\');
+                output.push(c);
+            }
+            ' '..='~' => output.push(c),
+            c => output.push_str(&format!("\\u{{{:X}}}", c as u32)),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format() {
+        let content = concat!(
+            "# the greeter\n",
+            "use   text\n",
+            "## Says hello.\n",
+            "  pub  proc greet(name String,  times Int32?)  do  # greets\n",
+            "debug:print(\"do # not a comment\\n\", name,'\\'')\n",
+            "\n",
+            "\n",
+            "      #[ a block\n",
+            "   comment ]#\n",
+            "  dim n   = times as?  Int8 # narrowed\n",
+            "        end\n",
+            "program do\n",
+            "\n",
+            "    text.greet( \"you\" , null)\n",
+            "    # last\n",
+            "\n",
+            "end\n",
+            "extern \"math.h\"   link \"m\" do\n",
+            "  proc sqrt(x Float64) Float64\n",
+            "end\n",
+            "bind  log:put \"putchar\"\n",
+        );
+        let expected = concat!(
+            "# the greeter\n",
+            "use text\n",
+            "\n",
+            "## Says hello.\n",
+            "pub proc greet(name String, times Int32?) do # greets\n",
+            "    debug:print(\"do # not a comment\\n\", name, '\\'')\n",
+            "\n",
+            "    #[ a block\n",
+            "   comment ]#\n",
+            "    dim n = times as? Int8 # narrowed\n",
+            "end\n",
+            "\n",
+            "program do\n",
+            "    text.greet(\"you\", null)\n",
+            "    # last\n",
+            "end\n",
+            "\n",
+            "extern \"math.h\" link \"m\" do\n",
+            "    proc sqrt(x Float64) Float64\n",
+            "end\n",
+            "\n",
+            "bind log:put \"putchar\"\n",
+        );
+        assert_eq!(format(content), Some(expected.to_string()));
+        assert_eq!(format(expected), Some(expected.to_string()));
+        assert_eq!(
+            format("program do\nend"),
+            Some("program do\nend\n".to_string())
+        );
+        assert_eq!(format("proc broken( do\nend\n"), None);
+    }
+
+    #[test]
+    fn test_format_literal_escapes() {
+        let content = concat!(
+            "program do\n",
+            "    dim s = \"\\u{1F600} \\u{E9} \\u{200B}\\u{7F}\\t\"\n",
+            "    dim c = '\\u{E9}'\n",
+            "end\n",
+        );
+        assert_eq!(format(content), Some(content.to_string()));
+        let raw = "program do\n    dim s = \"\u{E9}\u{200B}\"\nend\n";
+        assert_eq!(
+            format(raw),
+            Some("program do\n    dim s = \"\\u{E9}\\u{200B}\"\nend\n".to_string())
+        );
+    }
+}
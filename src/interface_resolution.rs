@@ -0,0 +1,274 @@
+use crate::compilation_error::{
+    CompilationError, DuplicateImplementation, DuplicateInterface, ImplementationMismatch,
+    MissingImplementation, UnknownInterface, UnknownProcedure,
+};
+use crate::palel::{Implementation, ProcedureSignature, Src, call_name};
+use crate::transpiler_context::Context;
+
+/// Checks that interface names are unique, also among the `builtins`, that
+/// every implementation and binding belongs to a declared interface procedure
+/// and that every interface procedure is implemented exactly once with the
+/// declared parameters and return type.
+pub fn check_interfaces(
+    src: &Src,
+    builtins: &[ProcedureSignature],
+) -> Option<Box<dyn CompilationError>> {
+    for (idx, interface) in src.interfaces.iter().enumerate() {
+        let duplicate = src.interfaces[..idx]
+            .iter()
+            .any(|i| i.identifier == interface.identifier)
+            || builtins.iter().any(|b| b.interface == interface.identifier);
+        if duplicate {
+            return Some(Box::new(DuplicateInterface {
+                name: interface.identifier.clone(),
+            }));
+        }
+    }
+
+    let implementations = src
+        .procedures
+        .iter()
+        .filter(|p| !p.interface.is_empty())
+        .map(|p| (&p.interface, &p.identifier));
+    let bindings = src.bindings.iter().map(|b| (&b.interface, &b.identifier));
+    for (interface, identifier) in implementations.chain(bindings) {
+        let name = call_name(interface, identifier);
+        let declared = match src.interfaces.iter().find(|i| &i.identifier == interface) {
+            Some(declared) => declared,
+            None => {
+                return Some(Box::new(UnknownInterface {
+                    interface: interface.clone(),
+                }));
+            }
+        };
+        if !declared
+            .procedures
+            .iter()
+            .any(|p| &p.identifier == identifier)
+        {
+            return Some(Box::new(UnknownProcedure { name }));
+        }
+    }
+
+    for interface in &src.interfaces {
+        for procedure in &interface.procedures {
+            let name = call_name(&interface.identifier, &procedure.identifier);
+            let declared = procedure.signature(interface);
+            let implemented: Vec<_> = src
+                .procedures
+                .iter()
+                .filter(|p| p.interface == interface.identifier)
+                .filter(|p| p.identifier == procedure.identifier)
+                .collect();
+            let bound = src
+                .bindings
+                .iter()
+                .filter(|b| b.interface == interface.identifier)
+                .filter(|b| b.identifier == procedure.identifier)
+                .count();
+            // the procedures of an extern interface are implemented by its header
+            let external = interface.header.is_some() as usize;
+            match implemented.len() + bound + external {
+                0 => return Some(Box::new(MissingImplementation { name })),
+                1 => {}
+                _ => return Some(Box::new(DuplicateImplementation { name })),
+            }
+            for implementation in implemented {
+                let signature = implementation.signature();
+                if signature.parameters != declared.parameters
+                    || signature.return_type != declared.return_type
+                {
+                    return Some(Box::new(ImplementationMismatch { name }));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Makes every interface procedure callable as `interface:procedure`, from
+/// any module.
+pub fn declare_interfaces(src: &Src, ctx: &mut Context) {
+    for interface in &src.interfaces {
+        for procedure in &interface.procedures {
+            let mut signature = procedure.signature(interface);
+            let implementation = src.procedures.iter().find(|p| {
+                p.interface == interface.identifier && p.identifier == procedure.identifier
+            });
+            let binding = src.bindings.iter().find(|b| {
+                b.interface == interface.identifier && b.identifier == procedure.identifier
+            });
+            if let Some(header) = &interface.header {
+                signature.implementation = Implementation::Header(header.clone());
+            } else if let Some(implementation) = implementation {
+                signature.module = implementation.module.clone();
+            } else if let Some(binding) = binding {
+                signature.implementation = Implementation::C(binding.symbol.clone());
+            }
+            ctx.declare_procedure(&signature.name(), signature);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palel::{
+        Binding, Interface, InterfaceProcedure, Procedure, SchemaIdentifier, schema_type,
+    };
+    use crate::test_support::{message, procedure};
+
+    fn interface(identifier: &str, procedures: Vec<&str>) -> Interface {
+        Interface {
+            doc: None,
+            module: "main".to_string(),
+            identifier: identifier.to_string(),
+            procedures: procedures
+                .iter()
+                .map(|p| InterfaceProcedure {
+                    identifier: p.to_string(),
+                    parameters: vec![],
+                    return_type: None,
+                })
+                .collect(),
+            header: None,
+            links: vec![],
+        }
+    }
+
+    fn implementation(interface: &str, identifier: &str) -> Procedure {
+        Procedure {
+            interface: interface.to_string(),
+            ..procedure("impl", identifier)
+        }
+    }
+
+    fn binding(interface: &str, identifier: &str, symbol: &str) -> Binding {
+        Binding {
+            module: "main".to_string(),
+            interface: interface.to_string(),
+            identifier: identifier.to_string(),
+            symbol: symbol.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_declare_interfaces() {
+        let input = Src {
+            interfaces: vec![interface("log", vec!["info", "flush"])],
+            procedures: vec![implementation("log", "info")],
+            bindings: vec![binding("log", "flush", "fflush_all")],
+            ..Src::default()
+        };
+        assert!(check_interfaces(&input, &[]).is_none());
+
+        let mut ctx = Context::default();
+        declare_interfaces(&input, &mut ctx);
+        let info = ctx.lookup_procedure("log:info").unwrap();
+        assert_eq!(info.module, "impl");
+        assert_eq!(info.implementation, Implementation::Palel);
+        assert_eq!(
+            ctx.lookup_procedure("log:flush").unwrap().implementation,
+            Implementation::C("fflush_all".to_string())
+        );
+        assert!(ctx.has_interface("log"));
+        assert!(ctx.lookup_procedure("info").is_none());
+    }
+
+    #[test]
+    fn test_interface_errors() {
+        let duplicate = Src {
+            interfaces: vec![interface("log", vec![]), interface("log", vec![])],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_interfaces(&duplicate, &[])),
+            "interface 'log' is declared more than once"
+        );
+
+        let builtin = Src {
+            interfaces: vec![interface("debug", vec![])],
+            ..Src::default()
+        };
+        let print = ProcedureSignature {
+            module: "".to_string(),
+            public: true,
+            export: false,
+            interface: "debug".to_string(),
+            identifier: "print".to_string(),
+            parameters: vec![],
+            return_type: None,
+            implementation: Implementation::Builtin,
+        };
+        assert!(check_interfaces(&builtin, &[print]).is_some());
+
+        let unknown = Src {
+            procedures: vec![implementation("log", "info")],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_interfaces(&unknown, &[])),
+            "could not find interface 'log'"
+        );
+
+        let undeclared = Src {
+            interfaces: vec![interface("log", vec![])],
+            bindings: vec![binding("log", "info", "puts")],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_interfaces(&undeclared, &[])),
+            "could not find procedure 'log:info'"
+        );
+
+        let missing = Src {
+            interfaces: vec![interface("log", vec!["info"])],
+            ..Src::default()
+        };
+        assert!(
+            message(check_interfaces(&missing, &[]))
+                .starts_with("'log:info' has no implementation")
+        );
+
+        let twice = Src {
+            interfaces: vec![interface("log", vec!["info"])],
+            procedures: vec![implementation("log", "info")],
+            bindings: vec![binding("log", "info", "puts")],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_interfaces(&twice, &[])),
+            "'log:info' is implemented more than once"
+        );
+
+        let mut external = Src {
+            interfaces: vec![interface("math", vec!["sqrt"])],
+            procedures: vec![implementation("math", "sqrt")],
+            ..Src::default()
+        };
+        external.interfaces[0].header = Some("math.h".to_string());
+        assert_eq!(
+            message(check_interfaces(&external, &[])),
+            "'math:sqrt' is implemented more than once"
+        );
+        external.procedures.clear();
+        assert_eq!(message(check_interfaces(&external, &[])), "");
+        let mut ctx = Context::default();
+        declare_interfaces(&external, &mut ctx);
+        assert_eq!(
+            ctx.lookup_procedure("math:sqrt").unwrap().implementation,
+            Implementation::Header("math.h".to_string())
+        );
+
+        let mut mismatch = Src {
+            interfaces: vec![interface("log", vec!["info"])],
+            procedures: vec![implementation("log", "info")],
+            ..Src::default()
+        };
+        mismatch.procedures[0].return_type = Some(schema_type(SchemaIdentifier::Int32));
+        assert!(
+            message(check_interfaces(&mismatch, &[]))
+                .starts_with("the implementation of 'log:info' does not match")
+        );
+    }
+}
@@ -0,0 +1,927 @@
+use std::io::Write;
+
+use crate::compilation_error::{
+    CallDepthExceeded, CompilationError, FailedToWriteToFile, InterpreterUnsupported,
+    ProcedureHasNoValue, TypeNotPrintable, UnknownProcedure, UnknownVariable,
+    VariableTypeAmbiguous,
+};
+use crate::core::Of;
+use crate::formatter::assertion_failure;
+use crate::palel::*;
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::{context, transpile};
+use crate::transpiler_context::{Context, Returns};
+use crate::type_checking::{determine_variable_type, type_of_expression};
+
+use SchemaIdentifier::*;
+
+/// Deeper calls can only come from unbounded recursion, the language has no
+/// conditionals yet.
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// A value as the C backend represents it: every integer type and `Bool` as
+/// an integer wrapped to the width of its type, nullable values as the same
+/// sentinels `CToolKit::transpile_null` uses.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Int(i128),
+    Float(f64),
+    Char(u8),
+    /// `None` is the null pointer of a `String?`
+    String(Option<String>),
+    StringList(Vec<String>),
+}
+
+/// Outcome of running part of a program, `process:exit` unwinds every call
+/// with `Exit`.
+pub enum Run<T> {
+    Ok(T),
+    Exit(i32),
+    Error(Box<dyn CompilationError>),
+}
+
+use Run::*;
+
+/// What a statement leaves to the block running it.
+enum Flow {
+    Next,
+    Return(Option<Value>),
+}
+
+/// Runs `program` of `input` without compiling it and returns its exit
+/// status. The program is checked like it is for the C backend first, so both
+/// reject the same programs. `program_name` stands in for `argv[0]`.
+pub fn interpret(
+    input: &Src,
+    program: &Program,
+    program_name: &str,
+    arguments: &[String],
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> Of<i32> {
+    let toolkit = CToolKit {};
+    if let Of::Error(err) = transpile(input, &toolkit) {
+        return Of::Error(err);
+    }
+    let mut interpreter = Interpreter {
+        src: input,
+        toolkit,
+        out,
+        err,
+        depth: 0,
+    };
+    let status = match interpreter.run_program(program, program_name, arguments) {
+        Ok(status) | Exit(status) => status,
+        Error(err) => return Of::Error(err),
+    };
+    if interpreter.out.flush().is_err() {
+        return Of::Error(Box::new(FailedToWriteToFile {
+            file: "<stdout>".to_string(),
+        }));
+    }
+    Of::Ok(status)
+}
+
+/// The module the entries of the REPL belong to. It imports every module of
+/// the project and, not being an identifier, cannot clash with one of them.
+const REPL_MODULE: &str = "<repl>";
+
+/// Runs the entries of the REPL one after another, the variables they declare
+/// stay for the entries after them.
+pub struct Session {
+    src: Src,
+    frame: Frame,
+}
+
+impl Session {
+    /// `src` must pass `transpile`, its public procedures are callable as
+    /// `module.procedure`.
+    pub fn new(mut src: Src) -> Session {
+        let imports = src.modules.iter().map(|m| m.name.clone()).collect();
+        src.modules.push(Module {
+            name: REPL_MODULE.to_string(),
+            file: "".to_string(),
+            imports,
+        });
+        let mut ctx = context(&src, REPL_MODULE, &CToolKit {});
+        ctx.returns = Returns::Nothing;
+        Session {
+            src,
+            frame: Frame {
+                ctx,
+                values: vec![],
+            },
+        }
+    }
+
+    /// What the entries can refer to: the variables declared so far and every
+    /// callable procedure.
+    pub fn context(&self) -> &Context {
+        &self.frame.ctx
+    }
+
+    /// Runs `statement`, which has been checked against `context()`.
+    pub fn execute(
+        &mut self,
+        statement: &Statement,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
+    ) -> Run<()> {
+        let mut interpreter = Interpreter {
+            src: &self.src,
+            toolkit: CToolKit {},
+            out,
+            err,
+            depth: 0,
+        };
+        match interpreter.run_statement(statement, &mut self.frame) {
+            Ok(_) => Ok(()),
+            Exit(status) => Exit(status),
+            Error(err) => Error(err),
+        }
+    }
+
+    /// Evaluates `expression`, which has been checked against `context()`, as
+    /// a value of `typ`.
+    pub fn evaluate(
+        &mut self,
+        expression: &Expression,
+        typ: &Type,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
+    ) -> Run<Value> {
+        let mut interpreter = Interpreter {
+            src: &self.src,
+            toolkit: CToolKit {},
+            out,
+            err,
+            depth: 0,
+        };
+        interpreter.eval(expression, typ, &mut self.frame)
+    }
+}
+
+struct Interpreter<'a> {
+    src: &'a Src,
+    toolkit: CToolKit,
+    out: &'a mut dyn Write,
+    /// Where usage and assertion failures go, stderr like the C backend
+    err: &'a mut dyn Write,
+    depth: usize,
+}
+
+/// The variables of the procedure being run, their types are kept in the
+/// context alongside.
+struct Frame {
+    ctx: Context,
+    values: Vec<(String, Value)>,
+}
+
+impl Frame {
+    fn declare(&mut self, name: &str, typ: Type, value: Value) {
+        self.ctx.declare(name, typ);
+        self.values.push((name.to_string(), value));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    fn run_program(
+        &mut self,
+        program: &Program,
+        program_name: &str,
+        arguments: &[String],
+    ) -> Run<i32> {
+        let (strings, rest) = match program.parameters.split_last() {
+            Some((last, strings)) if last.schema_type.identifier == StringList => {
+                (strings, Some(last))
+            }
+            _ => (program.parameters.as_slice(), None),
+        };
+        let required = strings
+            .iter()
+            .filter(|p| p.schema_type.postfix != TypePostfix::Opt)
+            .count();
+        if arguments.len() < required || rest.is_none() && arguments.len() > strings.len() {
+            let mut usage = format!("usage: {}", program_name);
+            for parameter in &program.parameters {
+                if parameter.schema_type.identifier == StringList {
+                    usage.push_str(&format!(" [{}...]", parameter.identifier));
+                } else if parameter.schema_type.postfix == TypePostfix::Opt {
+                    usage.push_str(&format!(" [{}]", parameter.identifier));
+                } else {
+                    usage.push_str(&format!(" <{}>", parameter.identifier));
+                }
+            }
+            let _ = writeln!(self.err, "{}", usage);
+            return Ok(2);
+        }
+
+        let mut frame = Frame {
+            ctx: context(self.src, &program.module, &self.toolkit),
+            values: vec![],
+        };
+        frame.ctx.returns = Returns::ExitStatus;
+        for (idx, parameter) in program.parameters.iter().enumerate() {
+            let value = match rest {
+                Some(rest) if rest == parameter => {
+                    Value::StringList(arguments.iter().skip(strings.len()).cloned().collect())
+                }
+                _ => Value::String(arguments.get(idx).cloned()),
+            };
+            frame.declare(
+                &parameter.identifier,
+                Type::Dim(parameter.schema_type.clone()),
+                value,
+            );
+        }
+        match self.run_block(&program.do_block, &mut frame) {
+            Ok(Flow::Return(Some(Value::Int(status)))) => Ok(status as i32),
+            Ok(_) => Ok(0),
+            Exit(status) => Exit(status),
+            Error(err) => Error(err),
+        }
+    }
+
+    fn run_block(&mut self, block: &DoBlock, frame: &mut Frame) -> Run<Flow> {
+        for statement in &block.statements {
+            match self.run_statement(statement, frame) {
+                Ok(Flow::Next) => {}
+                other => return other,
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn run_statement(&mut self, statement: &Statement, frame: &mut Frame) -> Run<Flow> {
+        match statement {
+            Statement::ProcedureCall(call) => match self.call(call, frame) {
+                Ok(_) => Ok(Flow::Next),
+                Exit(status) => Exit(status),
+                Error(err) => Error(err),
+            },
+            Statement::Return(ret) => {
+                let value = match (&ret.value, &frame.ctx.returns) {
+                    (Some(value), Returns::Value(_) | Returns::ExitStatus) => {
+                        let typ = match &frame.ctx.returns {
+                            Returns::Value(typ) => typ.clone(),
+                            _ => Type::Dim(schema_type(Int32)),
+                        };
+                        match self.eval(value, &typ, frame) {
+                            Ok(value) => Some(value),
+                            Exit(status) => return Exit(status),
+                            Error(err) => return Error(err),
+                        }
+                    }
+                    _ => None,
+                };
+                Ok(Flow::Return(value))
+            }
+            Statement::Variable(declaration) => {
+                let typ = match determine_variable_type(
+                    declaration.memory.clone(),
+                    declaration.schema_type.clone(),
+                    &declaration.expression,
+                    &frame.ctx,
+                ) {
+                    Some(typ) => typ,
+                    None => return Error(Box::new(VariableTypeAmbiguous {})),
+                };
+                let value = match self.eval(&declaration.expression, &typ, frame) {
+                    Ok(value) => value,
+                    Exit(status) => return Exit(status),
+                    Error(err) => return Error(err),
+                };
+                frame.declare(&declaration.identifier, typ, value);
+                Ok(Flow::Next)
+            }
+            Statement::Assert(assert) => {
+                let condition = Type::Dim(schema_type(Bool));
+                match self.eval(&assert.condition, &condition, frame) {
+                    Ok(Value::Int(0)) => {}
+                    Ok(_) => return Ok(Flow::Next),
+                    Exit(status) => return Exit(status),
+                    Error(err) => return Error(err),
+                }
+                if writeln!(self.err, "{}", assertion_failure(assert)).is_err() {
+                    return Error(Box::new(FailedToWriteToFile {
+                        file: "<stderr>".to_string(),
+                    }));
+                }
+                Exit(1)
+            }
+        }
+    }
+
+    /// Evaluates `expression` as a value of `typ`, the type it is assigned or
+    /// passed to.
+    fn eval(&mut self, expression: &Expression, typ: &Type, frame: &mut Frame) -> Run<Value> {
+        let schema = match typ {
+            Type::Dim(schema) => schema,
+            Type::Ref(schema) if schema.identifier == CharSeq => schema,
+            _ => {
+                return Error(Box::new(InterpreterUnsupported {
+                    feature: "references and addresses".to_string(),
+                }));
+            }
+        };
+        match expression {
+            Expression::Literal(literal) => eval_literal(literal, schema),
+            Expression::Variable(name) => match frame.lookup(name) {
+                Some(value) => Ok(convert(value.clone(), &schema.identifier)),
+                None => Error(Box::new(UnknownVariable { name: name.clone() })),
+            },
+            Expression::Cast(cast) => match self.eval_cast(cast, frame) {
+                Ok(value) => Ok(convert(value, &schema.identifier)),
+                other => other,
+            },
+            Expression::Call(call) => match self.call(call, frame) {
+                Ok(Some(value)) => Ok(convert(value, &schema.identifier)),
+                Ok(None) => Error(Box::new(ProcedureHasNoValue {
+                    procedure: call_name(&call.interface, &call.identifier),
+                })),
+                Exit(status) => Exit(status),
+                Error(err) => Error(err),
+            },
+        }
+    }
+
+    /// Evaluates `expression` as a value of its own type.
+    fn eval_own_type(&mut self, expression: &Expression, frame: &mut Frame) -> Run<(Value, Type)> {
+        let typ = match type_of_expression(expression, &frame.ctx).and_then(|t| t.to_type()) {
+            Some(typ) => typ,
+            None => return Error(Box::new(VariableTypeAmbiguous {})),
+        };
+        match self.eval(expression, &typ, frame) {
+            Ok(value) => Ok((value, typ)),
+            Exit(status) => Exit(status),
+            Error(err) => Error(err),
+        }
+    }
+
+    fn eval_cast(&mut self, cast: &Cast, frame: &mut Frame) -> Run<Value> {
+        let (value, from) = match self.eval_own_type(&cast.expression, frame) {
+            Ok((value, Type::Dim(from) | Type::Ref(from))) => (value, from),
+            Ok(_) => return Error(Box::new(VariableTypeAmbiguous {})),
+            Exit(status) => return Exit(status),
+            Error(err) => return Error(err),
+        };
+        Ok(cast_value(
+            value,
+            &from.identifier,
+            &cast.mode,
+            &cast.target.identifier,
+        ))
+    }
+
+    fn call(&mut self, call: &ProcedureCall, frame: &mut Frame) -> Run<Option<Value>> {
+        let name = call_name(&call.interface, &call.identifier);
+        let signature = match frame.ctx.lookup_procedure(&name) {
+            Some(signature) => signature.clone(),
+            None => return Error(Box::new(UnknownProcedure { name })),
+        };
+        match &signature.implementation {
+            Implementation::Builtin => return self.call_builtin(call, frame),
+            Implementation::C(symbol) | Implementation::Header(symbol) => {
+                return Error(Box::new(InterpreterUnsupported {
+                    feature: format!("calling C through '{}' ({})", name, symbol),
+                }));
+            }
+            Implementation::Palel => {}
+        }
+        let procedure = match self.src.procedures.iter().find(|p| {
+            p.interface == signature.interface
+                && p.identifier == signature.identifier
+                && (!p.interface.is_empty() || p.module == signature.module)
+        }) {
+            Some(procedure) => procedure,
+            None => return Error(Box::new(UnknownProcedure { name })),
+        };
+
+        let mut callee = Frame {
+            ctx: context(self.src, &procedure.module, &self.toolkit),
+            values: vec![],
+        };
+        callee.ctx.returns = match &signature.return_type {
+            Some(typ) => Returns::Value(typ.clone()),
+            None => Returns::Nothing,
+        };
+        for ((parameter, typ), argument) in procedure
+            .parameters
+            .iter()
+            .zip(&signature.parameters)
+            .zip(&call.arguments)
+        {
+            let value = match self.eval(argument, typ, frame) {
+                Ok(value) => value,
+                Exit(status) => return Exit(status),
+                Error(err) => return Error(err),
+            };
+            callee.declare(&parameter.identifier, typ.clone(), value);
+        }
+
+        if self.depth >= MAX_CALL_DEPTH {
+            return Error(Box::new(CallDepthExceeded { procedure: name }));
+        }
+        self.depth += 1;
+        let result = self.run_block(&procedure.do_block, &mut callee);
+        self.depth -= 1;
+        match result {
+            Ok(Flow::Return(value)) => Ok(value),
+            Ok(Flow::Next) => Ok(None),
+            Exit(status) => Exit(status),
+            Error(err) => Error(err),
+        }
+    }
+
+    fn call_builtin(&mut self, call: &ProcedureCall, frame: &mut Frame) -> Run<Option<Value>> {
+        match (call.interface.as_str(), call.identifier.as_str()) {
+            ("debug", "print") => {
+                let mut output = String::new();
+                for argument in &call.arguments {
+                    match self.eval_own_type(argument, frame) {
+                        Ok((_, Type::Dim(schema) | Type::Ref(schema)))
+                            if schema.identifier == StringList =>
+                        {
+                            return Error(Box::new(TypeNotPrintable {
+                                received_type: schema,
+                            }));
+                        }
+                        Ok((value, Type::Dim(schema) | Type::Ref(schema))) => {
+                            output.push_str(&print_value(&value, &schema));
+                        }
+                        Ok(_) => {
+                            return Error(Box::new(InterpreterUnsupported {
+                                feature: "printing addresses".to_string(),
+                            }));
+                        }
+                        Exit(status) => return Exit(status),
+                        Error(err) => return Error(err),
+                    }
+                }
+                if self.out.write_all(output.as_bytes()).is_err() {
+                    return Error(Box::new(FailedToWriteToFile {
+                        file: "<stdout>".to_string(),
+                    }));
+                }
+                Ok(None)
+            }
+            ("process", "exit") => {
+                let status = Type::Dim(schema_type(Int32));
+                match self.eval(&call.arguments[0], &status, frame) {
+                    Ok(Value::Int(status)) => Exit(status as i32),
+                    Ok(_) => Error(Box::new(VariableTypeAmbiguous {})),
+                    Exit(status) => Exit(status),
+                    Error(err) => Error(err),
+                }
+            }
+            ("strings", "count" | "get") => {
+                let list = match self.eval(
+                    &call.arguments[0],
+                    &Type::Dim(schema_type(StringList)),
+                    frame,
+                ) {
+                    Ok(Value::StringList(list)) => list,
+                    Ok(_) => return Error(Box::new(VariableTypeAmbiguous {})),
+                    Exit(status) => return Exit(status),
+                    Error(err) => return Error(err),
+                };
+                if call.identifier == "count" {
+                    return Ok(Some(Value::Int(list.len() as i128)));
+                }
+                match self.eval(&call.arguments[1], &Type::Dim(schema_type(Int32)), frame) {
+                    Ok(Value::Int(index)) => Ok(Some(Value::String(
+                        usize::try_from(index)
+                            .ok()
+                            .and_then(|index| list.get(index).cloned()),
+                    ))),
+                    Ok(_) => Error(Box::new(VariableTypeAmbiguous {})),
+                    Exit(status) => Exit(status),
+                    Error(err) => Error(err),
+                }
+            }
+            _ => Error(Box::new(UnknownProcedure {
+                name: call_name(&call.interface, &call.identifier),
+            })),
+        }
+    }
+}
+
+fn eval_literal(literal: &Literal, schema: &SchemaType) -> Run<Value> {
+    let value = match literal {
+        Literal::String(value) if schema.identifier == Char => {
+            Value::Char(value.bytes().next().unwrap_or(0))
+        }
+        Literal::String(value) => Value::String(Some(value.clone())),
+        Literal::Char(value) => Value::Char(*value as u8),
+        Literal::Boolean(value) => Value::Int(*value as i128),
+        Literal::Null => return null_value(schema),
+        Literal::Number(number) if schema.family == TypeFamily::Float || number.contains('.') => {
+            match number.parse::<f64>().ok() {
+                Some(value) => convert(Value::Float(value), &schema.identifier),
+                None => return Error(Box::new(VariableTypeAmbiguous {})),
+            }
+        }
+        Literal::Number(number) => match number.parse::<i128>().ok() {
+            Some(value) => convert(Value::Int(value), &schema.identifier),
+            None => return Error(Box::new(VariableTypeAmbiguous {})),
+        },
+    };
+    Ok(value)
+}
+
+/// The sentinel standing for null in a value of `schema`.
+fn null_value(schema: &SchemaType) -> Run<Value> {
+    let value = match schema.identifier {
+        Int8 | Int16 | Int32 | Int64 | UInt8 | UInt16 | UInt32 | UInt64 | Size => {
+            match (schema.family.clone(), schema.identifier.runtime_range()) {
+                (TypeFamily::Int, Some((min, _))) => Value::Int(min),
+                (_, Some((_, max))) => Value::Int(max),
+                _ => Value::Int(0),
+            }
+        }
+        Float64 => Value::Float(-f64::MAX),
+        Bool => Value::Int(3),
+        Char => Value::Char(0),
+        CharSeq => Value::String(None),
+        _ => {
+            return Error(Box::new(InterpreterUnsupported {
+                feature: format!("null of type {}", schema.identifier.to_string()),
+            }));
+        }
+    };
+    Ok(value)
+}
+
+/// Converts `value` like C converts it on assignment to `to`.
+fn convert(value: Value, to: &SchemaIdentifier) -> Value {
+    match (to.family(), value) {
+        (TypeFamily::Int | TypeFamily::UInt, Value::Int(value)) => Value::Int(wrap(value, to)),
+        (TypeFamily::Int | TypeFamily::UInt, Value::Char(value)) => {
+            Value::Int(wrap(value as i128, to))
+        }
+        (TypeFamily::Int | TypeFamily::UInt, Value::Float(value)) => {
+            Value::Int(wrap(value.trunc() as i128, to))
+        }
+        (TypeFamily::Float, Value::Int(value)) => float(value as f64, to),
+        (TypeFamily::Float, Value::Float(value)) => float(value, to),
+        (_, Value::Int(value)) if *to == Char => Value::Char(value as u8),
+        (_, value) => value,
+    }
+}
+
+fn float(value: f64, to: &SchemaIdentifier) -> Value {
+    match to {
+        Float32 => Value::Float(value as f32 as f64),
+        _ => Value::Float(value),
+    }
+}
+
+/// Keeps the bits of `value` that fit in the integer type `to`.
+fn wrap(value: i128, to: &SchemaIdentifier) -> i128 {
+    let bits = match to {
+        Size => 64,
+        _ => to.width().unwrap_or(64) as u32,
+    };
+    let modulus = 1i128 << bits;
+    let unsigned = value.rem_euclid(modulus);
+    if to.family() == TypeFamily::Int && unsigned >= modulus / 2 {
+        return unsigned - modulus;
+    }
+    unsigned
+}
+
+/// Bounds of `to` in C as a `double`, which is how a floating point value is
+/// compared against them.
+fn c_limits(to: &SchemaIdentifier) -> (f64, f64) {
+    match to {
+        Float32 => (-f32::MAX as f64, f32::MAX as f64),
+        Float64 => (-f64::MAX, f64::MAX),
+        _ => match to.runtime_range() {
+            Some((min, max)) => (min as f64, max as f64),
+            None => (f64::NEG_INFINITY, f64::INFINITY),
+        },
+    }
+}
+
+/// Applies a cast the way the C code generated for it by
+/// `CToolKit::transpile_cast` does.
+fn cast_value(
+    value: Value,
+    from: &SchemaIdentifier,
+    mode: &CastMode,
+    to: &SchemaIdentifier,
+) -> Value {
+    // only a cast the C backend checks the bounds of fails on a NaN
+    let overflow = match mode {
+        CastMode::Wrapping => None,
+        CastMode::Checked => from.checked_cast_overflow(to),
+        CastMode::Saturating => from.cast_overflow(to),
+    };
+    if overflow.is_none() {
+        return convert(value, to);
+    }
+    // the end of an integer type that is the null of `as?` does not fit it
+    let checked = *mode == CastMode::Checked;
+    let null_below = checked && to.family() == TypeFamily::Int;
+    let null_above = checked && to.family() == TypeFamily::UInt;
+    let (below, above, nan) = match (&value, to.runtime_range()) {
+        (Value::Int(value), Some((min, max))) => (
+            *value < min || null_below && *value == min,
+            *value > max || null_above && *value == max,
+            false,
+        ),
+        (Value::Int(value), None) => {
+            let (min, max) = c_limits(to);
+            ((*value as f64) < min, (*value as f64) > max, false)
+        }
+        (Value::Float(value), _) => {
+            let (min, max) = c_limits(to);
+            let above = match to.integer_bound() {
+                Some(_) if null_above => *value >= max,
+                Some(bound) => *value >= bound as f64,
+                None => *value > max,
+            };
+            (
+                *value < min || null_below && *value == min,
+                above,
+                value.is_nan(),
+            )
+        }
+        _ => return convert(value, to),
+    };
+    match mode {
+        CastMode::Checked if below || above || nan => {
+            let mut nullable = schema_type(to.clone());
+            nullable.postfix = TypePostfix::Opt;
+            match null_value(&nullable) {
+                Ok(null) => null,
+                _ => convert(value, to),
+            }
+        }
+        CastMode::Saturating if nan && to.family() != TypeFamily::Float => Value::Int(0),
+        CastMode::Saturating if above || below => {
+            let (min, max) = c_limits(to);
+            let limit = if above { max } else { min };
+            match to.runtime_range() {
+                Some((min, max)) => Value::Int(if above { max } else { min }),
+                None => Value::Float(limit),
+            }
+        }
+        _ => convert(value, to),
+    }
+}
+
+/// Formats `value` for the REPL: like a literal of `typ`, and `null` for the
+/// sentinel of a nullable type.
+pub fn display_value(value: &Value, typ: &Type) -> String {
+    let schema = match typ {
+        Type::Dim(schema) | Type::Ref(schema) => schema,
+        Type::Addr(_) => return print_value(value, &schema_type(UInt64)),
+    };
+    let is_null = matches!(null_value(schema), Ok(null) if &null == value);
+    if schema.postfix == TypePostfix::Opt && is_null {
+        return "null".to_string();
+    }
+    match (value, &schema.identifier) {
+        (Value::Int(value), Bool) => (*value != 0).to_string(),
+        (Value::Char(value), _) => format!("{:?}", *value as char),
+        (Value::String(Some(value)), _) => format!("{:?}", value),
+        (Value::StringList(list), _) => format!("{:?}", list),
+        _ => print_value(value, schema),
+    }
+}
+
+/// Formats `value` the way the `printf` call of `debug:print` does.
+fn print_value(value: &Value, schema: &SchemaType) -> String {
+    match value {
+        Value::Int(value) => match schema.family {
+            TypeFamily::UInt => (*value as u64).to_string(),
+            _ => (*value as i64).to_string(),
+        },
+        Value::Float(value) => format_general(*value),
+        Value::Char(value) => (*value as char).to_string(),
+        // printf stops at the first NUL and prints a null pointer as (null)
+        Value::String(Some(value)) => value.split('\0').next().unwrap_or("").to_string(),
+        Value::String(None) => "(null)".to_string(),
+        // debug:print rejects a list before it gets here
+        Value::StringList(list) => list.join(" "),
+    }
+}
+
+/// C's `%g`: six significant digits, scientific notation for very small or
+/// large exponents and no trailing zeros.
+fn format_general(value: f64) -> String {
+    if value.is_nan() {
+        return match value.is_sign_negative() {
+            true => "-nan".to_string(),
+            false => "nan".to_string(),
+        };
+    }
+    if value.is_infinite() {
+        return match value < 0.0 {
+            true => "-inf".to_string(),
+            false => "inf".to_string(),
+        };
+    }
+    if value == 0.0 {
+        return match value.is_sign_negative() {
+            true => "-0".to_string(),
+            false => "0".to_string(),
+        };
+    }
+    let scientific = format!("{:.5e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    if !(-4..6).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}e{}{:02}", strip_zeros(mantissa), sign, exponent.abs());
+    }
+    let decimals = (5 - exponent) as usize;
+    strip_zeros(&format!("{:.*}", decimals, value))
+}
+
+fn strip_zeros(number: &str) -> String {
+    if !number.contains('.') {
+        return number.to_string();
+    }
+    number
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_task::SrcFile;
+    use crate::parser::parse;
+    use pretty_assertions::assert_eq;
+
+    fn run(content: &str, arguments: &[&str]) -> (String, String, i32) {
+        let file = SrcFile {
+            file: "main.palel".to_string(),
+            content: content.to_string(),
+        };
+        let mut src = Src::default();
+        if let Some(err) = parse(&mut src, &file) {
+            panic!("{}", err.message())
+        }
+        let arguments: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+        let mut out = vec![];
+        let mut err = vec![];
+        let status = match interpret(
+            &src,
+            &src.programs[0],
+            "main",
+            &arguments,
+            &mut out,
+            &mut err,
+        ) {
+            Of::Ok(status) => status,
+            Of::Error(err) => panic!("{}", err.message()),
+        };
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+            status,
+        )
+    }
+
+    #[test]
+    fn test_interpret_print_and_nullables() {
+        let (out, _, status) = run(
+            r#"
+program do
+    dim small UInt8 = 200
+    dim wide UInt64 = small
+    dim half = 0.5
+    dim big Float64 = 12345678
+    dim missing Int32? = null
+    dim name String? = null
+    debug:print("a", 'b', " ", wide, " ", half, " ", big, " ", 0.0001, " ", true, "\n")
+    debug:print(missing, " ", name, " ", 300 as UInt8, " ", -1 as? UInt8, " ", 1000 as! Int8, "\n")
+end
+"#,
+            &[],
+        );
+        assert_eq!(
+            out,
+            "ab 200 0.5 1.23457e+07 0.0001 1\n-2147483648 (null) 44 255 127\n"
+        );
+        assert_eq!(status, 0);
+    }
+
+    #[test]
+    fn test_interpret_rest_arguments() {
+        let source = r#"
+program(command String, files StringList) do
+    debug:print(command, " ", strings:count(files), " ", strings:get(files, 1), " ", strings:get(files, 2), "\n")
+end
+"#;
+        let (out, _, status) = run(source, &["cat", "a", "b"]);
+        assert_eq!(out, "cat 2 b (null)\n");
+        assert_eq!(status, 0);
+        let (out, _, _) = run(source, &["ls"]);
+        assert_eq!(out, "ls 0 (null) (null)\n");
+        let (_, err, status) = run(source, &[]);
+        assert_eq!(err, "usage: main <command> [files...]\n");
+        assert_eq!(status, 2);
+    }
+
+    #[test]
+    fn test_interpret_failed_assert() {
+        let (out, err, status) = run(
+            r#"
+program do
+    debug:print("before\n")
+    assert false, "never"
+    debug:print("after\n")
+end
+"#,
+            &[],
+        );
+        assert_eq!(out, "before\n");
+        assert_eq!(err, "assertion failed: never\n");
+        assert_eq!(status, 1);
+    }
+
+    #[test]
+    fn test_interpret_procedures_and_exit() {
+        let source = r#"
+proc pick(a Int32, b Int32) Int32 do
+    return b
+end
+
+proc stop(status Int32) do
+    process:exit(status)
+    return
+end
+
+program(mode String, code String?) do
+    debug:print(mode, " ", code, "\n")
+    dim status = pick(1, 7)
+    stop(status)
+    return 3
+end
+"#;
+        assert_eq!(
+            run(source, &["x"]),
+            ("x (null)\n".to_string(), "".to_string(), 7)
+        );
+        assert_eq!(
+            run(source, &[]),
+            ("".to_string(), "usage: main <mode> [code]\n".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn test_interpret_rejects_what_c_rejects() {
+        let error = |content: &str| {
+            let file = SrcFile {
+                file: "main.palel".to_string(),
+                content: content.to_string(),
+            };
+            let mut src = Src::default();
+            assert!(parse(&mut src, &file).is_none());
+            let (mut out, mut err) = (vec![], vec![]);
+            match interpret(&src, &src.programs[0], "main", &[], &mut out, &mut err) {
+                Of::Ok(status) => panic!("ran with status {}", status),
+                Of::Error(err) => err.message(),
+            }
+        };
+        assert_eq!(
+            error("program do\n    dim a = 1\n    dim a = 2\nend\n"),
+            "'a' is already declared, give the new variable another name"
+        );
+        assert_eq!(
+            error("program(a String) do\n    dim a = 1\nend\n"),
+            "'a' is already declared, give the new variable another name"
+        );
+        assert_eq!(
+            error("program do\n    dim a = 3000000000.5 as Int32\nend\n"),
+            "cannot cast Float64 to Int32 with `as`, the value may not fit"
+        );
+        assert_eq!(
+            error("program do\n    dim a Int32 = 300000000000\nend\n"),
+            "integer literal 300000000000 does not fit in Int32, which holds -2147483648 to 2147483647"
+        );
+    }
+
+    #[test]
+    fn test_format_general() {
+        assert_eq!(format_general(1.5811388300841898), "1.58114");
+        assert_eq!(format_general(100000.0), "100000");
+        assert_eq!(format_general(1000000.0), "1e+06");
+        assert_eq!(format_general(0.00001234), "1.234e-05");
+        assert_eq!(format_general(-2.5), "-2.5");
+        assert_eq!(format_general(-f64::MAX), "-1.79769e+308");
+    }
+}
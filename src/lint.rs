@@ -0,0 +1,360 @@
+use pest::iterators::Pair;
+
+use crate::analysis::{Scoped, blocks, child};
+use crate::palel::*;
+use crate::parser::{Rule, parse_checked, parse_statement};
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::{CTranspile, transpile_statement};
+use crate::type_checking::{determine_variable_type, type_of_expression};
+
+/// A rule of `palel lint`, named in kebab case on the command line and in
+/// `palel.toml`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Lint {
+    UnusedVariable,
+    VarNeverReassigned,
+    UntypedAddr,
+    RedundantAnnotation,
+    UnreachableCode,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedVariable,
+        Lint::VarNeverReassigned,
+        Lint::UntypedAddr,
+        Lint::RedundantAnnotation,
+        Lint::UnreachableCode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::VarNeverReassigned => "var-never-reassigned",
+            Lint::UntypedAddr => "untyped-addr",
+            Lint::RedundantAnnotation => "redundant-annotation",
+            Lint::UnreachableCode => "unreachable-code",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+/// A finding of a lint, with the edits that fix it when they can be made
+/// without changing what the program does.
+#[derive(Debug, PartialEq)]
+pub struct LintWarning {
+    pub lint: Lint,
+    pub span: Span,
+    pub message: String,
+    pub fix: Vec<Edit>,
+}
+
+/// The warnings of the `enabled` lints for the file `file` of `src` with
+/// `content`, ordered by their position. The project must pass `transpile`,
+/// the statements the C backend rejects are not linted.
+pub fn lint(src: &Src, file: &str, content: &str, enabled: &[Lint]) -> Vec<LintWarning> {
+    let root = match parse_checked(Rule::src, content) {
+        Ok(mut pairs) => match pairs.next() {
+            Some(root) => root,
+            None => return vec![],
+        },
+        Err(_) => return vec![],
+    };
+    let mut warnings = vec![];
+    for block in blocks(src, &module_name_from_file(file), root) {
+        let statements = &block.statements;
+        for (idx, scoped) in statements.iter().enumerate() {
+            if scoped.error.is_some() {
+                continue;
+            }
+            if let Some(declaration) = child(&scoped.pair, Rule::variable_statement) {
+                lint_declaration(
+                    content,
+                    &declaration,
+                    scoped,
+                    &statements[idx + 1..],
+                    &mut warnings,
+                );
+            }
+        }
+        let returned = statements
+            .iter()
+            .position(|s| child(&s.pair, Rule::return_stmt).is_some());
+        if let Some(returned) = returned
+            && returned + 1 < statements.len()
+        {
+            let unreachable = &statements[returned + 1..];
+            let first = Span::of(&unreachable[0].pair);
+            let last = Span::of(&unreachable[unreachable.len() - 1].pair);
+            warnings.push(LintWarning {
+                lint: Lint::UnreachableCode,
+                span: Span {
+                    start: first.start,
+                    end: last.end,
+                },
+                message: "the statements after return are never run".to_string(),
+                fix: unreachable
+                    .iter()
+                    .filter_map(|s| remove_line(content, Span::of(&s.pair)))
+                    .collect(),
+            });
+        }
+    }
+    warnings.retain(|warning| enabled.contains(&warning.lint));
+    warnings.sort_by_key(|warning| (warning.span.start, warning.span.end));
+    warnings
+}
+
+fn lint_declaration(
+    content: &str,
+    declaration: &Pair<'_, Rule>,
+    scoped: &Scoped<'_>,
+    rest: &[Scoped<'_>],
+    warnings: &mut Vec<LintWarning>,
+) {
+    let variable = match parse_statement(scoped.pair.clone()) {
+        Some(Statement::Variable(variable)) => variable,
+        _ => return,
+    };
+    let identifier = match child(declaration, Rule::variable_identifier) {
+        Some(identifier) => identifier,
+        None => return,
+    };
+    let name = variable.identifier.as_str();
+    let span = Span::of(&identifier);
+
+    if !is_used(name, rest) {
+        // the line can only go when the value has no side effects
+        let pure = !contains(&scoped.pair, Rule::procedure_call);
+        warnings.push(LintWarning {
+            lint: Lint::UnusedVariable,
+            span,
+            message: format!("'{}' is never used", name),
+            fix: match pure {
+                true => remove_line(content, Span::of(&scoped.pair))
+                    .into_iter()
+                    .collect(),
+                false => vec![],
+            },
+        });
+    }
+
+    match variable.memory {
+        // palel has no assignment, a var is the same as a dim
+        MemoryModifier::Var => {
+            let modifier = child(declaration, Rule::memory_modifier).map(|m| Span::of(&m));
+            warnings.push(LintWarning {
+                lint: Lint::VarNeverReassigned,
+                span,
+                message: format!("'{}' is never reassigned, declare it with dim", name),
+                fix: modifier
+                    .map(|modifier| Edit {
+                        span: modifier,
+                        replacement: "dim".to_string(),
+                    })
+                    .into_iter()
+                    .collect(),
+            });
+        }
+        MemoryModifier::Addr if variable.schema_type.is_none() => {
+            warnings.push(LintWarning {
+                lint: Lint::UntypedAddr,
+                span,
+                message: format!("addr '{}' has no type, its uses cannot be checked", name),
+                fix: annotate_addr(scoped, span).into_iter().collect(),
+            });
+        }
+        _ => {}
+    }
+
+    if let (Some(schema), Some(type_spec)) =
+        (&variable.schema_type, child(declaration, Rule::type_spec))
+        && variable.memory != MemoryModifier::Addr
+    {
+        let annotated = determine_variable_type(
+            variable.memory.clone(),
+            Some(schema.clone()),
+            &variable.expression,
+            &scoped.before,
+        );
+        let inferred = determine_variable_type(
+            variable.memory.clone(),
+            None,
+            &variable.expression,
+            &scoped.before,
+        );
+        if inferred.is_some() && inferred == annotated {
+            warnings.push(LintWarning {
+                lint: Lint::RedundantAnnotation,
+                span: Span::of(&type_spec),
+                message: format!("the type of '{}' is the one inferred from its value", name),
+                fix: vec![Edit {
+                    span: Span {
+                        start: span.end,
+                        end: type_spec.as_span().end(),
+                    },
+                    replacement: String::new(),
+                }],
+            });
+        }
+    }
+}
+
+/// Whether a statement of `rest` reads `name`.
+fn is_used(name: &str, rest: &[Scoped<'_>]) -> bool {
+    rest.iter().any(|scoped| {
+        scoped
+            .pair
+            .clone()
+            .into_inner()
+            .flat_map(|p| p.into_inner())
+            .filter(|p| p.as_rule() != Rule::variable_identifier)
+            .any(|p| reads(&p, name))
+    })
+}
+
+fn reads(pair: &Pair<'_, Rule>, name: &str) -> bool {
+    match pair.as_rule() {
+        Rule::variable_identifier => pair.as_str() == name,
+        _ => pair.clone().into_inner().any(|inner| reads(&inner, name)),
+    }
+}
+
+fn contains(pair: &Pair<'_, Rule>, rule: Rule) -> bool {
+    pair.as_rule() == rule
+        || pair
+            .clone()
+            .into_inner()
+            .any(|inner| contains(&inner, rule))
+}
+
+/// Gives an untyped addr the type of its value, when the declaration still
+/// passes the checks of the C backend with it.
+fn annotate_addr(scoped: &Scoped<'_>, identifier: Span) -> Option<Edit> {
+    let mut variable = match parse_statement(scoped.pair.clone())? {
+        Statement::Variable(variable) => variable,
+        _ => return None,
+    };
+    let schema = match type_of_expression(&variable.expression, &scoped.before)?.to_type()? {
+        Type::Dim(schema) | Type::Ref(schema) | Type::Addr(Some(schema)) => schema,
+        Type::Addr(None) => return None,
+    };
+    variable.schema_type = Some(schema.clone());
+    let annotated = Statement::Variable(variable);
+    let mut ctx = scoped.before.clone();
+    match transpile_statement(&annotated, &mut ctx, &CToolKit {}) {
+        CTranspile::Ok(..) => Some(Edit {
+            span: Span {
+                start: identifier.end,
+                end: identifier.end,
+            },
+            replacement: format!(" {}", schema.to_string()),
+        }),
+        CTranspile::Error(_) => None,
+    }
+}
+
+/// Removes the line of the statement at `span` when nothing but a comment
+/// shares the line with it.
+fn remove_line(content: &str, span: Span) -> Option<Edit> {
+    let start = content[..span.start].rfind('\n').map_or(0, |idx| idx + 1);
+    if !content[start..span.start].trim().is_empty() {
+        return None;
+    }
+    let end = content[span.end..]
+        .find('\n')
+        .map_or(content.len(), |idx| span.end + idx + 1);
+    let rest = content[span.end..end].trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return None;
+    }
+    Some(Edit {
+        span: Span { start, end },
+        replacement: String::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::apply_edits;
+    use crate::build_task::SrcFile;
+    use crate::core::Of;
+    use crate::parser::parse;
+    use crate::transpiler_c::transpile;
+    use pretty_assertions::assert_eq;
+
+    const MAIN: &str = concat!(
+        "program do\n",
+        "    var count Int64 = 1\n",
+        "    dim name String = \"palel\"\n",
+        "    dim total = count as Int64\n",
+        "    dim unused = 3 # gone\n",
+        "    addr p = name\n",
+        "    debug:print(name, total, p)\n",
+        "    return 0\n",
+        "    debug:print(name)\n",
+        "end\n",
+    );
+
+    fn lint_main(enabled: &[Lint]) -> Vec<LintWarning> {
+        let mut src = Src::default();
+        let file = SrcFile {
+            file: "main.palel".to_string(),
+            content: MAIN.to_string(),
+        };
+        assert!(parse(&mut src, &file).is_none());
+        assert!(matches!(transpile(&src, &CToolKit {}), Of::Ok(_)));
+        lint(&src, "main.palel", MAIN, enabled)
+    }
+
+    #[test]
+    fn test_lint() {
+        let warnings = lint_main(&Lint::ALL);
+        let found: Vec<(&str, &str)> = warnings
+            .iter()
+            .map(|w| (w.lint.name(), &MAIN[w.span.start..w.span.end]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("var-never-reassigned", "count"),
+                ("redundant-annotation", "String"),
+                ("unused-variable", "unused"),
+                ("untyped-addr", "p"),
+                ("unreachable-code", "debug:print(name)"),
+            ]
+        );
+        assert_eq!(
+            lint_main(&[Lint::UnusedVariable])
+                .iter()
+                .map(|w| w.message.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["'unused' is never used"]
+        );
+    }
+
+    #[test]
+    fn test_fixes() {
+        let edits: Vec<Edit> = lint_main(&Lint::ALL)
+            .into_iter()
+            .flat_map(|warning| warning.fix)
+            .collect();
+        assert_eq!(
+            apply_edits(MAIN, &edits),
+            concat!(
+                "program do\n",
+                "    dim count Int64 = 1\n",
+                "    dim name = \"palel\"\n",
+                "    dim total = count as Int64\n",
+                "    addr p String = name\n",
+                "    debug:print(name, total, p)\n",
+                "    return 0\n",
+                "end\n",
+            )
+        );
+    }
+}
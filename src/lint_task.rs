@@ -0,0 +1,145 @@
+use crate::analysis::apply_edits;
+use crate::build_task::{BuildTask, SrcFile, load_src, src_files, write};
+use crate::compilation_error::{CompilationError, LintWarningsFound, UnknownLint};
+use crate::core::Of;
+use crate::lint::{Lint, lint};
+use crate::palel::{Edit, Src};
+use crate::parser::parse;
+use crate::project_config::{PROJECT_CONFIG, load_project_config};
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::transpile;
+
+/// How often the sources are linted again after fixing them. Removing an
+/// unused variable can leave the variables of its value unused in turn.
+const MAX_ROUNDS: usize = 8;
+
+pub struct LintTaskConfig {
+    /// Make the fixes of the warnings in the source files
+    pub fix: bool,
+    /// Lints not to run
+    pub allow: Vec<String>,
+    /// Lints to run even when `palel.toml` allows them
+    pub warn: Vec<String>,
+}
+
+/// Prints the warnings of the enabled lints for every source file, after
+/// making their fixes with `fix` until no fix is left. Warnings that remain
+/// are the error.
+pub fn run_lint_task(
+    task: &mut BuildTask,
+    config: &LintTaskConfig,
+) -> Option<Box<dyn CompilationError>> {
+    let project = match load_project_config(PROJECT_CONFIG) {
+        Of::Ok(project) => project,
+        Of::Error(err) => return Some(err),
+    };
+    let mut enabled = Lint::ALL.to_vec();
+    for name in project.lint.allow.iter().chain(&config.allow) {
+        match find_lint(name) {
+            Of::Ok(lint) => enabled.retain(|l| *l != lint),
+            Of::Error(err) => return Some(err),
+        }
+    }
+    for name in &config.warn {
+        match find_lint(name) {
+            Of::Ok(lint) if !enabled.contains(&lint) => enabled.push(lint),
+            Of::Ok(_) => {}
+            Of::Error(err) => return Some(err),
+        }
+    }
+
+    let mut src = match load_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Some(err),
+    };
+    if let Of::Error(err) = transpile(&src, &CToolKit {}) {
+        return Some(err);
+    }
+    let mut files: Vec<SrcFile> = src_files(task)
+        .iter()
+        .map(|file| SrcFile {
+            file: file.file.clone(),
+            content: file.content.clone(),
+        })
+        .collect();
+
+    if config.fix {
+        let mut fixed = vec![false; files.len()];
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+            for (idx, file) in files.iter_mut().enumerate() {
+                let edits: Vec<Edit> = lint(&src, &file.file, &file.content, &enabled)
+                    .into_iter()
+                    .flat_map(|warning| warning.fix)
+                    .collect();
+                if edits.is_empty() {
+                    continue;
+                }
+                file.content = apply_edits(&file.content, &edits);
+                fixed[idx] = true;
+                changed = true;
+            }
+            if !changed {
+                break;
+            }
+            // the next round lints the fixed sources
+            src = Src::default();
+            for file in &files {
+                if let Some(err) = parse(&mut src, file) {
+                    return Some(err);
+                }
+            }
+        }
+        for (file, _) in files.iter().zip(fixed).filter(|(_, fixed)| *fixed) {
+            let output_file = format!("{}/{}", task.config.src_dir, file.file);
+            if let Some(err) = write(&output_file, &file.content) {
+                return Some(err);
+            }
+        }
+    }
+
+    let mut count = 0;
+    let mut fixable = 0;
+    for file in &files {
+        for warning in lint(&src, &file.file, &file.content, &enabled) {
+            let (line, column) = line_column(&file.content, warning.span.start);
+            println!(
+                "{}/{}:{}:{}: warning[{}]: {}",
+                task.config.src_dir,
+                file.file,
+                line,
+                column,
+                warning.lint.name(),
+                warning.message
+            );
+            count += 1;
+            if !warning.fix.is_empty() {
+                fixable += 1;
+            }
+        }
+    }
+    match count {
+        0 => None,
+        _ => Some(Box::new(LintWarningsFound { count, fixable })),
+    }
+}
+
+fn find_lint(name: &str) -> Of<Lint> {
+    match Lint::from_name(name) {
+        Some(lint) => Of::Ok(lint),
+        None => Of::Error(Box::new(UnknownLint {
+            name: name.to_string(),
+            known: Lint::ALL.iter().map(|l| l.name().to_string()).collect(),
+        })),
+    }
+}
+
+/// The line and column of `offset` in `content`, both starting at 1.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
@@ -0,0 +1,391 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, Formatting, GotoDefinition, HoverRequest, Request as LspRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentFormattingParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url,
+};
+use walkdir::WalkDir;
+
+use crate::analysis::Workspace;
+use crate::compilation_error::{CompilationError, LanguageServerFailed};
+use crate::formatter::format;
+use crate::palel::Span;
+
+type LspResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Serves the Language Server Protocol over standard input and output until
+/// the editor shuts the server down. `src_dir` is resolved against the root
+/// of the workspace the editor opens.
+pub fn run_lsp_server(src_dir: &str) -> Option<Box<dyn CompilationError>> {
+    let (connection, io_threads) = Connection::stdio();
+    if let Err(err) = serve(connection, src_dir) {
+        return Some(Box::new(LanguageServerFailed {
+            reason: err.to_string(),
+        }));
+    }
+    match io_threads.join() {
+        Ok(()) => None,
+        Err(err) => Some(Box::new(LanguageServerFailed {
+            reason: err.to_string(),
+        })),
+    }
+}
+
+struct Server {
+    connection: Connection,
+    src_dir: PathBuf,
+    workspace: Workspace,
+    /// Documents open in the editor, their diagnostics are kept up to date
+    open: Vec<Url>,
+}
+
+fn serve(connection: Connection, src_dir: &str) -> LspResult<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    #[allow(deprecated)]
+    let root = params
+        .workspace_folders
+        .and_then(|folders| folders.into_iter().next().map(|folder| folder.uri))
+        .or(params.root_uri)
+        .and_then(|uri| uri.to_file_path().ok())
+        .unwrap_or_default();
+    let mut server = Server {
+        connection,
+        src_dir: root.join(src_dir),
+        workspace: Workspace::default(),
+        open: vec![],
+    };
+    server.load();
+    server.run()
+}
+
+impl Server {
+    /// Reads the saved source files of the project.
+    fn load(&mut self) {
+        for entry in WalkDir::new(&self.src_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+        {
+            let file = match entry.path().strip_prefix(&self.src_dir) {
+                Ok(file) => file.to_string_lossy().into_owned(),
+                Err(_) => continue,
+            };
+            if file.ends_with(".palel")
+                && let Ok(content) = fs::read_to_string(entry.path())
+            {
+                self.workspace.set(&file, content);
+            }
+        }
+    }
+
+    fn run(&mut self) -> LspResult<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.respond(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => self.notify(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => serde_json::from_value(request.params)
+                .map_err(|e| e.into())
+                .and_then(|params| self.hover(params))
+                .and_then(|hover| Ok(serde_json::to_value(hover)?)),
+            GotoDefinition::METHOD => serde_json::from_value(request.params)
+                .map_err(|e| e.into())
+                .and_then(|params| self.definition(params))
+                .and_then(|location| Ok(serde_json::to_value(location)?)),
+            Completion::METHOD => serde_json::from_value(request.params)
+                .map_err(|e| e.into())
+                .and_then(|params| self.completion(params))
+                .and_then(|items| Ok(serde_json::to_value(items)?)),
+            Formatting::METHOD => serde_json::from_value(request.params)
+                .map_err(|e| e.into())
+                .and_then(|params| self.formatting(params))
+                .and_then(|edits| Ok(serde_json::to_value(edits)?)),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("palel does not support {}", method),
+                );
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+        }
+    }
+
+    fn notify(&mut self, notification: Notification) -> LspResult<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+                self.workspace.set(&self.file(&document.uri), document.text);
+                if !self.open.contains(&document.uri) {
+                    self.open.push(document.uri);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // the whole document is synchronized, the last change holds it
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    let file = self.file(&params.text_document.uri);
+                    self.workspace.set(&file, change.text);
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.open.retain(|open| open != &uri);
+                // the saved content of a project file stays in the workspace
+                let file = self.file(&uri);
+                let path = uri.to_file_path().unwrap_or_default();
+                match fs::read_to_string(&path) {
+                    Ok(content) if path.starts_with(&self.src_dir) => {
+                        self.workspace.set(&file, content)
+                    }
+                    _ => self.workspace.remove(&file),
+                }
+                self.publish(uri, vec![])?;
+            }
+            _ => return Ok(()),
+        }
+        // a change to one file can fix or break the files using it
+        for uri in self.open.clone() {
+            let file = self.file(&uri);
+            let content = self.workspace.content(&file).unwrap_or_default();
+            let diagnostics = self
+                .workspace
+                .diagnostics(&file)
+                .into_iter()
+                .map(|diagnostic| Diagnostic {
+                    range: range(content, diagnostic.span),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("palel".to_string()),
+                    message: diagnostic.message,
+                    ..Diagnostic::default()
+                })
+                .collect();
+            self.publish(uri, diagnostics)?;
+        }
+        Ok(())
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> LspResult<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))?;
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> LspResult<Option<Hover>> {
+        let document = params.text_document_position_params;
+        let file = self.file(&document.text_document.uri);
+        let content = self.workspace.content(&file).unwrap_or_default();
+        let hover = self
+            .workspace
+            .hover(&file, offset(content, document.position))
+            .map(|(span, text)| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("```palel\n{}\n```", text),
+                }),
+                range: Some(range(content, span)),
+            });
+        Ok(hover)
+    }
+
+    fn definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> LspResult<Option<GotoDefinitionResponse>> {
+        let document = params.text_document_position_params;
+        let file = self.file(&document.text_document.uri);
+        let content = self.workspace.content(&file).unwrap_or_default();
+        let (target, span) = match self
+            .workspace
+            .definition(&file, offset(content, document.position))
+        {
+            Some(definition) => definition,
+            None => return Ok(None),
+        };
+        let target_content = self.workspace.content(&target).unwrap_or_default();
+        let uri = match Url::from_file_path(self.src_dir.join(&target)) {
+            Ok(uri) => uri,
+            Err(()) => return Ok(None),
+        };
+        Ok(Some(GotoDefinitionResponse::Scalar(Location {
+            uri,
+            range: range(target_content, span),
+        })))
+    }
+
+    fn completion(&self, params: CompletionParams) -> LspResult<Option<CompletionResponse>> {
+        let document = params.text_document_position;
+        let file = self.file(&document.text_document.uri);
+        let content = self.workspace.content(&file).unwrap_or_default();
+        let items = self
+            .workspace
+            .completion(&file, offset(content, document.position))
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(completion.detail),
+                ..CompletionItem::default()
+            })
+            .collect();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    fn formatting(&self, params: DocumentFormattingParams) -> LspResult<Option<Vec<TextEdit>>> {
+        let file = self.file(&params.text_document.uri);
+        let content = match self.workspace.content(&file) {
+            Some(content) => content,
+            None => return Ok(None),
+        };
+        // nothing to format until the document parses
+        let formatted = match format(content) {
+            Some(formatted) if formatted != content => formatted,
+            _ => return Ok(Some(vec![])),
+        };
+        let whole = Span {
+            start: 0,
+            end: content.len(),
+        };
+        Ok(Some(vec![TextEdit {
+            range: range(content, whole),
+            new_text: formatted,
+        }]))
+    }
+
+    /// The name of the document in the workspace, relative to the source
+    /// directory like the build names it, so it belongs to the same module.
+    fn file(&self, uri: &Url) -> String {
+        let path = uri
+            .to_file_path()
+            .unwrap_or_else(|_| PathBuf::from(uri.path()));
+        match path.strip_prefix(&self.src_dir) {
+            Ok(file) => file.to_string_lossy().into_owned(),
+            Err(_) => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// The byte offset of `position`, whose character counts UTF-16 code units.
+fn offset(content: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match content[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return content.len(),
+        }
+    }
+    let mut character = 0;
+    for (idx, c) in content[line_start..].char_indices() {
+        if c == '\n' || character >= position.character {
+            return line_start + idx;
+        }
+        character += c.len_utf16() as u32;
+    }
+    content.len()
+}
+
+fn position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+fn range(content: &str, span: Span) -> Range {
+    Range {
+        start: position(content, span.start),
+        end: position(content, span.end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_positions() {
+        let content = "dim s = \"\u{1F600}\"\ndim n = 1\n";
+        let after_emoji = content.find("\"\n").unwrap();
+        let emoji_position = Position {
+            line: 0,
+            character: 11,
+        };
+        assert_eq!(position(content, after_emoji), emoji_position);
+        assert_eq!(offset(content, emoji_position), after_emoji);
+        let second_line = Position {
+            line: 1,
+            character: 4,
+        };
+        assert_eq!(offset(content, second_line), content.find("n =").unwrap());
+        assert_eq!(position(content, content.find("n =").unwrap()), second_line);
+        assert_eq!(
+            offset(
+                content,
+                Position {
+                    line: 1,
+                    character: 99
+                }
+            ),
+            content.len() - 1
+        );
+    }
+}
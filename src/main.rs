@@ -1,25 +1,363 @@
+mod analysis;
+mod bench_task;
+mod bindgen;
+mod bindgen_task;
 mod build_task;
 mod c;
 mod compilation_error;
 mod core;
+mod coverage;
+mod doc_task;
 mod downstream_compiler_c;
+mod fix_task;
+mod fmt_task;
+mod formatter;
+mod interface_resolution;
+mod interpreter;
+mod lint;
+mod lint_task;
+mod lsp;
+mod module_resolution;
 mod palel;
 mod parser;
+mod project_config;
 mod renderer_c;
+mod renderer_doc;
+mod repl;
+mod repl_task;
+mod run_task;
+#[cfg(test)]
+mod test_support;
+mod test_task;
 mod toolkit_c;
 mod transpiler_c;
 mod transpiler_c_patch;
+mod transpiler_context;
 mod type_checking;
 
-use std::process;
+use std::{env, process};
 
-use crate::build_task::{create_build_task, default_build_task_config, run_build_task};
+use clap::{Args, Parser, Subcommand};
+
+use crate::bench_task::{BenchTaskConfig, run_bench_task};
+use crate::bindgen_task::{BindgenTaskConfig, run_bindgen_task};
+use crate::build_task::{
+    BuildTaskConfig, create_build_task, default_build_task_config, run_build_task,
+};
+use crate::compilation_error::CompilationError;
+use crate::core::Of;
+use crate::doc_task::run_doc_task;
+use crate::downstream_compiler_c::Profile;
+use crate::fix_task::run_fix_task;
+use crate::fmt_task::{FmtTaskConfig, run_fmt_task};
+use crate::lint_task::{LintTaskConfig, run_lint_task};
+use crate::lsp::run_lsp_server;
+use crate::project_config::{PROJECT_CONFIG, c_source_dirs, compiler_config, load_project_config};
+use crate::repl_task::run_repl_task;
+use crate::run_task::{RunTaskConfig, run_run_task};
+use crate::test_task::{TestTaskConfig, run_test_task};
+
+#[derive(Parser)]
+#[command(name = "palel", about = "Compiler for the palel language")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile every program into an executable, the default when no command is given
+    Build {
+        /// Only build the program with this name
+        #[arg(long)]
+        bin: Option<String>,
+        /// Build a static and a shared library of the `pub extern` procedures
+        #[arg(long, conflicts_with = "bin")]
+        lib: bool,
+        #[command(flatten)]
+        compiler: CompilerArgs,
+    },
+    /// Build a program and run it, the exit status is the one of the program
+    Run {
+        /// The program to run when the project has several
+        #[arg(long)]
+        bin: Option<String>,
+        /// Evaluate the program directly instead of compiling it with the C compiler
+        #[arg(long)]
+        interpret: bool,
+        #[command(flatten)]
+        compiler: CompilerArgs,
+        /// Arguments passed to the program, after `--`. They are bound in order
+        /// to its String parameters and a last StringList parameter collects
+        /// the rest, a program given too few or too many prints its usage and
+        /// exits with status 2
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Evaluate declarations and expressions entered line by line
+    Repl,
+    /// Serve the Language Server Protocol over standard input and output
+    Lsp,
+    /// Make the fixes the errors of the source files suggest
+    Fix,
+    /// Rewrite the source files in the canonical format
+    Fmt {
+        /// Leave the files untouched, print a diff and fail when one is not formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Report style and correctness warnings of the source files
+    Lint {
+        /// Make the fixes of the warnings that have one
+        #[arg(long)]
+        fix: bool,
+        /// A lint not to run, may be given more than once
+        #[arg(short = 'A', long)]
+        allow: Vec<String>,
+        /// A lint to run even when palel.toml allows it, may be given more than once
+        #[arg(short = 'W', long)]
+        warn: Vec<String>,
+    },
+    /// Build the test blocks into a harness and run them
+    Test {
+        /// Only run the tests whose name contains it
+        filter: Option<String>,
+        /// Report which statements of the procedures the tests ran, as text
+        /// and lcov in the coverage folder of the build directory
+        #[arg(long)]
+        coverage: bool,
+        #[command(flatten)]
+        compiler: CompilerArgs,
+    },
+    /// Build the bench blocks with optimizations, time them and compare with the last run
+    Bench {
+        /// Only run the benches whose name contains it
+        filter: Option<String>,
+        /// Untimed iterations before the timed ones
+        #[arg(long, default_value_t = 10)]
+        warmup: usize,
+        /// Timed iterations of every bench
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+        /// Percent a bench may get slower than in the last run before it is a regression
+        #[arg(long, default_value_t = 5.0)]
+        threshold: f64,
+        #[command(flatten)]
+        compiler: CompilerArgs,
+    },
+    /// Generate API documentation into the doc folder of the build directory
+    Doc,
+    /// Generate an extern interface for the functions declared by a C header
+    Bindgen {
+        /// The C header to read
+        header: String,
+        /// How the extern includes the header, its file name by default
+        #[arg(long)]
+        include: Option<String>,
+        /// A library to link, may be given more than once
+        #[arg(long)]
+        link: Vec<String>,
+        /// The palel file to write, standard output by default
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+/// Options of the downstream C compiler, they override `palel.toml`.
+#[derive(Args, Default)]
+struct CompilerArgs {
+    /// The C compiler to run, `CC` or gcc by default
+    #[arg(long)]
+    cc: Option<String>,
+    /// Optimize and leave out debug information
+    #[arg(long)]
+    release: bool,
+    /// Optimization level passed as -O<level>
+    #[arg(short = 'O', long = "opt-level", value_parser = ["0", "1", "2", "3", "s", "z", "g"])]
+    optimization: Option<String>,
+    /// Warning passed as -W<warning>, may be given more than once
+    #[arg(short = 'W', long = "warning")]
+    warnings: Vec<String>,
+    /// Directory searched for C headers
+    #[arg(short = 'I', long = "include-dir")]
+    include_dirs: Vec<String>,
+    /// Directory searched for libraries
+    #[arg(short = 'L', long = "library-dir")]
+    library_dirs: Vec<String>,
+    /// Preprocessor definition, NAME or NAME=value
+    #[arg(short = 'D', long = "define")]
+    defines: Vec<String>,
+    /// Print the commands run to compile the C code and the warnings of the compiler
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// Applies the C settings of `palel.toml` and the command-line options on
+/// top of them to `config`.
+fn configure_c(
+    args: &CompilerArgs,
+    config: &mut BuildTaskConfig,
+) -> Option<Box<dyn CompilationError>> {
+    let project = match load_project_config(PROJECT_CONFIG) {
+        Of::Ok(project) => project,
+        Of::Error(err) => return Some(err),
+    };
+    let profile = match args.release {
+        true => Profile::Release,
+        false => Profile::Debug,
+    };
+    let mut compiler = compiler_config(&project, profile, env::var("CC").ok());
+    if let Some(cc) = &args.cc {
+        compiler.compiler = cc.clone();
+    }
+    if args.optimization.is_some() {
+        compiler.optimization = args.optimization.clone();
+    }
+    compiler.warnings.extend(args.warnings.iter().cloned());
+    compiler
+        .include_dirs
+        .extend(args.include_dirs.iter().cloned());
+    compiler
+        .library_dirs
+        .extend(args.library_dirs.iter().cloned());
+    compiler.defines.extend(args.defines.iter().cloned());
+    compiler.verbose = args.verbose;
+    config.compiler = compiler;
+    config.c_dirs = c_source_dirs(&project, profile);
+    None
+}
 
 fn main() {
-    let config = default_build_task_config();
+    let cli = Cli::parse();
+    if let Some(Command::Bindgen {
+        header,
+        include,
+        link,
+        output,
+    }) = cli.command
+    {
+        let config = BindgenTaskConfig {
+            header,
+            include,
+            links: link,
+            output,
+        };
+        if let Some(err) = run_bindgen_task(&config) {
+            print!("{}", err.report());
+            process::exit(err.exit_code());
+        }
+        return;
+    }
+    let mut config = default_build_task_config();
+    if let Some(Command::Lsp) = cli.command {
+        if let Some(err) = run_lsp_server(&config.src_dir) {
+            eprintln!("{}", err.report());
+            process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(Command::Run {
+        bin,
+        interpret,
+        compiler,
+        args,
+    }) = cli.command
+    {
+        config.bin = bin;
+        if let Some(err) = configure_c(&compiler, &mut config) {
+            print!("{}", err.report());
+            process::exit(err.exit_code());
+        }
+        let mut task = create_build_task(config);
+        let run_config = RunTaskConfig {
+            interpret,
+            arguments: args,
+        };
+        match run_run_task(&mut task, &run_config) {
+            Of::Ok(status) => process::exit(status),
+            Of::Error(err) => {
+                print!("{}", err.report());
+                process::exit(err.exit_code());
+            }
+        }
+    }
+    if let Some(Command::Repl) = cli.command {
+        let mut task = create_build_task(config);
+        match run_repl_task(&mut task) {
+            Of::Ok(status) => process::exit(status),
+            Of::Error(err) => {
+                print!("{}", err.report());
+                process::exit(err.exit_code());
+            }
+        }
+    }
+    let mut command = cli.command.unwrap_or(Command::Build {
+        bin: None,
+        lib: false,
+        compiler: CompilerArgs::default(),
+    });
+    if let Command::Build {
+        bin,
+        lib,
+        compiler: args,
+    } = &command
+    {
+        config.bin = bin.clone();
+        config.lib = *lib;
+        if let Some(err) = configure_c(args, &mut config) {
+            print!("{}", err.report());
+            process::exit(err.exit_code());
+        }
+    }
+    if let Command::Test { compiler: args, .. } = &command
+        && let Some(err) = configure_c(args, &mut config)
+    {
+        print!("{}", err.report());
+        process::exit(err.exit_code());
+    }
+    if let Command::Bench { compiler: args, .. } = &mut command {
+        // benches measure the code as it is released
+        args.release = true;
+        if let Some(err) = configure_c(args, &mut config) {
+            print!("{}", err.report());
+            process::exit(err.exit_code());
+        }
+    }
     let mut task = create_build_task(config);
-    if let Some(err) = run_build_task(&mut task) {
-        print!("{}", err.message());
+    let result = match command {
+        Command::Build { .. } => run_build_task(&mut task),
+        Command::Fix => run_fix_task(&mut task),
+        Command::Fmt { check } => run_fmt_task(&mut task, &FmtTaskConfig { check }),
+        Command::Lint { fix, allow, warn } => {
+            let lint_config = LintTaskConfig { fix, allow, warn };
+            run_lint_task(&mut task, &lint_config)
+        }
+        Command::Test {
+            filter, coverage, ..
+        } => {
+            let test_config = TestTaskConfig { filter, coverage };
+            run_test_task(&mut task, &test_config)
+        }
+        Command::Bench {
+            filter,
+            warmup,
+            iterations,
+            threshold,
+            ..
+        } => {
+            let bench_config = BenchTaskConfig {
+                filter,
+                warmup,
+                iterations,
+                threshold,
+            };
+            run_bench_task(&mut task, &bench_config)
+        }
+        Command::Doc => run_doc_task(&mut task),
+        Command::Run { .. } | Command::Repl | Command::Lsp | Command::Bindgen { .. } => None,
+    };
+    if let Some(err) = result {
+        print!("{}", err.report());
         process::exit(err.exit_code());
     }
 }
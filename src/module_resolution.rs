@@ -0,0 +1,296 @@
+use crate::compilation_error::{
+    CompilationError, CyclicImport, DuplicateExport, DuplicateImport, DuplicateModule,
+    DuplicateProcedure, InvalidExport, InvalidModuleFile, UnknownModule,
+};
+use crate::palel::{Src, call_name, is_module_segment, module_alias, module_path_segments};
+use crate::transpiler_context::Context;
+
+/// Checks that every source file names a module that can be imported and no
+/// two files name the same one, that every `use` refers to an existing module,
+/// that no two imports of a module share an alias, that imports do not form a
+/// cycle and that no module defines a procedure twice or two modules export
+/// the same C name.
+pub fn check_modules(src: &Src) -> Option<Box<dyn CompilationError>> {
+    for (idx, module) in src.modules.iter().enumerate() {
+        let segments = module_path_segments(&module.file);
+        if let Some(segment) = segments.iter().find(|s| !is_module_segment(s)) {
+            return Some(Box::new(InvalidModuleFile {
+                file: module.file.clone(),
+                segment: segment.to_string(),
+            }));
+        }
+        if let Some(other) = src.modules[..idx].iter().find(|m| m.name == module.name) {
+            return Some(Box::new(DuplicateModule {
+                module: module.name.clone(),
+                files: (other.file.clone(), module.file.clone()),
+            }));
+        }
+    }
+
+    for module in &src.modules {
+        let mut aliases: Vec<&str> = vec![];
+        for path in &module.imports {
+            if !src.modules.iter().any(|m| &m.name == path) {
+                return Some(Box::new(UnknownModule {
+                    module: module.name.clone(),
+                    path: path.clone(),
+                }));
+            }
+            let alias = module_alias(path);
+            if aliases.contains(&alias) {
+                return Some(Box::new(DuplicateImport {
+                    module: module.name.clone(),
+                    alias: alias.to_string(),
+                }));
+            }
+            aliases.push(alias);
+        }
+    }
+
+    for module in &src.modules {
+        let mut path = vec![module.name.clone()];
+        if let Some(cycle) = find_cycle(src, &mut path) {
+            return Some(Box::new(CyclicImport { cycle }));
+        }
+    }
+
+    // implementations of interface procedures are checked with the interfaces
+    let procedures: Vec<_> = src
+        .procedures
+        .iter()
+        .filter(|p| p.interface.is_empty())
+        .collect();
+    for (idx, procedure) in procedures.iter().enumerate() {
+        let duplicate = procedures[..idx]
+            .iter()
+            .any(|p| p.module == procedure.module && p.identifier == procedure.identifier);
+        if duplicate {
+            return Some(Box::new(DuplicateProcedure {
+                module: procedure.module.clone(),
+                name: procedure.identifier.clone(),
+            }));
+        }
+    }
+    check_exports(src)
+}
+
+/// Exported procedures keep their bare identifier as C name, which has to be
+/// unique across all modules.
+fn check_exports(src: &Src) -> Option<Box<dyn CompilationError>> {
+    let exports: Vec<_> = src.procedures.iter().filter(|p| p.export).collect();
+    for (idx, procedure) in exports.iter().enumerate() {
+        if !procedure.interface.is_empty() {
+            return Some(Box::new(InvalidExport {
+                name: call_name(&procedure.interface, &procedure.identifier),
+            }));
+        }
+        if exports[..idx]
+            .iter()
+            .any(|p| p.identifier == procedure.identifier)
+        {
+            return Some(Box::new(DuplicateExport {
+                name: procedure.identifier.clone(),
+            }));
+        }
+    }
+    None
+}
+
+fn find_cycle(src: &Src, path: &mut Vec<String>) -> Option<Vec<String>> {
+    let current = path.last()?.clone();
+    let module = src.modules.iter().find(|m| m.name == current)?;
+    for import in &module.imports {
+        if let Some(start) = path.iter().position(|m| m == import) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(import.clone());
+            return Some(cycle);
+        }
+        path.push(import.clone());
+        if let Some(cycle) = find_cycle(src, path) {
+            return Some(cycle);
+        }
+        path.pop();
+    }
+    None
+}
+
+/// The procedures visible from `module`: its own by their bare name and those
+/// of imported modules qualified by the module alias, e.g. `http.get`.
+pub fn module_context(src: &Src, module: &str) -> Context {
+    let mut ctx = Context::default();
+    let procedures = src.procedures.iter().filter(|p| p.interface.is_empty());
+    for procedure in procedures.clone().filter(|p| p.module == module) {
+        ctx.declare_procedure(&procedure.identifier, procedure.signature());
+    }
+    let imports = src
+        .modules
+        .iter()
+        .filter(|m| m.name == module)
+        .flat_map(|m| m.imports.iter());
+    for path in imports {
+        for procedure in procedures.clone().filter(|p| &p.module == path) {
+            let name = format!("{}.{}", module_alias(path), procedure.identifier);
+            ctx.declare_procedure(&name, procedure.signature());
+        }
+    }
+    ctx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_task::SrcFile;
+    use crate::palel::Procedure;
+    use crate::parser::parse;
+    use crate::test_support::{self, message, module};
+
+    fn procedure(module: &str, identifier: &str, public: bool) -> Procedure {
+        Procedure {
+            public,
+            ..test_support::procedure(module, identifier)
+        }
+    }
+
+    #[test]
+    fn test_valid_modules() {
+        let input = Src {
+            modules: vec![
+                module("main", vec!["net.http", "util"]),
+                module("net.http", vec!["util"]),
+                module("util", vec![]),
+            ],
+            procedures: vec![
+                procedure("util", "helper", true),
+                procedure("net.http", "helper", false),
+            ],
+            ..Src::default()
+        };
+        assert!(check_modules(&input).is_none());
+    }
+
+    #[test]
+    fn test_module_errors() {
+        let unknown = Src {
+            modules: vec![module("main", vec!["missing"])],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_modules(&unknown)),
+            "module 'main' uses 'missing' which does not exist"
+        );
+
+        let cyclic = Src {
+            modules: vec![
+                module("main", vec!["a"]),
+                module("a", vec!["b"]),
+                module("b", vec!["a"]),
+            ],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_modules(&cyclic)),
+            "modules import each other: a -> b -> a"
+        );
+
+        let aliases = Src {
+            modules: vec![
+                module("main", vec!["a.util", "b.util"]),
+                module("a.util", vec![]),
+                module("b.util", vec![]),
+            ],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_modules(&aliases)),
+            "module 'main' imports more than one module named 'util'"
+        );
+
+        let duplicate = Src {
+            modules: vec![module("main", vec![])],
+            procedures: vec![
+                procedure("main", "run", false),
+                procedure("main", "run", true),
+            ],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(check_modules(&duplicate)),
+            "procedure 'run' is defined more than once in module 'main'"
+        );
+
+        let mut exports = Src {
+            modules: vec![module("a", vec![]), module("b", vec![])],
+            procedures: vec![procedure("a", "run", true), procedure("b", "run", true)],
+            ..Src::default()
+        };
+        assert!(check_modules(&exports).is_none());
+        for procedure in exports.procedures.iter_mut() {
+            procedure.export = true;
+        }
+        assert_eq!(
+            message(check_modules(&exports)),
+            "more than one procedure is exported to C as 'run'"
+        );
+    }
+
+    #[test]
+    fn test_module_files() {
+        let parsed = |files: &[&str]| {
+            let mut input = Src::default();
+            for file in files {
+                let file = SrcFile {
+                    file: file.to_string(),
+                    content: "proc helper() do\nend\n".to_string(),
+                };
+                assert!(parse(&mut input, &file).is_none());
+            }
+            input
+        };
+        assert!(check_modules(&parsed(&["./net/http.palel", "util_2.palel"])).is_none());
+
+        let invalid = |file: &str, segment: &str| {
+            format!(
+                "source file '{}' cannot be used as a module, '{}' must start with a lowercase letter followed by letters, digits or _",
+                file, segment
+            )
+        };
+        assert_eq!(
+            message(check_modules(&parsed(&["my-util.palel"]))),
+            invalid("my-util.palel", "my-util")
+        );
+        assert_eq!(
+            message(check_modules(&parsed(&["Util.palel"]))),
+            invalid("Util.palel", "Util")
+        );
+        assert_eq!(
+            message(check_modules(&parsed(&["net/2fa.palel"]))),
+            invalid("net/2fa.palel", "2fa")
+        );
+        assert_eq!(
+            message(check_modules(&parsed(&["a.b.palel"]))),
+            invalid("a.b.palel", "a.b")
+        );
+        assert_eq!(
+            message(check_modules(&parsed(&["a/b.palel", "a\\b.palel"]))),
+            "source files 'a/b.palel' and 'a\\b.palel' are both module 'a.b'"
+        );
+    }
+
+    #[test]
+    fn test_module_context() {
+        let input = Src {
+            modules: vec![module("main", vec!["net.http"]), module("net.http", vec![])],
+            procedures: vec![
+                procedure("main", "run", false),
+                procedure("net.http", "get", true),
+                procedure("net.http", "run", false),
+            ],
+            ..Src::default()
+        };
+        let ctx = module_context(&input, "main");
+        assert_eq!(ctx.lookup_procedure("run").unwrap().module, "main");
+        assert_eq!(ctx.lookup_procedure("http.get").unwrap().module, "net.http");
+        assert!(!ctx.lookup_procedure("http.run").unwrap().public);
+        assert!(ctx.lookup_procedure("get").is_none());
+    }
+}
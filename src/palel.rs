@@ -1,29 +1,285 @@
 #[derive(Debug, PartialEq)]
 pub struct Src {
+    pub modules: Vec<Module>,
     pub programs: Vec<Program>,
+    pub procedures: Vec<Procedure>,
+    pub interfaces: Vec<Interface>,
+    pub bindings: Vec<Binding>,
+    pub tests: Vec<Test>,
+    pub benches: Vec<Bench>,
 }
 
 impl Default for Src {
     fn default() -> Src {
-        Src { programs: vec![] }
+        Src {
+            modules: vec![],
+            programs: vec![],
+            procedures: vec![],
+            interfaces: vec![],
+            bindings: vec![],
+            tests: vec![],
+            benches: vec![],
+        }
     }
 }
 
+/// Every source file is a module, named by its path below the source
+/// directory with `/` replaced by `.`. The directories and the file name are
+/// identifiers, so that `use` can refer to the module.
+#[derive(Debug, PartialEq)]
+pub struct Module {
+    pub name: String,
+    /// The source file the module was parsed from
+    pub file: String,
+    pub imports: Vec<String>,
+}
+
+pub fn module_name_from_file(file: &str) -> String {
+    module_path_segments(file).join(".")
+}
+
+/// The directories and the file name without its extension that make up the
+/// module name of `file`.
+pub fn module_path_segments(file: &str) -> Vec<&str> {
+    let path = file.strip_prefix("./").unwrap_or(file);
+    let path = path.strip_suffix(".palel").unwrap_or(path);
+    path.split(['/', '\\']).collect()
+}
+
+/// Whether `segment` can be a segment of a module path in `use`, a lowercase
+/// letter followed by letters, digits and `_` like any identifier.
+pub fn is_module_segment(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_lowercase() => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// The source file of `module`, relative to the source directory.
+pub fn file_from_module_name(module: &str) -> String {
+    format!("{}.palel", module.replace('.', "/"))
+}
+
+/// The name an imported module is referred to by, its last path segment.
+pub fn module_alias(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path)
+}
+
+/// A range of a file in bytes, `end` exclusive.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Replaces the text of `span` with `replacement`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: String,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct DoBlock {
     pub statements: Vec<Statement>,
+    /// The line each statement starts on in the file of its module, empty
+    /// when the block was not parsed from a source file
+    pub lines: Vec<usize>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Program {
+    pub doc: Option<String>,
+    pub name: Option<String>,
+    pub module: String,
+    /// Bound in order to the command-line arguments, all of them are `String`
+    /// and the `String?` ones may be left out. A last `StringList` parameter
+    /// takes the arguments after those, however many there are. Given fewer
+    /// arguments than it requires or more than it declares, the program
+    /// prints its usage to standard error and exits with status 2.
+    pub parameters: Vec<Parameter>,
     pub do_block: DoBlock,
 }
 
+/// `test "name" do ... end`, built into the test harness and never into a
+/// program.
+#[derive(Debug, PartialEq)]
+pub struct Test {
+    pub module: String,
+    pub name: String,
+    pub do_block: DoBlock,
+}
+
+/// `bench "name" do ... end`, built with optimizations into the benchmark
+/// harness, which times the block over many iterations.
+#[derive(Debug, PartialEq)]
+pub struct Bench {
+    pub module: String,
+    pub name: String,
+    pub do_block: DoBlock,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Procedure {
+    pub doc: Option<String>,
+    pub module: String,
+    pub public: bool,
+    /// `pub extern proc` keeps the identifier as its C name so the procedure
+    /// can be called from C when built as a library
+    pub export: bool,
+    /// Set when the procedure implements `interface:identifier`
+    pub interface: String,
+    pub identifier: String,
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<SchemaType>,
+    pub do_block: DoBlock,
+}
+
+impl Procedure {
+    pub fn signature(&self) -> ProcedureSignature {
+        ProcedureSignature {
+            module: self.module.clone(),
+            public: self.public,
+            export: self.export,
+            interface: self.interface.clone(),
+            identifier: self.identifier.clone(),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|param| Type::Dim(param.schema_type.clone()))
+                .collect(),
+            return_type: self.return_type.clone().map(Type::Dim),
+            implementation: Implementation::Palel,
+        }
+    }
+}
+
+/// A set of procedures called as `name:procedure(...)`. Interfaces are not
+/// scoped by module, every interface procedure has exactly one implementation
+/// in palel or bound to a C function.
+#[derive(Debug, PartialEq)]
+pub struct Interface {
+    pub doc: Option<String>,
+    pub module: String,
+    pub identifier: String,
+    pub procedures: Vec<InterfaceProcedure>,
+    /// Set for `extern "header.h" do ... end`, whose procedures are the C
+    /// functions of the same name declared by the header.
+    pub header: Option<String>,
+    /// Libraries to link for an extern interface, `m` for `-lm`
+    pub links: Vec<String>,
+}
+
+/// The interface name of `extern "header.h"` without `as name`, the file
+/// name of the header without its extension.
+pub fn extern_interface_name(header: &str) -> String {
+    let file = header.rsplit('/').next().unwrap_or(header);
+    file.split('.').next().unwrap_or(file).to_string()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InterfaceProcedure {
+    pub identifier: String,
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<SchemaType>,
+}
+
+impl InterfaceProcedure {
+    pub fn signature(&self, interface: &Interface) -> ProcedureSignature {
+        ProcedureSignature {
+            module: interface.module.clone(),
+            public: true,
+            export: false,
+            interface: interface.identifier.clone(),
+            identifier: self.identifier.clone(),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|param| Type::Dim(param.schema_type.clone()))
+                .collect(),
+            return_type: self.return_type.clone().map(Type::Dim),
+            implementation: Implementation::Palel,
+        }
+    }
+}
+
+/// `bind interface:procedure "symbol"` implements an interface procedure with
+/// the C function `symbol`.
+#[derive(Debug, PartialEq)]
+pub struct Binding {
+    pub module: String,
+    pub interface: String,
+    pub identifier: String,
+    pub symbol: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Parameter {
+    pub identifier: String,
+    pub schema_type: SchemaType,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProcedureSignature {
+    pub module: String,
+    pub public: bool,
+    pub export: bool,
+    pub interface: String,
+    pub identifier: String,
+    pub parameters: Vec<Type>,
+    pub return_type: Option<Type>,
+    pub implementation: Implementation,
+}
+
+impl ProcedureSignature {
+    /// The name the procedure is called by, `interface:identifier` for
+    /// interface procedures.
+    pub fn name(&self) -> String {
+        call_name(&self.interface, &self.identifier)
+    }
+}
+
+pub fn call_name(interface: &str, identifier: &str) -> String {
+    if interface.is_empty() {
+        return identifier.to_string();
+    }
+    format!("{}:{}", interface, identifier)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Implementation {
+    Palel,
+    /// Calls the C function of this name
+    C(String),
+    /// Calls the C function of the same name declared by this header
+    Header(String),
+    /// Transpiled by the toolkit itself, e.g. `debug:print`
+    Builtin,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Statement {
     ProcedureCall(ProcedureCall),
     Return(Return),
     Variable(VariableDeclaration),
+    Assert(Assert),
+}
+
+/// `assert condition, "message"` ends the process with status 1 when the
+/// `Bool` condition is false.
+#[derive(Debug, PartialEq)]
+pub struct Assert {
+    pub condition: Expression,
+    pub message: Option<String>,
+}
+
+impl Assert {
+    pub fn to_statement(self) -> Statement {
+        Statement::Assert(self)
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -48,11 +304,16 @@ impl ProcedureCall {
     pub fn to_statement(self) -> Statement {
         Statement::ProcedureCall(self)
     }
+
+    pub fn to_expression(self) -> Expression {
+        Expression::Call(self)
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Literal {
     String(String),
+    Char(char),
     Number(String),
     Boolean(bool),
     Null,
@@ -67,6 +328,36 @@ impl Literal {
 #[derive(Debug, PartialEq)]
 pub enum Expression {
     Literal(Literal),
+    Variable(String),
+    Cast(Cast),
+    Call(ProcedureCall),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CastMode {
+    /// `as`, keeps the low bits of an integer like a C cast. Not allowed from
+    /// a floating point type to an integer type, C leaves that undefined when
+    /// the value does not fit
+    Wrapping,
+    /// `as?`, yields null when the value does not fit. The null of an
+    /// integer type takes its smallest signed or largest unsigned value, which
+    /// therefore does not fit either: `255 as? UInt8` is null
+    Checked,
+    /// `as!`, clamps to the nearest representable value
+    Saturating,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cast {
+    pub expression: Box<Expression>,
+    pub mode: CastMode,
+    pub target: SchemaType,
+}
+
+impl Cast {
+    pub fn to_expression(self) -> Expression {
+        Expression::Cast(self)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -77,11 +368,22 @@ pub enum MemoryModifier {
     Addr,
 }
 
+/// A line entered into the REPL: a variable declaration, or an expression
+/// whose value is shown, or a call of a procedure without one.
+#[derive(Debug, PartialEq)]
+pub enum ReplEntry {
+    Variable(VariableDeclaration),
+    Expression(Expression),
+}
+
 #[derive(Debug, PartialEq)]
 pub struct VariableDeclaration {
     pub memory: MemoryModifier,
     pub identifier: String,
     pub schema_type: Option<SchemaType>,
+    /// Where `schema_type` is written in the file of its module, none when
+    /// the declaration was not parsed from a source file
+    pub type_span: Option<Span>,
     pub expression: Expression,
 }
 
@@ -101,6 +403,7 @@ pub enum TypePostfix {
 #[derive(Debug, PartialEq, Clone)]
 pub enum TypeFamily {
     Int,
+    UInt,
     Float,
     None,
 }
@@ -115,19 +418,38 @@ pub struct SchemaType {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum SchemaIdentifier {
+    Int8,
+    Int16,
     Int32,
     Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Size,
     Float32,
     Float64,
     Bool,
     Char,
+    CharSeq,
+    /// The command-line arguments a program collects after its `String`
+    /// parameters, read with `strings:count` and `strings:get`
+    StringList,
     UserDefined(String),
 }
 
 impl SchemaIdentifier {
     pub fn family(&self) -> TypeFamily {
         match self {
-            SchemaIdentifier::Int32 | SchemaIdentifier::Int64 => TypeFamily::Int,
+            SchemaIdentifier::Int8
+            | SchemaIdentifier::Int16
+            | SchemaIdentifier::Int32
+            | SchemaIdentifier::Int64 => TypeFamily::Int,
+            SchemaIdentifier::UInt8
+            | SchemaIdentifier::UInt16
+            | SchemaIdentifier::UInt32
+            | SchemaIdentifier::UInt64
+            | SchemaIdentifier::Size => TypeFamily::UInt,
             SchemaIdentifier::Float32 | SchemaIdentifier::Float64 => TypeFamily::Float,
             _ => TypeFamily::None,
         }
@@ -135,22 +457,118 @@ impl SchemaIdentifier {
 
     pub fn width(&self) -> Option<i32> {
         match self {
-            SchemaIdentifier::Int32 | SchemaIdentifier::Float32 => Some(32),
-            SchemaIdentifier::Int64 | SchemaIdentifier::Float64 => Some(64),
+            SchemaIdentifier::Int8 | SchemaIdentifier::UInt8 => Some(8),
+            SchemaIdentifier::Int16 | SchemaIdentifier::UInt16 => Some(16),
+            SchemaIdentifier::Int32 | SchemaIdentifier::UInt32 | SchemaIdentifier::Float32 => {
+                Some(32)
+            }
+            SchemaIdentifier::Int64 | SchemaIdentifier::UInt64 | SchemaIdentifier::Float64 => {
+                Some(64)
+            }
             _ => None,
         }
     }
+
+    /// Inclusive range of values representable by an integer type. `Size` is
+    /// pointer sized, so only the range guaranteed on every target is reported.
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        match self {
+            SchemaIdentifier::Int8 => Some((i8::MIN as i128, i8::MAX as i128)),
+            SchemaIdentifier::Int16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            SchemaIdentifier::Int32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            SchemaIdentifier::Int64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            SchemaIdentifier::UInt8 => Some((0, u8::MAX as i128)),
+            SchemaIdentifier::UInt16 => Some((0, u16::MAX as i128)),
+            SchemaIdentifier::UInt32 | SchemaIdentifier::Size => Some((0, u32::MAX as i128)),
+            SchemaIdentifier::UInt64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
+
+    /// Like `integer_range`, but the widest range a value can take at run
+    /// time, which casts check their bounds by. A `Size` may be as wide as 64
+    /// bits, the C backend compares it against `SIZE_MAX` and the interpreter
+    /// runs it as 64 bits.
+    pub fn runtime_range(&self) -> Option<(i128, i128)> {
+        match self {
+            SchemaIdentifier::Size => Some((0, u64::MAX as i128)),
+            _ => self.integer_range(),
+        }
+    }
+
+    /// The power of two just above the largest run time value of an integer
+    /// type. Unlike that value it is exact as a `float` or `double`, so a
+    /// floating point value fits the type only while it stays below it.
+    pub fn integer_bound(&self) -> Option<i128> {
+        self.runtime_range().map(|(_, max)| max + 1)
+    }
+
+    /// Whether a value of this type cast to `to` can fall below the range of
+    /// `to` and whether it can fall above it, `None` when a cast between the
+    /// two never needs its bounds checked. Both backends check a cast by it.
+    /// A value may take the widest range of its type, but only the range `to`
+    /// guarantees on every target is taken to hold it, so a cast to `Size` is
+    /// checked against `SIZE_MAX` even where it is 64 bits wide.
+    pub fn cast_overflow(&self, to: &SchemaIdentifier) -> Option<(bool, bool)> {
+        if self == to {
+            return None;
+        }
+        let (from_min, from_max) = cast_range(self, self.runtime_range())?;
+        let (to_min, to_max) = cast_range(to, to.integer_range())?;
+        match (from_min < to_min, from_max > to_max) {
+            (false, false) => None,
+            overflow => Some(overflow),
+        }
+    }
+
+    /// Like `cast_overflow`, for `as?` to an integer type. Its nullable result
+    /// stores null as the smallest value of a signed type and the largest of
+    /// an unsigned one, so a value reaching that end does not fit either.
+    pub fn checked_cast_overflow(&self, to: &SchemaIdentifier) -> Option<(bool, bool)> {
+        if to.integer_range().is_none() {
+            return self.cast_overflow(to);
+        }
+        let (from_min, from_max) = cast_range(self, self.runtime_range())?;
+        let (to_min, to_max) = cast_range(to, to.integer_range())?;
+        let overflow = match to.family() {
+            TypeFamily::UInt => (from_min < to_min, from_max >= to_max),
+            _ => (from_min <= to_min, from_max > to_max),
+        };
+        match overflow {
+            (false, false) => None,
+            overflow => Some(overflow),
+        }
+    }
+}
+
+/// The range of `typ` as a `double`, `integer` being the range taken for an
+/// integer type.
+fn cast_range(typ: &SchemaIdentifier, integer: Option<(i128, i128)>) -> Option<(f64, f64)> {
+    match typ {
+        SchemaIdentifier::Float32 => Some((-f32::MAX as f64, f32::MAX as f64)),
+        SchemaIdentifier::Float64 => Some((f64::NEG_INFINITY, f64::INFINITY)),
+        _ => integer.map(|(min, max)| (min as f64, max as f64)),
+    }
 }
 
 impl ToString for SchemaIdentifier {
     fn to_string(&self) -> String {
         match self {
+            Self::Int8 => "Int8".to_string(),
+            Self::Int16 => "Int16".to_string(),
             Self::Int32 => "Int32".to_string(),
             Self::Int64 => "Int64".to_string(),
+            Self::UInt8 => "UInt8".to_string(),
+            Self::UInt16 => "UInt16".to_string(),
+            Self::UInt32 => "UInt32".to_string(),
+            Self::UInt64 => "UInt64".to_string(),
+            Self::Size => "Size".to_string(),
             Self::Float32 => "Float32".to_string(),
             Self::Float64 => "Float64".to_string(),
             Self::Bool => "Bool".to_string(),
             Self::Char => "Char".to_string(),
+            Self::CharSeq => "String".to_string(),
+            Self::StringList => "StringList".to_string(),
             Self::UserDefined(id) => id.clone(),
         }
     }
@@ -162,16 +580,37 @@ impl SchemaType {
         self.width = new_identifier.width();
         self.identifier = new_identifier;
     }
+
+    /// Inclusive range of the integer values a value of this type holds. A
+    /// nullable type stores null as its smallest signed or largest unsigned
+    /// value, which is left out.
+    pub fn value_range(&self) -> Option<(i128, i128)> {
+        let (min, max) = self.identifier.integer_range()?;
+        match (&self.postfix, &self.family) {
+            (TypePostfix::Opt, TypeFamily::UInt) => Some((min, max - 1)),
+            (TypePostfix::Opt, _) => Some((min + 1, max)),
+            _ => Some((min, max)),
+        }
+    }
 }
 
 pub fn schema_identifier_from_string(value: String) -> SchemaIdentifier {
     match value.as_str() {
+        "Int8" => SchemaIdentifier::Int8,
+        "Int16" => SchemaIdentifier::Int16,
         "Int32" => SchemaIdentifier::Int32,
         "Int64" => SchemaIdentifier::Int64,
+        "UInt8" => SchemaIdentifier::UInt8,
+        "UInt16" => SchemaIdentifier::UInt16,
+        "UInt32" => SchemaIdentifier::UInt32,
+        "UInt64" => SchemaIdentifier::UInt64,
+        "Size" => SchemaIdentifier::Size,
         "Float32" => SchemaIdentifier::Float32,
         "Float64" => SchemaIdentifier::Float64,
         "Bool" => SchemaIdentifier::Bool,
         "Char" => SchemaIdentifier::Char,
+        "String" => SchemaIdentifier::CharSeq,
+        "StringList" => SchemaIdentifier::StringList,
         _ => SchemaIdentifier::UserDefined(value.clone()),
     }
 }
@@ -241,6 +680,16 @@ pub enum Type {
     Dim(SchemaType),
 }
 
+impl Type {
+    pub fn to_expression_type(self) -> ExpressionType {
+        match self {
+            Type::Addr(t) => ExpressionType::Addr(t),
+            Type::Ref(t) => ExpressionType::Ref(t),
+            Type::Dim(t) => ExpressionType::Dim(t),
+        }
+    }
+}
+
 impl ToString for Type {
     fn to_string(&self) -> String {
         let mut output = String::new();
@@ -277,7 +726,7 @@ pub fn schema_type(identifier: SchemaIdentifier) -> SchemaType {
 
 pub fn charseq_type() -> SchemaType {
     SchemaType {
-        identifier: SchemaIdentifier::Char,
+        identifier: SchemaIdentifier::CharSeq,
         postfix: TypePostfix::None,
         family: TypeFamily::None,
         width: None,
@@ -1,17 +1,51 @@
 use pest::Parser;
-use pest::iterators::Pair;
+use pest::error::{Error, ErrorVariant};
+use pest::iterators::{Pair, Pairs};
 use pest_derive::Parser;
 
 use crate::build_task::SrcFile;
-use crate::compilation_error::{CompilationError, FailedToParseSrcFile};
+use crate::compilation_error::{CompilationError, FailedToParseReplEntry, FailedToParseSrcFile};
+use crate::core::Of;
 use crate::palel::*;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
-struct PalelParser;
+pub struct PalelParser;
+
+/// Parses `content` as `rule` like `PalelParser::parse`, and also rejects a
+/// `\u{...}` escape that is not a unicode scalar value, which the grammar
+/// cannot tell apart from one that is.
+pub fn parse_checked(rule: Rule, content: &str) -> Result<Pairs<'_, Rule>, Box<Error<Rule>>> {
+    let pairs = PalelParser::parse(rule, content)?;
+    let escapes = pairs
+        .clone()
+        .flatten()
+        .filter(|p| p.as_rule() == Rule::escape);
+    for escape in escapes {
+        let hex = match escape.as_str().strip_prefix("\\u{") {
+            Some(hex) => hex.trim_end_matches('}'),
+            None => continue,
+        };
+        if u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .is_none()
+        {
+            let message = format!(
+                "{} is not a unicode scalar value, which is at most 10FFFF and not in D800 to DFFF",
+                escape.as_str()
+            );
+            return Err(Box::new(Error::new_from_span(
+                ErrorVariant::CustomError { message },
+                escape.as_span(),
+            )));
+        }
+    }
+    Ok(pairs)
+}
 
 pub fn parse(src: &mut Src, file: &SrcFile) -> Option<Box<dyn CompilationError>> {
-    let mut parse = match PalelParser::parse(Rule::src, &file.content) {
+    let mut parse = match parse_checked(Rule::src, &file.content) {
         Ok(p) => p,
         Err(e) => {
             println!("{}", e);
@@ -21,7 +55,7 @@ pub fn parse(src: &mut Src, file: &SrcFile) -> Option<Box<dyn CompilationError>>
         }
     };
     if let Some(root) = parse.next() {
-        parse_root(src, root);
+        parse_root(src, root, &file.file);
         None
     } else {
         Some(Box::new(FailedToParseSrcFile {
@@ -30,23 +64,104 @@ pub fn parse(src: &mut Src, file: &SrcFile) -> Option<Box<dyn CompilationError>>
     }
 }
 
-fn parse_root(src: &mut Src, root: Pair<'_, Rule>) {
+pub fn parse_repl_entry(line: &str) -> Of<ReplEntry> {
+    let mut parse = match parse_checked(Rule::repl_entry, line) {
+        Ok(p) => p,
+        Err(e) => {
+            return Of::Error(Box::new(FailedToParseReplEntry {
+                reason: e.to_string(),
+            }));
+        }
+    };
+    let entry = parse.next().and_then(|root| {
+        root.into_inner().find_map(|inner| match inner.as_rule() {
+            Rule::variable_statement => parse_variable_declaration(inner).map(ReplEntry::Variable),
+            Rule::expression => parse_expression(inner).map(ReplEntry::Expression),
+            _ => None,
+        })
+    });
+    match entry {
+        Some(entry) => Of::Ok(entry),
+        None => Of::Error(Box::new(FailedToParseReplEntry {
+            reason: "nothing to evaluate".to_string(),
+        })),
+    }
+}
+
+fn parse_root(src: &mut Src, root: Pair<'_, Rule>, file: &str) {
+    let mut module = Module {
+        name: module_name_from_file(file),
+        file: file.to_string(),
+        imports: Vec::new(),
+    };
+    let mut doc: Option<String> = None;
     for pair in root.into_inner() {
         match pair.as_rule() {
-            Rule::program => src.programs.push(parse_program(pair)),
+            Rule::doc_comment => doc = Some(parse_doc_comment(pair)),
+            Rule::use_declaration => module.imports.push(parse_use_declaration(pair)),
+            Rule::program => src
+                .programs
+                .push(parse_program(pair, &module.name, doc.take())),
+            Rule::procedure => src
+                .procedures
+                .push(parse_procedure(pair, &module.name, doc.take())),
+            Rule::interface | Rule::extern_interface => {
+                src.interfaces
+                    .push(parse_interface(pair, &module.name, doc.take()))
+            }
+            Rule::binding => {
+                doc = None;
+                src.bindings.push(parse_binding(pair, &module.name))
+            }
+            Rule::test_block => {
+                doc = None;
+                src.tests.push(parse_test(pair, &module.name))
+            }
+            Rule::bench_block => {
+                doc = None;
+                src.benches.push(parse_bench(pair, &module.name))
+            }
             _ => {}
         }
     }
+    src.modules.push(module);
+}
+
+pub fn parse_use_declaration(rule: Pair<'_, Rule>) -> String {
+    for inner in rule.into_inner() {
+        if inner.as_rule() == Rule::module_path {
+            return inner.as_str().to_string();
+        }
+    }
+    "".to_string()
+}
+
+fn parse_doc_comment(rule: Pair<'_, Rule>) -> String {
+    let mut lines: Vec<&str> = vec![];
+    for inner in rule.into_inner() {
+        if inner.as_rule() == Rule::doc_line {
+            let line = inner.as_str();
+            lines.push(line.strip_prefix(" ").unwrap_or(line).trim_end());
+        }
+    }
+    lines.join("\n")
 }
 
-fn parse_program(rule: Pair<'_, Rule>) -> Program {
+pub fn parse_program(rule: Pair<'_, Rule>, module: &str, doc: Option<String>) -> Program {
     let mut program = Program {
+        doc,
+        name: None,
+        module: module.to_string(),
+        parameters: vec![],
         do_block: DoBlock {
             statements: Vec::new(),
+            lines: Vec::new(),
         },
     };
     for inner in rule.into_inner() {
         match inner.as_rule() {
+            Rule::program_identifier => program.name = Some(inner.as_str().to_string()),
+            Rule::parameter_list => program.parameters = parse_parameter_list(inner),
             Rule::do_block => program.do_block = parse_do_block(inner),
             _ => {}
         }
@@ -54,15 +169,172 @@ fn parse_program(rule: Pair<'_, Rule>) -> Program {
     program
 }
 
+pub fn parse_test(rule: Pair<'_, Rule>, module: &str) -> Test {
+    let mut test = Test {
+        module: module.to_string(),
+        name: "".to_string(),
+        do_block: DoBlock {
+            statements: Vec::new(),
+            lines: Vec::new(),
+        },
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::string => test.name = get_string(inner),
+            Rule::do_block => test.do_block = parse_do_block(inner),
+            _ => {}
+        }
+    }
+    test
+}
+
+pub fn parse_bench(rule: Pair<'_, Rule>, module: &str) -> Bench {
+    let mut bench = Bench {
+        module: module.to_string(),
+        name: "".to_string(),
+        do_block: DoBlock {
+            statements: Vec::new(),
+            lines: Vec::new(),
+        },
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::string => bench.name = get_string(inner),
+            Rule::do_block => bench.do_block = parse_do_block(inner),
+            _ => {}
+        }
+    }
+    bench
+}
+
+pub fn parse_procedure(rule: Pair<'_, Rule>, module: &str, doc: Option<String>) -> Procedure {
+    let mut procedure = Procedure {
+        doc,
+        module: module.to_string(),
+        public: false,
+        export: false,
+        interface: "".to_string(),
+        identifier: "".to_string(),
+        parameters: Vec::new(),
+        return_type: None,
+        do_block: DoBlock {
+            statements: Vec::new(),
+            lines: Vec::new(),
+        },
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::visibility => {
+                procedure.public = true;
+                procedure.export = inner.into_inner().any(|r| r.as_rule() == Rule::export);
+            }
+            Rule::interface_identifier => procedure.interface = get_identifier(inner),
+            Rule::procedure_identifier => procedure.identifier = get_identifier(inner),
+            Rule::parameter_list => procedure.parameters = parse_parameter_list(inner),
+            Rule::type_spec => procedure.return_type = Some(parse_type_spec(inner)),
+            Rule::do_block => procedure.do_block = parse_do_block(inner),
+            _ => {}
+        }
+    }
+    procedure
+}
+
+pub fn parse_interface(rule: Pair<'_, Rule>, module: &str, doc: Option<String>) -> Interface {
+    let mut interface = Interface {
+        doc,
+        module: module.to_string(),
+        identifier: "".to_string(),
+        procedures: Vec::new(),
+        header: None,
+        links: vec![],
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::interface_identifier => interface.identifier = get_identifier(inner),
+            Rule::interface_proc => interface.procedures.push(parse_interface_proc(inner)),
+            Rule::c_header => interface.header = Some(inner.as_str().to_string()),
+            Rule::c_library => interface.links.push(inner.as_str().to_string()),
+            _ => {}
+        }
+    }
+    if let (Some(header), "") = (&interface.header, interface.identifier.as_str()) {
+        interface.identifier = extern_interface_name(header);
+    }
+    interface
+}
+
+fn parse_interface_proc(rule: Pair<'_, Rule>) -> InterfaceProcedure {
+    let mut procedure = InterfaceProcedure {
+        identifier: "".to_string(),
+        parameters: Vec::new(),
+        return_type: None,
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::procedure_identifier => procedure.identifier = get_identifier(inner),
+            Rule::parameter_list => procedure.parameters = parse_parameter_list(inner),
+            Rule::type_spec => procedure.return_type = Some(parse_type_spec(inner)),
+            _ => {}
+        }
+    }
+    procedure
+}
+
+pub fn parse_binding(rule: Pair<'_, Rule>, module: &str) -> Binding {
+    let mut binding = Binding {
+        module: module.to_string(),
+        interface: "".to_string(),
+        identifier: "".to_string(),
+        symbol: "".to_string(),
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::interface_identifier => binding.interface = get_identifier(inner),
+            Rule::procedure_identifier => binding.identifier = get_identifier(inner),
+            Rule::c_symbol => binding.symbol = inner.as_str().to_string(),
+            _ => {}
+        }
+    }
+    binding
+}
+
+fn parse_parameter_list(rule: Pair<'_, Rule>) -> Vec<Parameter> {
+    let mut parameters: Vec<Parameter> = Vec::new();
+    for inner in rule.into_inner() {
+        if inner.as_rule() == Rule::parameter {
+            parameters.push(parse_parameter(inner))
+        }
+    }
+    parameters
+}
+
+fn parse_parameter(rule: Pair<'_, Rule>) -> Parameter {
+    let mut parameter = Parameter {
+        identifier: "".to_string(),
+        schema_type: schema_type(SchemaIdentifier::UserDefined("".to_string())),
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::variable_identifier => parameter.identifier = get_identifier(inner),
+            Rule::type_spec => parameter.schema_type = parse_type_spec(inner),
+            _ => {}
+        }
+    }
+    parameter
+}
+
 fn parse_do_block(rule: Pair<'_, Rule>) -> DoBlock {
     let mut do_block = DoBlock {
         statements: Vec::new(),
+        lines: Vec::new(),
     };
     for inner in rule.into_inner() {
         match inner.as_rule() {
             Rule::statement => {
+                let (line, _) = inner.line_col();
                 if let Some(statement) = parse_statement(inner) {
                     do_block.statements.push(statement);
+                    do_block.lines.push(line);
                 }
             }
             _ => {}
@@ -71,7 +343,7 @@ fn parse_do_block(rule: Pair<'_, Rule>) -> DoBlock {
     do_block
 }
 
-fn parse_statement(rule: Pair<'_, Rule>) -> Option<Statement> {
+pub fn parse_statement(rule: Pair<'_, Rule>) -> Option<Statement> {
     for inner in rule.into_inner() {
         match inner.as_rule() {
             Rule::procedure_call => {
@@ -80,6 +352,9 @@ fn parse_statement(rule: Pair<'_, Rule>) -> Option<Statement> {
             Rule::return_stmt => {
                 return Some(parse_return_statement(inner).to_statement());
             }
+            Rule::assert_stmt => {
+                return parse_assert_statement(inner).map(|a| a.to_statement());
+            }
             Rule::variable_statement => {
                 return parse_variable_declaration(inner).map(|vd| vd.to_statement());
             }
@@ -94,6 +369,7 @@ fn parse_variable_declaration(rule: Pair<'_, Rule>) -> Option<VariableDeclaratio
         memory: MemoryModifier::Var,
         identifier: "".to_string(),
         schema_type: None,
+        type_span: None,
         expression: Expression::Literal(Literal::Null),
     };
     for inner in rule.into_inner() {
@@ -105,6 +381,10 @@ fn parse_variable_declaration(rule: Pair<'_, Rule>) -> Option<VariableDeclaratio
                 var.identifier = get_identifier(inner);
             }
             Rule::type_spec => {
+                var.type_span = Some(Span {
+                    start: inner.as_span().start(),
+                    end: inner.as_span().end(),
+                });
                 var.schema_type = Some(parse_type_spec(inner));
             }
             Rule::expression => {
@@ -137,6 +417,22 @@ fn parse_return_statement(rule: Pair<'_, Rule>) -> Return {
     return_stmt
 }
 
+fn parse_assert_statement(rule: Pair<'_, Rule>) -> Option<Assert> {
+    let mut condition = None;
+    let mut message = None;
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::expression => condition = parse_expression(inner),
+            Rule::string => message = Some(get_string(inner)),
+            _ => {}
+        }
+    }
+    Some(Assert {
+        condition: condition?,
+        message,
+    })
+}
+
 fn parse_procedure_call(rule: Pair<'_, Rule>) -> ProcedureCall {
     let mut procedure_call = ProcedureCall {
         interface: "".to_string(),
@@ -148,8 +444,8 @@ fn parse_procedure_call(rule: Pair<'_, Rule>) -> ProcedureCall {
             Rule::interface_identifier => {
                 procedure_call.interface = get_identifier(inner);
             }
-            Rule::procedure_identifier => {
-                procedure_call.identifier = get_identifier(inner);
+            Rule::procedure_path => {
+                procedure_call.identifier = inner.as_str().to_string();
             }
             Rule::argument_list => {
                 procedure_call.arguments = parse_argument_list(inner);
@@ -160,7 +456,7 @@ fn parse_procedure_call(rule: Pair<'_, Rule>) -> ProcedureCall {
     procedure_call
 }
 
-fn get_identifier(rule: Pair<'_, Rule>) -> String {
+pub fn get_identifier(rule: Pair<'_, Rule>) -> String {
     for inner in rule.into_inner() {
         match inner.as_rule() {
             Rule::identifier => {
@@ -226,29 +522,104 @@ fn parse_argument_list(rule: Pair<'_, Rule>) -> Vec<Expression> {
 }
 
 fn parse_expression(rule: Pair<'_, Rule>) -> Option<Expression> {
+    let mut expression: Option<Expression> = None;
     for inner in rule.into_inner() {
         match inner.as_rule() {
             Rule::string => {
-                return Some(Literal::String(get_string(inner)).to_expression());
+                expression = Some(Literal::String(get_string(inner)).to_expression());
+            }
+            Rule::char => {
+                expression = Some(Literal::Char(get_char(inner)).to_expression());
             }
             Rule::number => {
-                return Some(Literal::Number(get_value(inner)).to_expression());
+                expression = Some(Literal::Number(get_value(inner)).to_expression());
             }
             Rule::boolean => {
-                return Some(Literal::Boolean(get_bool_value(inner)).to_expression());
+                expression = Some(Literal::Boolean(get_bool_value(inner)).to_expression());
             }
             Rule::null => {
-                return Some(Literal::Null.to_expression());
+                expression = Some(Literal::Null.to_expression());
+            }
+            Rule::variable_identifier => {
+                expression = Some(Expression::Variable(get_identifier(inner)));
+            }
+            Rule::procedure_call => {
+                expression = Some(parse_procedure_call(inner).to_expression());
+            }
+            Rule::cast => {
+                expression = expression.map(|expr| parse_cast(inner, expr));
             }
             _ => {}
         }
     }
-    None
+    expression
+}
+
+fn parse_cast(rule: Pair<'_, Rule>, expression: Expression) -> Expression {
+    let mut cast = Cast {
+        expression: Box::new(expression),
+        mode: CastMode::Wrapping,
+        target: schema_type(SchemaIdentifier::UserDefined("".to_string())),
+    };
+    for inner in rule.into_inner() {
+        match inner.as_rule() {
+            Rule::cast_operator => {
+                cast.mode = match inner.as_str() {
+                    "as?" => CastMode::Checked,
+                    "as!" => CastMode::Saturating,
+                    _ => CastMode::Wrapping,
+                };
+            }
+            Rule::type_name => {
+                cast.target =
+                    schema_type(schema_identifier_from_string(inner.as_str().to_string()));
+            }
+            _ => {}
+        }
+    }
+    cast.to_expression()
 }
 
 fn get_string(rule: Pair<'_, Rule>) -> String {
     let val = rule.as_str();
-    val[1..val.len() - 1].to_string()
+    unescape(&val[1..val.len() - 1])
+}
+
+fn get_char(rule: Pair<'_, Rule>) -> char {
+    let val = rule.as_str();
+    unescape(&val[1..val.len() - 1])
+        .chars()
+        .next()
+        .unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Decodes the escape sequences accepted by the `escape` rule, whose
+/// `\u{...}` values `parse_checked` has checked.
+fn unescape(raw: &str) -> String {
+    let mut output = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('0') => output.push('\0'),
+            Some('u') => {
+                let hex: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let decoded = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                output.push(decoded);
+            }
+            Some(other) => output.push(other),
+            None => {}
+        }
+    }
+    output
 }
 
 fn get_value(rule: Pair<'_, Rule>) -> String {
@@ -263,8 +634,17 @@ fn get_bool_value(rule: Pair<'_, Rule>) -> bool {
 mod tests {
     use super::*;
     use SchemaIdentifier::*;
+    use pest::error::LineColLocation;
     use pretty_assertions::assert_eq;
 
+    fn code_module() -> Module {
+        Module {
+            name: "code".to_string(),
+            file: "./code.palel".to_string(),
+            imports: vec![],
+        }
+    }
+
     fn run(input: &str) -> Src {
         let file = SrcFile {
             file: "./code.palel".to_string(),
@@ -289,7 +669,12 @@ mod tests {
         "#;
         let actual = run(input);
         let expected = Src {
+            modules: vec![code_module()],
             programs: vec![Program {
+                doc: None,
+                name: None,
+                module: "code".to_string(),
+                parameters: vec![],
                 do_block: DoBlock {
                     statements: vec![
                         ProcedureCall {
@@ -299,8 +684,10 @@ mod tests {
                         }
                         .to_statement(),
                     ],
+                    lines: vec![3],
                 },
             }],
+            ..Src::default()
         };
         assert_eq!(actual, expected);
     }
@@ -314,7 +701,12 @@ mod tests {
         "#;
         let actual = run(&input);
         let expected = Src {
+            modules: vec![code_module()],
             programs: vec![Program {
+                doc: None,
+                name: None,
+                module: "code".to_string(),
+                parameters: vec![],
                 do_block: DoBlock {
                     statements: vec![
                         ProcedureCall {
@@ -326,8 +718,10 @@ mod tests {
                         }
                         .to_statement(),
                     ],
+                    lines: vec![3],
                 },
             }],
+            ..Src::default()
         };
         assert_eq!(actual, expected);
     }
@@ -355,14 +749,28 @@ mod tests {
         .replace("insert_space_here", "   ");
 
         let actual = run(&input);
+        let type_span = |declaration: &str| {
+            let end = input.find(declaration).unwrap() + declaration.len();
+            let typ = declaration.rsplit(' ').next().unwrap();
+            Some(Span {
+                start: end - typ.len(),
+                end,
+            })
+        };
         let expected = Src {
+            modules: vec![code_module()],
             programs: vec![Program {
+                doc: None,
+                name: None,
+                module: "code".to_string(),
+                parameters: vec![],
                 do_block: DoBlock {
                     statements: vec![
                         VariableDeclaration {
                             memory: MemoryModifier::Dim,
                             identifier: "a".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("1".to_string())),
                         }
                         .to_statement(),
@@ -370,6 +778,7 @@ mod tests {
                             memory: MemoryModifier::Ref,
                             identifier: "b".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("2".to_string())),
                         }
                         .to_statement(),
@@ -377,6 +786,7 @@ mod tests {
                             memory: MemoryModifier::Var,
                             identifier: "c".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("3".to_string())),
                         }
                         .to_statement(),
@@ -384,6 +794,7 @@ mod tests {
                             memory: MemoryModifier::Addr,
                             identifier: "d".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("4".to_string())),
                         }
                         .to_statement(),
@@ -396,6 +807,7 @@ mod tests {
                                 family: TypeFamily::Int,
                                 width: Some(32),
                             }),
+                            type_span: type_span("dim e Int32"),
                             expression: Expression::Literal(Literal::Number("-5".to_string())),
                         }
                         .to_statement(),
@@ -408,6 +820,7 @@ mod tests {
                                 family: TypeFamily::Float,
                                 width: Some(64),
                             }),
+                            type_span: type_span("dim f Float64"),
                             expression: Expression::Literal(Literal::Number("6.2".to_string())),
                         }
                         .to_statement(),
@@ -420,6 +833,7 @@ mod tests {
                                 family: TypeFamily::None,
                                 width: None,
                             }),
+                            type_span: type_span("dim g Bool"),
                             expression: Expression::Literal(Literal::Boolean(true)),
                         }
                         .to_statement(),
@@ -432,6 +846,7 @@ mod tests {
                                 family: TypeFamily::Int,
                                 width: Some(64),
                             }),
+                            type_span: type_span("dim my_z_var Int64"),
                             expression: Expression::Literal(Literal::Null),
                         }
                         .to_statement(),
@@ -444,13 +859,549 @@ mod tests {
                                 family: TypeFamily::Int,
                                 width: Some(32),
                             }),
+                            type_span: type_span("dim maybe_num Int32?"),
                             expression: Expression::Literal(Literal::Null),
                         }
                         .to_statement(),
                     ],
+                    lines: vec![3, 4, 5, 6, 7, 8, 9, 14, 16],
                 },
             }],
+            ..Src::default()
         };
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn test_casts() {
+        let input = r#"
+        program do
+            dim a Int64 = 300
+            dim b = a as Int8
+            dim c = a as? UInt8
+            dim d = a as! Int16 as Float32
+            dim e = nullish
+        end
+        "#;
+        let actual = run(input);
+        let cast = |expression: Expression, mode: CastMode, target: SchemaIdentifier| {
+            Cast {
+                expression: Box::new(expression),
+                mode,
+                target: schema_type(target),
+            }
+            .to_expression()
+        };
+        let variable = |name: &str| Expression::Variable(name.to_string());
+        let statements = &actual.programs[0].do_block.statements;
+        assert_eq!(
+            statements[1],
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: "b".to_string(),
+                schema_type: None,
+                type_span: None,
+                expression: cast(variable("a"), CastMode::Wrapping, Int8),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            statements[2],
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: "c".to_string(),
+                schema_type: None,
+                type_span: None,
+                expression: cast(variable("a"), CastMode::Checked, UInt8),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            statements[3],
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: "d".to_string(),
+                schema_type: None,
+                type_span: None,
+                expression: cast(
+                    cast(variable("a"), CastMode::Saturating, Int16),
+                    CastMode::Wrapping,
+                    Float32
+                ),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            statements[4],
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: "e".to_string(),
+                schema_type: None,
+                type_span: None,
+                expression: variable("nullish"),
+            }
+            .to_statement()
+        );
+    }
+
+    #[test]
+    fn test_char_literals_and_escapes() {
+        let input = r#"
+        program do
+            dim a = 'a'
+            dim b = '\n'
+            dim c = "tab\there \"quoted\" \\ \u{1F600} 100%"
+            dim d = '\''
+        end
+        "#;
+        let actual = run(input);
+        let expressions: Vec<&Expression> = actual.programs[0]
+            .do_block
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Variable(var) => &var.expression,
+                _ => panic!("expected variable declaration"),
+            })
+            .collect();
+        assert_eq!(
+            expressions,
+            vec![
+                &Literal::Char('a').to_expression(),
+                &Literal::Char('\n').to_expression(),
+                &Literal::String("tab\there \"quoted\" \\ \u{1F600} 100%".to_string())
+                    .to_expression(),
+                &Literal::Char('\'').to_expression(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_unicode_escapes() {
+        let error = |content: &str| match parse_checked(Rule::src, content) {
+            Ok(_) => panic!("expected an invalid escape"),
+            Err(e) => (e.variant.message().to_string(), e.line_col.clone()),
+        };
+        assert_eq!(
+            error("program do\n    dim s = \"ok \\u{D800}\"\nend\n"),
+            (
+                "\\u{D800} is not a unicode scalar value, which is at most 10FFFF and not in D800 to DFFF".to_string(),
+                LineColLocation::Span((2, 17), (2, 25))
+            )
+        );
+        assert_eq!(
+            error("program do\n    dim c = '\\u{110000}'\nend\n").1,
+            LineColLocation::Span((2, 14), (2, 24))
+        );
+        assert!(parse_checked(Rule::src, "program do\n    dim c = '\\u{10FFFF}'\nend\n").is_ok());
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = r##"
+        # leading comment
+        program do # after do
+            # on its own line
+            debug:print("# not a comment") # trailing
+            #[ block #[ nested ]# still
+               a comment ]#
+            dim a #[ inline ]# = 1
+        end # after end
+        "##;
+        let actual = run(input);
+        let expected = Src {
+            modules: vec![code_module()],
+            programs: vec![Program {
+                doc: None,
+                name: None,
+                module: "code".to_string(),
+                parameters: vec![],
+                do_block: DoBlock {
+                    statements: vec![
+                        ProcedureCall {
+                            interface: "debug".to_string(),
+                            identifier: "print".to_string(),
+                            arguments: vec![
+                                Literal::String("# not a comment".to_string()).to_expression(),
+                            ],
+                        }
+                        .to_statement(),
+                        VariableDeclaration {
+                            memory: MemoryModifier::Dim,
+                            identifier: "a".to_string(),
+                            schema_type: None,
+                            type_span: None,
+                            expression: Literal::Number("1".to_string()).to_expression(),
+                        }
+                        .to_statement(),
+                    ],
+                    lines: vec![5, 8],
+                },
+            }],
+            ..Src::default()
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_procedures_and_doc_comments() {
+        let input = r#"
+        ## Adds nothing yet.
+        ##
+        ## Returns `a` unchanged.
+        proc add(a Int32, b Int64?) Int32 do
+            return a
+        end
+
+        # an ordinary comment is not documentation
+        proc greet() do
+            debug:print("hi")
+        end
+
+        ## The entry point
+        program do
+            dim x = add(1, null)
+            greet()
+        end
+        "#;
+        let actual = run(input);
+        let mut nullable = schema_type(Int64);
+        nullable.postfix = TypePostfix::Opt;
+        let expected = Src {
+            modules: vec![code_module()],
+            programs: vec![Program {
+                doc: Some("The entry point".to_string()),
+                name: None,
+                module: "code".to_string(),
+                parameters: vec![],
+                do_block: DoBlock {
+                    statements: vec![
+                        VariableDeclaration {
+                            memory: MemoryModifier::Dim,
+                            identifier: "x".to_string(),
+                            schema_type: None,
+                            type_span: None,
+                            expression: ProcedureCall {
+                                interface: "".to_string(),
+                                identifier: "add".to_string(),
+                                arguments: vec![
+                                    Literal::Number("1".to_string()).to_expression(),
+                                    Literal::Null.to_expression(),
+                                ],
+                            }
+                            .to_expression(),
+                        }
+                        .to_statement(),
+                        ProcedureCall {
+                            interface: "".to_string(),
+                            identifier: "greet".to_string(),
+                            arguments: vec![],
+                        }
+                        .to_statement(),
+                    ],
+                    lines: vec![16, 17],
+                },
+            }],
+            procedures: vec![
+                Procedure {
+                    doc: Some("Adds nothing yet.\n\nReturns `a` unchanged.".to_string()),
+                    module: "code".to_string(),
+                    public: false,
+                    export: false,
+                    interface: "".to_string(),
+                    identifier: "add".to_string(),
+                    parameters: vec![
+                        Parameter {
+                            identifier: "a".to_string(),
+                            schema_type: schema_type(Int32),
+                        },
+                        Parameter {
+                            identifier: "b".to_string(),
+                            schema_type: nullable,
+                        },
+                    ],
+                    return_type: Some(schema_type(Int32)),
+                    do_block: DoBlock {
+                        statements: vec![
+                            Return {
+                                value: Some(Expression::Variable("a".to_string())),
+                            }
+                            .to_statement(),
+                        ],
+                        lines: vec![6],
+                    },
+                },
+                Procedure {
+                    doc: None,
+                    module: "code".to_string(),
+                    public: false,
+                    export: false,
+                    interface: "".to_string(),
+                    identifier: "greet".to_string(),
+                    parameters: vec![],
+                    return_type: None,
+                    do_block: DoBlock {
+                        statements: vec![
+                            ProcedureCall {
+                                interface: "debug".to_string(),
+                                identifier: "print".to_string(),
+                                arguments: vec![Literal::String("hi".to_string()).to_expression()],
+                            }
+                            .to_statement(),
+                        ],
+                        lines: vec![11],
+                    },
+                },
+            ],
+            ..Src::default()
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_doc_comments_without_declaration() {
+        let docs = |input: &str| -> Vec<Option<String>> {
+            let src = run(input);
+            src.procedures.into_iter().map(|p| p.doc).collect()
+        };
+        let banner = "#########\n# Helpers\n#########\n\nproc a() do\nend\n";
+        assert_eq!(docs(banner), vec![None]);
+        assert_eq!(docs("#########"), vec![]);
+        let dangling = "proc a() do\nend\n\n##";
+        assert_eq!(docs(dangling), vec![None]);
+        assert_eq!(docs("proc a() do\nend\n## the end\n"), vec![None]);
+        let blank_line = "## Does a.\n\nproc a() do\nend\n";
+        assert_eq!(docs(blank_line), vec![Some("Does a.".to_string())]);
+        let comment = "## Does a.\n# note\n#[ block ]#\nproc a() do\nend\n";
+        assert_eq!(docs(comment), vec![Some("Does a.".to_string())]);
+        let later = "## Not for a.\n\n## Does a.\nproc a() do\nend\n";
+        assert_eq!(docs(later), vec![Some("Does a.".to_string())]);
+    }
+
+    #[test]
+    fn test_modules() {
+        let file = SrcFile {
+            file: "net/http.palel".to_string(),
+            content: r#"
+            use util
+            use net.tls
+
+            pub proc get() do
+                tls.connect()
+                return
+            end
+
+            pub extern proc version() Int32 do
+                return 1
+            end
+            "#
+            .to_string(),
+        };
+        let mut actual = Src::default();
+        if let Some(err) = parse(&mut actual, &file) {
+            panic!("{}", err.message())
+        }
+        assert_eq!(
+            actual.modules,
+            vec![Module {
+                name: "net.http".to_string(),
+                file: "net/http.palel".to_string(),
+                imports: vec!["util".to_string(), "net.tls".to_string()],
+            }]
+        );
+        let procedure = &actual.procedures[0];
+        assert_eq!(procedure.module, "net.http");
+        assert!(procedure.public);
+        assert!(!procedure.export);
+        assert!(actual.procedures[1].public);
+        assert!(actual.procedures[1].export);
+        assert_eq!(
+            procedure.do_block.statements[0],
+            ProcedureCall {
+                interface: "".to_string(),
+                identifier: "tls.connect".to_string(),
+                arguments: vec![],
+            }
+            .to_statement()
+        );
+    }
+
+    #[test]
+    fn test_named_programs() {
+        let actual = run(r#"
+        program do
+        end
+
+        program server do
+        end
+
+        program done do
+        end
+
+        program (input String, mode String?) do
+        end
+        "#);
+        let names: Vec<Option<String>> = actual.programs.iter().map(|p| p.name.clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                None,
+                Some("server".to_string()),
+                Some("done".to_string()),
+                None
+            ]
+        );
+        let parameters: Vec<&str> = actual.programs[3]
+            .parameters
+            .iter()
+            .map(|p| p.identifier.as_str())
+            .collect();
+        assert_eq!(parameters, vec!["input", "mode"]);
+        assert_eq!(
+            actual.programs[3].parameters[1].schema_type.postfix,
+            TypePostfix::Opt
+        );
+    }
+
+    #[test]
+    fn test_tests_and_asserts() {
+        let actual = run(r#"
+        program do
+            assert true
+        end
+
+        test "holds" do
+            assert true, "always"
+        end
+        "#);
+        assert_eq!(
+            actual.programs[0].do_block.statements,
+            vec![
+                Assert {
+                    condition: Literal::Boolean(true).to_expression(),
+                    message: None,
+                }
+                .to_statement()
+            ]
+        );
+        assert_eq!(
+            actual.tests,
+            vec![Test {
+                module: "code".to_string(),
+                name: "holds".to_string(),
+                do_block: DoBlock {
+                    statements: vec![
+                        Assert {
+                            condition: Literal::Boolean(true).to_expression(),
+                            message: Some("always".to_string()),
+                        }
+                        .to_statement()
+                    ],
+                    lines: vec![7],
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_benches() {
+        let actual = run(r#"
+        bench "calls" do
+            debug:print()
+        end
+        "#);
+        assert_eq!(
+            actual.benches,
+            vec![Bench {
+                module: "code".to_string(),
+                name: "calls".to_string(),
+                do_block: DoBlock {
+                    statements: vec![
+                        ProcedureCall {
+                            interface: "debug".to_string(),
+                            identifier: "print".to_string(),
+                            arguments: vec![],
+                        }
+                        .to_statement()
+                    ],
+                    lines: vec![3],
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let actual = run(r#"
+        ## Where diagnostics go.
+        interface log do
+            proc info(message String)
+            proc count() Int32
+        end
+
+        proc log:info(message String) do
+            return
+        end
+
+        bind log:count "log_count"
+        "#);
+        assert_eq!(
+            actual.interfaces,
+            vec![Interface {
+                doc: Some("Where diagnostics go.".to_string()),
+                module: "code".to_string(),
+                identifier: "log".to_string(),
+                procedures: vec![
+                    InterfaceProcedure {
+                        identifier: "info".to_string(),
+                        parameters: vec![Parameter {
+                            identifier: "message".to_string(),
+                            schema_type: charseq_type(),
+                        }],
+                        return_type: None,
+                    },
+                    InterfaceProcedure {
+                        identifier: "count".to_string(),
+                        parameters: vec![],
+                        return_type: Some(schema_type(SchemaIdentifier::Int32)),
+                    },
+                ],
+                header: None,
+                links: vec![],
+            }]
+        );
+        assert_eq!(actual.procedures[0].interface, "log");
+        assert_eq!(actual.procedures[0].identifier, "info");
+        assert_eq!(
+            actual.bindings,
+            vec![Binding {
+                module: "code".to_string(),
+                interface: "log".to_string(),
+                identifier: "count".to_string(),
+                symbol: "log_count".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_extern_interfaces() {
+        let actual = run(r#"
+        extern "math.h" link "m" do
+            proc sqrt(x Float64) Float64
+        end
+
+        extern "SDL2/SDL.h" as sdl link "SDL2" link "SDL2main" do
+        end
+        "#);
+        let math = &actual.interfaces[0];
+        assert_eq!(math.identifier, "math");
+        assert_eq!(math.header, Some("math.h".to_string()));
+        assert_eq!(math.links, vec!["m".to_string()]);
+        assert_eq!(math.procedures[0].identifier, "sqrt");
+        let sdl = &actual.interfaces[1];
+        assert_eq!(sdl.identifier, "sdl");
+        assert_eq!(sdl.header, Some("SDL2/SDL.h".to_string()));
+        assert_eq!(sdl.links, vec!["SDL2".to_string(), "SDL2main".to_string()]);
+        assert_eq!(extern_interface_name("sys/stat.h"), "stat");
+    }
 }
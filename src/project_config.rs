@@ -0,0 +1,174 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::compilation_error::FailedToParseProjectConfig;
+use crate::core::Of;
+use crate::downstream_compiler_c::{CCompilerConfig, Profile};
+
+/// The optional project configuration next to the `src` directory.
+pub const PROJECT_CONFIG: &str = "palel.toml";
+
+/// Settings of `palel.toml`, command-line options take precedence over them.
+///
+/// ```toml
+/// [c]
+/// compiler = "clang"
+/// warnings = ["all", "extra"]
+///
+/// [profile.release]
+/// optimization = "3"
+///
+/// [lint]
+/// allow = ["var-never-reassigned"]
+/// ```
+#[derive(Debug, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub c: CSettings,
+    pub profile: ProfileSettings,
+    pub lint: LintSettings,
+}
+
+#[derive(Debug, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintSettings {
+    /// Lints that are not run, by their kebab-case names
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CSettings {
+    pub compiler: Option<String>,
+    pub optimization: Option<String>,
+    pub warnings: Vec<String>,
+    pub include_dirs: Vec<String>,
+    pub library_dirs: Vec<String>,
+    pub defines: Vec<String>,
+    pub flags: Vec<String>,
+    /// Directories of hand-written `.c` and `.h` files built with the project
+    pub source_dirs: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileSettings {
+    pub debug: CSettings,
+    pub release: CSettings,
+}
+
+/// Reads `file`, a project without one uses the defaults.
+pub fn load_project_config(file: &str) -> Of<ProjectConfig> {
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(_) => return Of::Ok(ProjectConfig::default()),
+    };
+    parse_project_config(file, &content)
+}
+
+fn parse_project_config(file: &str, content: &str) -> Of<ProjectConfig> {
+    match toml::from_str(content) {
+        Ok(config) => Of::Ok(config),
+        Err(err) => Of::Error(Box::new(FailedToParseProjectConfig {
+            file: file.to_string(),
+            reason: err.message().to_string(),
+        })),
+    }
+}
+
+/// The compiler settings of `profile`: those of the profile section extend
+/// the `[c]` section. The `CC` environment variable, passed as `env_cc`,
+/// wins over the configured compiler.
+pub fn compiler_config(
+    project: &ProjectConfig,
+    profile: Profile,
+    env_cc: Option<String>,
+) -> CCompilerConfig {
+    let profile_settings = match profile {
+        Profile::Debug => &project.profile.debug,
+        Profile::Release => &project.profile.release,
+    };
+    let mut config = CCompilerConfig {
+        profile,
+        ..CCompilerConfig::default()
+    };
+    for settings in [&project.c, profile_settings] {
+        if let Some(compiler) = &settings.compiler {
+            config.compiler = compiler.clone();
+        }
+        if let Some(optimization) = &settings.optimization {
+            config.optimization = Some(optimization.clone());
+        }
+        config.warnings.extend(settings.warnings.iter().cloned());
+        config
+            .include_dirs
+            .extend(settings.include_dirs.iter().cloned());
+        config
+            .library_dirs
+            .extend(settings.library_dirs.iter().cloned());
+        config.defines.extend(settings.defines.iter().cloned());
+        config.flags.extend(settings.flags.iter().cloned());
+    }
+    if let Some(compiler) = env_cc.filter(|cc| !cc.trim().is_empty()) {
+        config.compiler = compiler;
+    }
+    config
+}
+
+/// The directories of C files of `profile` besides the `src` directory.
+pub fn c_source_dirs(project: &ProjectConfig, profile: Profile) -> Vec<String> {
+    let profile_settings = match profile {
+        Profile::Debug => &project.profile.debug,
+        Profile::Release => &project.profile.release,
+    };
+    let mut dirs = project.c.source_dirs.clone();
+    dirs.extend(profile_settings.source_dirs.iter().cloned());
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_compiler_config() {
+        let content = r#"
+            [c]
+            compiler = "clang"
+            warnings = ["all"]
+            include-dirs = ["vendor/include"]
+            source-dirs = ["csrc"]
+
+            [profile.release]
+            optimization = "3"
+            defines = ["FAST"]
+        "#;
+        let project = match parse_project_config(PROJECT_CONFIG, content) {
+            Of::Ok(project) => project,
+            Of::Error(err) => panic!("{}", err.message()),
+        };
+
+        let debug = compiler_config(&project, Profile::Debug, None);
+        assert_eq!(debug.compiler, "clang");
+        assert_eq!(debug.optimization, None);
+        assert_eq!(debug.defines, Vec::<String>::new());
+
+        let release = compiler_config(&project, Profile::Release, Some("tcc".to_string()));
+        assert_eq!(release.compiler, "tcc");
+        assert_eq!(release.optimization, Some("3".to_string()));
+        assert_eq!(release.warnings, vec!["all".to_string()]);
+        assert_eq!(release.include_dirs, vec!["vendor/include".to_string()]);
+        assert_eq!(release.defines, vec!["FAST".to_string()]);
+        assert_eq!(
+            c_source_dirs(&project, Profile::Release),
+            vec!["csrc".to_string()]
+        );
+
+        match parse_project_config(PROJECT_CONFIG, "[c]\ncompilr = \"cc\"\n") {
+            Of::Ok(_) => panic!("unknown settings must be rejected"),
+            Of::Error(err) => assert!(err.message().starts_with("failed to read palel.toml: ")),
+        }
+    }
+}
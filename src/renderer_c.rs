@@ -1,27 +1,125 @@
 use crate::c::*;
 
 pub fn render(src: &CSrc) -> String {
+    render_exporting(src, &[])
+}
+
+/// Renders `src` with the functions declared by `exports` visible outside of
+/// a shared library built with `-fvisibility=hidden`.
+pub fn render_exporting(src: &CSrc, exports: &[CPrototype]) -> String {
     let mut output = String::new();
     for include in &src.includes {
         output.push_str(&render_include(include));
     }
+    for prototype in &src.prototypes {
+        output.push_str(&render_prototype(prototype));
+    }
+    for helper in &src.helpers {
+        output.push_str(&render_helper(helper));
+    }
+    for function in &src.functions {
+        if exports.iter().any(|export| export.name == function.name) {
+            output.push_str("__attribute__((visibility(\"default\"))) ");
+        }
+        if function.name != "main" {
+            output.push_str(&render_function_signature(function));
+            output.push_str(";\n");
+        }
+    }
     for function in &src.functions {
         output.push_str(&render_function(function));
     }
     output
 }
 
+/// Renders the prototypes in `src` and those of its functions behind an
+/// include guard so the functions can be called from other translation units.
+pub fn render_header(src: &CSrc, guard: &str) -> String {
+    let mut output = String::new();
+    output.push_str(&format!("#ifndef {}\n#define {}\n", guard, guard));
+    for include in &src.includes {
+        output.push_str(&render_include(include));
+    }
+    for prototype in &src.prototypes {
+        output.push_str(&render_prototype(prototype));
+    }
+    for function in &src.functions {
+        output.push_str(&render_function_signature(function));
+        output.push_str(";\n");
+    }
+    output.push_str("#endif\n");
+    output
+}
+
+/// Renders the includes and prototypes `patch` adds to a file.
+pub fn render_patch(patch: &CSrcPatch) -> String {
+    let mut output = String::new();
+    for include in &patch.includes {
+        output.push_str(&render_include(include));
+    }
+    for prototype in &patch.prototypes {
+        output.push_str(&render_prototype(prototype));
+    }
+    for helper in &patch.helpers {
+        output.push_str(&render_helper(helper));
+    }
+    output
+}
+
 pub fn render_include(include: &CInclude) -> String {
+    if include.local {
+        return format!("#include \"{}\"\n", include.file);
+    }
     format!("#include <{}>\n", include.file)
 }
 
 fn render_function(function: &CFunction) -> String {
+    let mut output = render_function_signature(function);
+    output.push('\n');
+    output.push_str(&render_block(&function.block));
+    output
+}
+
+/// A helper is private to each file defining it.
+fn render_helper(helper: &CFunction) -> String {
+    format!("static {}", render_function(helper))
+}
+
+fn render_function_signature(function: &CFunction) -> String {
+    render_signature(&function.return_type, &function.name, &function.parameters)
+}
+
+fn render_prototype(prototype: &CPrototype) -> String {
+    let mut output = render_signature(
+        &prototype.return_type,
+        &prototype.name,
+        &prototype.parameters,
+    );
+    output.push_str(";\n");
+    output
+}
+
+fn render_signature(return_type: &CType, name: &str, parameters: &[CParameter]) -> String {
     let mut output = String::new();
-    output.push_str(render_type(&function.return_type));
+    output.push_str(render_type(return_type));
     output.push_str(" ");
-    output.push_str(&function.name);
-    output.push_str("()\n");
-    output.push_str(&render_block(&function.block));
+    if return_type.is_pointer {
+        output.push('*');
+    }
+    output.push_str(name);
+    output.push('(');
+    for (idx, parameter) in parameters.iter().enumerate() {
+        if idx > 0 {
+            output.push_str(", ");
+        }
+        output.push_str(render_type(&parameter.typ));
+        output.push(' ');
+        if parameter.typ.is_pointer {
+            output.push('*');
+        }
+        output.push_str(&parameter.name);
+    }
+    output.push(')');
     output
 }
 
@@ -35,16 +133,46 @@ fn render_block(block: &CBlock) -> String {
     output
 }
 
-fn render_statement(statement: &CStatement) -> String {
+pub fn render_statement(statement: &CStatement) -> String {
     let mut output = match statement {
         CStatement::FunctionCall(function_call) => render_function_call(function_call),
         CStatement::Return(ret) => render_return(ret),
         CStatement::Variable(dec) => render_variable_declaration(dec),
+        CStatement::If(cif) => return render_if(cif),
+        CStatement::Line(line) => return render_line(line),
+        CStatement::While(cwhile) => return render_while(cwhile),
+        CStatement::Assignment(assignment) => format!(
+            "{} = {}",
+            assignment.name,
+            render_expression(&assignment.value)
+        ),
     };
     output.push_str(";\n");
     output
 }
 
+fn render_if(cif: &CIf) -> String {
+    let mut output = String::new();
+    output.push_str("if (");
+    output.push_str(&render_expression(&cif.condition));
+    output.push_str(")\n");
+    output.push_str(&render_block(&cif.block));
+    output
+}
+
+fn render_while(cwhile: &CWhile) -> String {
+    let mut output = String::new();
+    output.push_str("while (");
+    output.push_str(&render_expression(&cwhile.condition));
+    output.push_str(")\n");
+    output.push_str(&render_block(&cwhile.block));
+    output
+}
+
+fn render_line(line: &CLine) -> String {
+    format!("#line {} \"{}\"\n", line.line, escape(&line.file, '"'))
+}
+
 fn render_variable_declaration(vardec: &CVariableDeclaration) -> String {
     let mut output = String::new();
     output.push_str(&vardec.var_type.name);
@@ -83,19 +211,87 @@ fn render_function_call(function_call: &CFunctionCall) -> String {
     output
 }
 
-fn render_expression(argument: &CExpression) -> String {
+pub fn render_expression(argument: &CExpression) -> String {
     match argument {
         CExpression::Literal(literal) => render_literal(literal),
         CExpression::Variable(variable) => variable.clone(),
+        CExpression::Cast(cast) => format!(
+            "(({}{}){})",
+            render_type(&cast.typ),
+            if cast.typ.is_pointer { " *" } else { "" },
+            render_expression(&cast.expression)
+        ),
+        CExpression::Conditional(conditional) => format!(
+            "({} ? {} : {})",
+            render_expression(&conditional.condition),
+            render_expression(&conditional.then),
+            render_expression(&conditional.otherwise)
+        ),
+        CExpression::Call(function_call) => render_function_call(function_call),
+        CExpression::Member(member) => format!(
+            "{}.{}",
+            render_expression(&member.expression),
+            member.member
+        ),
+        CExpression::Unary(unary) => format!(
+            "({}{})",
+            unary.operator,
+            render_expression(&unary.expression)
+        ),
+        CExpression::Initializer(values) => format!(
+            "{{{}}}",
+            values
+                .iter()
+                .map(render_expression)
+                .collect::<Vec<String>>()
+                .join(",")
+        ),
+        CExpression::Index(index) => format!(
+            "{}[{}]",
+            render_expression(&index.expression),
+            render_expression(&index.index)
+        ),
+        CExpression::Binary(binary) => format!(
+            "({} {} {})",
+            render_expression(&binary.left),
+            binary.operator,
+            render_expression(&binary.right)
+        ),
     }
 }
 
 fn render_literal(literal: &CLiteral) -> String {
     match literal {
         CLiteral::Number(value) => value.clone(),
-        CLiteral::String(value) => format!("\"{}\"", value),
-        CLiteral::Char(value) => format!("'{}'", value),
+        CLiteral::String(value) => format!("\"{}\"", escape(value, '"')),
+        CLiteral::Char(value) => format!("'{}'", escape(value, '\'')),
+    }
+}
+
+/// Escapes `value` so it can be placed between `quote`s in C source. Bytes
+/// outside printable ASCII are written as three digit octal escapes so a
+/// following digit can never be read as part of the escape.
+fn escape(value: &str, quote: char) -> String {
+    let mut output = String::new();
+    let mut previous = None;
+    for byte in value.bytes() {
+        let c = byte as char;
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            // a second `?` could start a trigraph
+            '?' if previous == Some('?') => output.push_str("\\?"),
+            c if c == quote => {
+                output.push('\\');
+                output.push(c);
+            }
+            ' '..='~' => output.push(c),
+            _ => output.push_str(&format!("\\{:03o}", byte)),
+        }
+        previous = Some(c);
     }
+    output
 }
 
 fn render_type(typ: &CType) -> &str {
@@ -121,9 +317,13 @@ mod tests {
         let src = CSrc {
             includes: vec![CInclude {
                 file: "stdio.h".to_string(),
+                local: false,
             }],
+            prototypes: vec![],
+            helpers: vec![],
             functions: vec![CFunction {
                 name: "main".to_string(),
+                parameters: vec![],
                 return_type: CType {
                     name: "int".to_string(),
                     is_pointer: false,
@@ -163,8 +363,11 @@ mod tests {
     fn test_variable_declarations() {
         let src = CSrc {
             includes: vec![],
+            prototypes: vec![],
+            helpers: vec![],
             functions: vec![CFunction {
                 name: "main".to_string(),
+                parameters: vec![],
                 return_type: CType {
                     name: "int".to_string(),
                     is_pointer: false,
@@ -290,4 +493,185 @@ mod tests {
 
         assert_eq!(norm(&actual), norm(&expected))
     }
+
+    #[test]
+    fn test_literal_escaping() {
+        let literal = |value: CLiteral| render_expression(&value.to_expression());
+        assert_eq!(
+            literal(CLiteral::String("say \"hi\"\n\t\\ 100%".to_string())),
+            r#""say \"hi\"\n\t\\ 100%""#
+        );
+        assert_eq!(
+            literal(CLiteral::String("\u{1F600}1".to_string())),
+            r#""\360\237\230\2001""#
+        );
+        assert_eq!(literal(CLiteral::String("??=".to_string())), r#""?\?=""#);
+        assert_eq!(literal(CLiteral::Char("'".to_string())), r#"'\''"#);
+        assert_eq!(literal(CLiteral::Char("\0".to_string())), r#"'\000'"#);
+    }
+
+    #[test]
+    fn test_loops_and_lines() {
+        let now = || Box::new(CExpression::Variable("now".to_string()));
+        let statements = [
+            CLine {
+                line: 3,
+                file: "main.palel".to_string(),
+            }
+            .to_statement(),
+            CVariableDeclaration {
+                name: "now".to_string(),
+                var_type: CType {
+                    name: "struct timespec".to_string(),
+                    is_pointer: false,
+                },
+                value: CExpression::Initializer(vec![
+                    CLiteral::Number("0".to_string()).to_expression(),
+                ]),
+            }
+            .to_statement(),
+            CWhile {
+                condition: CUnary {
+                    operator: "!".to_string(),
+                    expression: Box::new(
+                        CMember {
+                            expression: now(),
+                            member: "tv_sec".to_string(),
+                        }
+                        .to_expression(),
+                    ),
+                }
+                .to_expression(),
+                block: CBlock {
+                    statements: vec![
+                        CAssignment {
+                            name: "now".to_string(),
+                            value: CExpression::Variable("later".to_string()),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }
+            .to_statement(),
+        ];
+        let rendered: Vec<String> = statements.iter().map(render_statement).collect();
+        assert_eq!(
+            rendered.concat(),
+            concat!(
+                "#line 3 \"main.palel\"\n",
+                "struct timespec now = {0};\n",
+                "while ((!now.tv_sec))\n",
+                "{\n",
+                "now = later;\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_function_with_parameters() {
+        let int32 = CType {
+            name: "int32_t".to_string(),
+            is_pointer: false,
+        };
+        let src = CSrc {
+            includes: vec![],
+            prototypes: vec![],
+            helpers: vec![],
+            functions: vec![
+                CFunction {
+                    name: "main".to_string(),
+                    parameters: vec![],
+                    return_type: int_type(),
+                    block: CBlock {
+                        statements: vec![
+                            CReturn {
+                                value: Some(
+                                    CFunctionCall {
+                                        function_name: "first".to_string(),
+                                        arguments: vec![
+                                            CLiteral::Number("1".to_string()).to_expression(),
+                                            CLiteral::String("b".to_string()).to_expression(),
+                                        ],
+                                    }
+                                    .to_expression(),
+                                ),
+                            }
+                            .to_statement(),
+                        ],
+                    },
+                },
+                CFunction {
+                    name: "first".to_string(),
+                    parameters: vec![
+                        CParameter {
+                            name: "a".to_string(),
+                            typ: int32.clone(),
+                        },
+                        CParameter {
+                            name: "b".to_string(),
+                            typ: CType {
+                                name: "char".to_string(),
+                                is_pointer: true,
+                            },
+                        },
+                    ],
+                    return_type: int32.clone(),
+                    block: CBlock {
+                        statements: vec![
+                            CReturn {
+                                value: Some(CExpression::Variable("a".to_string())),
+                            }
+                            .to_statement(),
+                        ],
+                    },
+                },
+            ],
+        };
+
+        let expected = r#"
+        int32_t first(int32_t a, char *b);
+        int main()
+        {
+        return first(1,"b");
+        }
+        int32_t first(int32_t a, char *b)
+        {
+        return a;
+        }
+        "#;
+        let actual = render(&src);
+
+        assert_eq!(norm(&actual), norm(expected))
+    }
+
+    #[test]
+    fn test_exported_function() {
+        let function = |name: &str| CFunction {
+            name: name.to_string(),
+            parameters: vec![],
+            return_type: void_type(),
+            block: CBlock { statements: vec![] },
+        };
+        let src = CSrc {
+            includes: vec![],
+            prototypes: vec![],
+            helpers: vec![],
+            functions: vec![function("math__hidden"), function("area")],
+        };
+
+        let expected = r#"
+        void math__hidden();
+        __attribute__((visibility("default"))) void area();
+        void math__hidden()
+        {
+        }
+        void area()
+        {
+        }
+        "#;
+        let actual = render_exporting(&src, &[src.functions[1].prototype()]);
+
+        assert_eq!(norm(&actual), norm(expected))
+    }
 }
@@ -0,0 +1,397 @@
+use crate::palel::*;
+
+pub fn render_doc(src: &Src) -> String {
+    let mut output = String::new();
+    output.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    output.push_str("<title>palel documentation</title>\n</head>\n<body>\n");
+    output.push_str("<h1>palel documentation</h1>\n");
+    output.push_str(&render_index(src));
+
+    if !src.programs.is_empty() {
+        output.push_str("<h2 id=\"programs\">Programs</h2>\n");
+        for (idx, program) in src.programs.iter().enumerate() {
+            output.push_str(&render_program(src, idx, program));
+        }
+    }
+    if documented_procedures(src).next().is_some() {
+        output.push_str("<h2 id=\"procedures\">Procedures</h2>\n");
+        for procedure in documented_procedures(src) {
+            output.push_str(&render_procedure(src, procedure));
+        }
+    }
+    if !src.interfaces.is_empty() {
+        output.push_str("<h2 id=\"interfaces\">Interfaces</h2>\n");
+        for interface in &src.interfaces {
+            output.push_str(&render_interface(src, interface));
+        }
+    }
+    let types = referenced_types(src);
+    if !types.is_empty() {
+        output.push_str("<h2 id=\"types\">Types</h2>\n");
+        for typ in &types {
+            output.push_str(&render_type(typ));
+        }
+    }
+
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+fn render_index(src: &Src) -> String {
+    let mut output = String::new();
+    output.push_str("<ul>\n");
+    for (idx, program) in src.programs.iter().enumerate() {
+        output.push_str(&format!(
+            "<li><a href=\"#{}\">program {}</a></li>\n",
+            program_anchor(idx, program),
+            escape(&program_title(idx, program))
+        ));
+    }
+    for procedure in documented_procedures(src) {
+        output.push_str(&format!(
+            "<li><a href=\"#{}\">proc {}</a></li>\n",
+            procedure_anchor(&procedure.module, &procedure.identifier),
+            escape(&qualified_name(procedure))
+        ));
+    }
+    for interface in &src.interfaces {
+        output.push_str(&format!(
+            "<li><a href=\"#{}\">interface {}</a></li>\n",
+            interface_anchor(&interface.identifier),
+            escape(&interface.identifier)
+        ));
+    }
+    output.push_str("</ul>\n");
+    output
+}
+
+fn render_program(src: &Src, idx: usize, program: &Program) -> String {
+    let mut output = String::new();
+    output.push_str(&format!(
+        "<h3 id=\"{}\">program {}</h3>\n",
+        program_anchor(idx, program),
+        escape(&program_title(idx, program))
+    ));
+    output.push_str("<pre><code>");
+    output.push_str(&render_program_signature(program));
+    output.push_str("</code></pre>\n");
+    output.push_str(&render_doc_text(src, &program.module, &program.doc));
+    output
+}
+
+fn render_procedure(src: &Src, procedure: &Procedure) -> String {
+    let mut output = String::new();
+    output.push_str(&format!(
+        "<h3 id=\"{}\">{}</h3>\n",
+        procedure_anchor(&procedure.module, &procedure.identifier),
+        escape(&qualified_name(procedure))
+    ));
+    output.push_str("<pre><code>");
+    output.push_str(&render_signature(procedure));
+    output.push_str("</code></pre>\n");
+    output.push_str(&render_doc_text(src, &procedure.module, &procedure.doc));
+    output
+}
+
+/// Only public procedures are part of the API, implementations of interface
+/// procedures are documented by their interface.
+fn documented_procedures(src: &Src) -> impl Iterator<Item = &Procedure> {
+    src.procedures
+        .iter()
+        .filter(|p| p.public && p.interface.is_empty())
+}
+
+fn render_interface(src: &Src, interface: &Interface) -> String {
+    let mut output = String::new();
+    output.push_str(&format!(
+        "<h3 id=\"{}\">interface {}</h3>\n",
+        interface_anchor(&interface.identifier),
+        escape(&interface.identifier)
+    ));
+    output.push_str(&render_doc_text(src, &interface.module, &interface.doc));
+    if let Some(header) = &interface.header {
+        output.push_str(&format!(
+            "<p>Calls the C functions declared by <code>{}</code>.</p>\n",
+            escape(header)
+        ));
+    }
+    output.push_str("<pre><code>");
+    for (idx, procedure) in interface.procedures.iter().enumerate() {
+        if idx > 0 {
+            output.push('\n');
+        }
+        output.push_str(&render_parts(
+            "proc ",
+            &procedure.identifier,
+            &procedure.parameters,
+            &procedure.return_type,
+        ));
+    }
+    output.push_str("</code></pre>\n");
+    output
+}
+
+/// Renders `pub proc name(param Type, ...) Type` with every type linked to its
+/// entry in the types section.
+fn render_signature(procedure: &Procedure) -> String {
+    let keyword = match procedure.export {
+        true => "pub extern proc ",
+        false => "pub proc ",
+    };
+    render_parts(
+        keyword,
+        &procedure.identifier,
+        &procedure.parameters,
+        &procedure.return_type,
+    )
+}
+
+/// Renders `program name(param Type, ...)` the way it is declared.
+fn render_program_signature(program: &Program) -> String {
+    let mut output = "program".to_string();
+    if let Some(name) = &program.name {
+        output.push(' ');
+        output.push_str(&escape(name));
+    }
+    if !program.parameters.is_empty() {
+        output.push('(');
+        output.push_str(&render_parameters(&program.parameters));
+        output.push(')');
+    }
+    output
+}
+
+fn render_parts(
+    keyword: &str,
+    identifier: &str,
+    parameters: &[Parameter],
+    return_type: &Option<SchemaType>,
+) -> String {
+    let mut output = String::new();
+    output.push_str(keyword);
+    output.push_str(&escape(identifier));
+    output.push('(');
+    output.push_str(&render_parameters(parameters));
+    output.push(')');
+    if let Some(return_type) = return_type {
+        output.push(' ');
+        output.push_str(&render_type_link(return_type));
+    }
+    output
+}
+
+fn render_parameters(parameters: &[Parameter]) -> String {
+    let mut output = String::new();
+    for (idx, parameter) in parameters.iter().enumerate() {
+        if idx > 0 {
+            output.push_str(", ");
+        }
+        output.push_str(&escape(&parameter.identifier));
+        output.push(' ');
+        output.push_str(&render_type_link(&parameter.schema_type));
+    }
+    output
+}
+
+fn render_type_link(typ: &SchemaType) -> String {
+    format!(
+        "<a href=\"#{}\">{}</a>",
+        type_anchor(&typ.identifier),
+        escape(&typ.to_string())
+    )
+}
+
+fn render_type(typ: &SchemaIdentifier) -> String {
+    format!(
+        "<h3 id=\"{}\">{}</h3>\n<p>{}</p>\n",
+        type_anchor(typ),
+        escape(&typ.to_string()),
+        describe_type(typ)
+    )
+}
+
+/// Doc comments are split into paragraphs on blank lines, a name in backticks
+/// that matches a procedure of the same module becomes a link to it.
+fn render_doc_text(src: &Src, module: &str, doc: &Option<String>) -> String {
+    let text = match doc {
+        Some(text) => text,
+        None => return String::new(),
+    };
+    let mut output = String::new();
+    for paragraph in text.split("\n\n") {
+        if paragraph.trim().is_empty() {
+            continue;
+        }
+        output.push_str("<p>");
+        for (idx, part) in paragraph.split('`').enumerate() {
+            if idx % 2 == 0 {
+                output.push_str(&escape(part));
+            } else if documented_procedures(src).any(|p| p.module == module && p.identifier == part)
+            {
+                output.push_str(&format!(
+                    "<a href=\"#{}\"><code>{}</code></a>",
+                    procedure_anchor(module, part),
+                    escape(part)
+                ));
+            } else {
+                output.push_str(&format!("<code>{}</code>", escape(part)));
+            }
+        }
+        output.push_str("</p>\n");
+    }
+    output
+}
+
+fn referenced_types(src: &Src) -> Vec<SchemaIdentifier> {
+    let mut types: Vec<SchemaIdentifier> = vec![];
+    let programs = src.programs.iter().map(|p| (&p.parameters, &None));
+    let procedures = documented_procedures(src).map(|p| (&p.parameters, &p.return_type));
+    let interface_procedures = src
+        .interfaces
+        .iter()
+        .flat_map(|i| i.procedures.iter())
+        .map(|p| (&p.parameters, &p.return_type));
+    for (parameters, return_type) in programs.chain(procedures).chain(interface_procedures) {
+        let parameters = parameters.iter().map(|p| &p.schema_type);
+        for typ in parameters.chain(return_type.iter()) {
+            if !types.contains(&typ.identifier) {
+                types.push(typ.identifier.clone());
+            }
+        }
+    }
+    types
+}
+
+fn describe_type(typ: &SchemaIdentifier) -> String {
+    match typ {
+        SchemaIdentifier::Bool => "true or false".to_string(),
+        SchemaIdentifier::Char => "a single ASCII character".to_string(),
+        SchemaIdentifier::CharSeq => "a sequence of characters".to_string(),
+        SchemaIdentifier::StringList => "a list of strings".to_string(),
+        SchemaIdentifier::Size => "an unsigned pointer sized integer".to_string(),
+        SchemaIdentifier::UserDefined(_) => "a user defined type".to_string(),
+        _ => match (typ.family(), typ.width()) {
+            (TypeFamily::Int, Some(width)) => format!("a {} bit signed integer", width),
+            (TypeFamily::UInt, Some(width)) => format!("a {} bit unsigned integer", width),
+            (TypeFamily::Float, Some(width)) => format!("a {} bit floating point number", width),
+            _ => String::new(),
+        },
+    }
+}
+
+/// Named programs go by their name, the others by their position.
+fn program_title(idx: usize, program: &Program) -> String {
+    match &program.name {
+        Some(name) => name.clone(),
+        None => (idx + 1).to_string(),
+    }
+}
+
+fn program_anchor(idx: usize, program: &Program) -> String {
+    format!("program.{}", program_title(idx, program))
+}
+
+fn procedure_anchor(module: &str, name: &str) -> String {
+    if module.is_empty() {
+        return format!("proc.{}", name);
+    }
+    format!("proc.{}.{}", module, name)
+}
+
+fn qualified_name(procedure: &Procedure) -> String {
+    if procedure.module.is_empty() {
+        return procedure.identifier.clone();
+    }
+    format!("{}.{}", procedure.module, procedure.identifier)
+}
+
+fn interface_anchor(name: &str) -> String {
+    format!("interface.{}", name)
+}
+
+fn type_anchor(typ: &SchemaIdentifier) -> String {
+    format!("type.{}", typ.to_string())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{parameter, procedure, program};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_procedure_cross_links() {
+        let mut nullable = schema_type(SchemaIdentifier::Int32);
+        nullable.postfix = TypePostfix::Opt;
+        let src = Src {
+            procedures: vec![
+                Procedure {
+                    public: true,
+                    doc: Some("Wraps `inner` & friends.\n\nSecond paragraph.".to_string()),
+                    parameters: vec![Parameter {
+                        identifier: "value".to_string(),
+                        schema_type: nullable,
+                    }],
+                    return_type: Some(schema_type(SchemaIdentifier::Bool)),
+                    ..procedure("util", "outer")
+                },
+                procedure("util", "inner"),
+            ],
+            ..Src::default()
+        };
+        let actual = render_procedure(&src, &src.procedures[0]);
+        let expected = concat!(
+            "<h3 id=\"proc.util.outer\">util.outer</h3>\n",
+            "<pre><code>pub proc outer(value <a href=\"#type.Int32\">Int32?</a>) ",
+            "<a href=\"#type.Bool\">Bool</a></code></pre>\n",
+            "<p>Wraps <code>inner</code> &amp; friends.</p>\n",
+            "<p>Second paragraph.</p>\n",
+        );
+        assert_eq!(actual, expected);
+        assert_eq!(
+            referenced_types(&src),
+            vec![SchemaIdentifier::Int32, SchemaIdentifier::Bool]
+        );
+        let page = render_doc(&src);
+        assert!(page.contains("proc.util.outer"));
+        assert!(!page.contains("proc.util.inner"));
+    }
+
+    #[test]
+    fn test_program_signature() {
+        let server = Program {
+            doc: Some("Serves.".to_string()),
+            name: Some("server".to_string()),
+            module: "main".to_string(),
+            ..program(
+                vec![parameter(
+                    "port",
+                    SchemaIdentifier::CharSeq,
+                    TypePostfix::None,
+                )],
+                vec![],
+            )
+        };
+        let src = Src {
+            programs: vec![server],
+            ..Src::default()
+        };
+        let expected = concat!(
+            "<h3 id=\"program.server\">program server</h3>\n",
+            "<pre><code>program server(port <a href=\"#type.String\">String</a>)</code></pre>\n",
+            "<p>Serves.</p>\n",
+        );
+        assert_eq!(render_program(&src, 0, &src.programs[0]), expected);
+        assert_eq!(referenced_types(&src), vec![SchemaIdentifier::CharSeq]);
+        let unnamed = program(vec![], vec![]);
+        assert_eq!(render_program_signature(&unnamed), "program");
+    }
+}
@@ -0,0 +1,357 @@
+use std::io::{self, BufRead, Write};
+
+use crate::c::{CExpression, CSrcPatch, CStatement};
+use crate::compilation_error::{
+    CompilationError, FailedToParseReplEntry, FailedToReadInput, FailedToWriteToFile,
+    VariableTypeAmbiguous,
+};
+use crate::core::Of;
+use crate::interpreter::{Run, Session, display_value};
+use crate::palel::*;
+use crate::parser::parse_repl_entry;
+use crate::renderer_c::{render_expression, render_patch, render_statement};
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::{CTranspile, transpile_expression, transpile_statement};
+use crate::transpiler_context::Context;
+use crate::type_checking::{resolve_call, resolve_expression_type};
+
+const HELP: &str = "\
+enter a variable declaration or an expression to evaluate it
+:type <entry>  show the type the type checker resolves for the entry
+:ast <entry>   show the syntax tree of the entry
+:c <entry>     show the C code the entry transpiles to
+:help          show this help
+:quit          leave, as does the end of the input
+";
+
+/// An entry that passed the checks of the C backend, with the C it
+/// transpiles to.
+enum Checked {
+    /// A variable declaration, with the type of the variable, or a call of a
+    /// procedure without a value
+    Statement(Statement, Option<Type>, CStatement, CSrcPatch),
+    Expression(Expression, Type, CExpression, CSrcPatch),
+}
+
+/// What the REPL does with the entry on a line.
+enum Command {
+    Run,
+    Type,
+    Ast,
+    C,
+}
+
+/// Reads entries from `input` line by line until the input ends or the
+/// program exits, and returns the exit status. `src` holds the procedures of
+/// the project and must pass `transpile`. A prompt is written before each
+/// line when `prompt` is set. A line that is not valid UTF-8 is reported and
+/// skipped.
+pub fn repl(src: Src, input: &mut dyn BufRead, out: &mut dyn Write, prompt: bool) -> Of<i32> {
+    match run_repl(Session::new(src), input, out, prompt) {
+        Ok(status) => status,
+        Err(_) => Of::Error(Box::new(FailedToWriteToFile {
+            file: "<stdout>".to_string(),
+        })),
+    }
+}
+
+/// Fails with the errors of writing to `out`, the errors of reading `input`
+/// end the session with an error instead.
+fn run_repl(
+    mut session: Session,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    prompt: bool,
+) -> io::Result<Of<i32>> {
+    if prompt {
+        writeln!(out, "palel repl, :help lists the commands")?;
+    }
+    loop {
+        if prompt {
+            write!(out, "> ")?;
+        }
+        out.flush()?;
+        let mut bytes = vec![];
+        match input.read_until(b'\n', &mut bytes) {
+            Ok(0) => {
+                if prompt {
+                    writeln!(out)?;
+                }
+                return Ok(Of::Ok(0));
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                return Ok(Of::Error(Box::new(FailedToReadInput {
+                    reason: err.to_string(),
+                })));
+            }
+        }
+        let line = match String::from_utf8(bytes) {
+            Ok(line) => line,
+            Err(_) => {
+                let err = FailedToParseReplEntry {
+                    reason: "the line is not valid UTF-8".to_string(),
+                };
+                writeln!(out, "{}", err.report())?;
+                continue;
+            }
+        };
+        let line = line.trim();
+        let (command, entry) = match line.split_once(char::is_whitespace) {
+            Some((":type", entry)) => (Command::Type, entry),
+            Some((":ast", entry)) => (Command::Ast, entry),
+            Some((":c", entry)) => (Command::C, entry),
+            _ => match line {
+                "" => continue,
+                ":help" => {
+                    write!(out, "{}", HELP)?;
+                    continue;
+                }
+                ":quit" => return Ok(Of::Ok(0)),
+                _ if line.starts_with(':') => {
+                    writeln!(
+                        out,
+                        "unknown command '{}', :help lists the commands",
+                        line.split_whitespace().next().unwrap_or(line)
+                    )?;
+                    continue;
+                }
+                _ => (Command::Run, line),
+            },
+        };
+        let entry = match parse_repl_entry(entry.trim()) {
+            Of::Ok(entry) => entry,
+            Of::Error(err) => {
+                writeln!(out, "{}", err.report())?;
+                continue;
+            }
+        };
+        if let Command::Ast = command {
+            writeln!(out, "{:#?}", entry)?;
+            continue;
+        }
+        let checked = match check_entry(entry, session.context()) {
+            Of::Ok(checked) => checked,
+            Of::Error(err) => {
+                writeln!(out, "{}", err.report())?;
+                continue;
+            }
+        };
+        let mut failures = vec![];
+        let outcome = match (command, checked) {
+            (Command::Type, Checked::Statement(Statement::Variable(declaration), typ, ..)) => {
+                let typ = typ.map(|t| t.to_string()).unwrap_or_default();
+                writeln!(out, "{} : {}", declaration.identifier, typ)?;
+                continue;
+            }
+            (Command::Type, Checked::Statement(..)) => {
+                writeln!(out, "the procedure has no value")?;
+                continue;
+            }
+            (Command::Type, Checked::Expression(_, typ, ..)) => {
+                writeln!(out, "{}", typ.to_string())?;
+                continue;
+            }
+            (Command::C, Checked::Statement(_, _, statement, patch)) => {
+                write!(
+                    out,
+                    "{}{}",
+                    render_patch(&patch),
+                    render_statement(&statement)
+                )?;
+                continue;
+            }
+            (Command::C, Checked::Expression(_, _, expression, patch)) => {
+                write!(out, "{}", render_patch(&patch))?;
+                writeln!(out, "{}", render_expression(&expression))?;
+                continue;
+            }
+            (_, Checked::Statement(statement, ..)) => {
+                session.execute(&statement, out, &mut failures)
+            }
+            (_, Checked::Expression(expression, typ, ..)) => {
+                match session.evaluate(&expression, &typ, out, &mut failures) {
+                    Run::Ok(value) => {
+                        writeln!(out, "{} : {}", display_value(&value, &typ), typ.to_string())?;
+                        Run::Ok(())
+                    }
+                    Run::Exit(status) => Run::Exit(status),
+                    Run::Error(err) => Run::Error(err),
+                }
+            }
+        };
+        // failed assertions belong in the transcript like the errors
+        out.write_all(&failures)?;
+        match outcome {
+            Run::Ok(()) => {}
+            Run::Exit(status) => return Ok(Of::Ok(status)),
+            Run::Error(err) => writeln!(out, "{}", err.report())?,
+        }
+    }
+}
+
+/// Checks `entry` against the variables and procedures of `ctx` the way the
+/// C backend checks a statement of a program.
+fn check_entry(entry: ReplEntry, ctx: &Context) -> Of<Checked> {
+    let toolkit = CToolKit {};
+    let expression = match entry {
+        ReplEntry::Variable(declaration) => {
+            return check_statement(Statement::Variable(declaration), ctx, &toolkit);
+        }
+        ReplEntry::Expression(Expression::Call(call)) if !has_value(&call, ctx) => {
+            return check_statement(Statement::ProcedureCall(call), ctx, &toolkit);
+        }
+        ReplEntry::Expression(expression) => expression,
+    };
+    let typ = match resolve_expression_type(&expression, ctx) {
+        Of::Ok(typ) => match typ.to_type() {
+            Some(typ) => typ,
+            None => return Of::Error(Box::new(VariableTypeAmbiguous {})),
+        },
+        Of::Error(err) => return Of::Error(err),
+    };
+    match transpile_expression(&expression, &typ, ctx, &toolkit) {
+        CTranspile::Ok(c, patch) => Of::Ok(Checked::Expression(expression, typ, c, patch)),
+        CTranspile::Error(err) => Of::Error(err),
+    }
+}
+
+fn check_statement(statement: Statement, ctx: &Context, toolkit: &CToolKit) -> Of<Checked> {
+    let mut ctx = ctx.clone();
+    match transpile_statement(&statement, &mut ctx, toolkit) {
+        CTranspile::Ok(c, patch) => {
+            let typ = match &statement {
+                Statement::Variable(declaration) => ctx.lookup(&declaration.identifier).cloned(),
+                _ => None,
+            };
+            Of::Ok(Checked::Statement(statement, typ, c, patch))
+        }
+        CTranspile::Error(err) => Of::Error(err),
+    }
+}
+
+/// Calls of procedures without a value are run as statements, any other
+/// error is left to the checks of the expression.
+fn has_value(call: &ProcedureCall, ctx: &Context) -> bool {
+    match resolve_call(call, ctx) {
+        Of::Ok(signature) => signature.return_type.is_some(),
+        Of::Error(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_task::SrcFile;
+    use crate::parser::parse;
+    use pretty_assertions::assert_eq;
+
+    fn run(content: &str, input: &[u8]) -> (String, i32) {
+        let mut src = Src::default();
+        let file = SrcFile {
+            file: "math.palel".to_string(),
+            content: content.to_string(),
+        };
+        assert!(parse(&mut src, &file).is_none());
+        let mut out = vec![];
+        let status = match repl(src, &mut &input[..], &mut out, false) {
+            Of::Ok(status) => status,
+            Of::Error(err) => panic!("{}", err.message()),
+        };
+        (String::from_utf8(out).unwrap(), status)
+    }
+
+    #[test]
+    fn test_repl() {
+        let content = "pub proc twice(x Int64) Int64 do\n    return x\nend\n";
+        let input = concat!(
+            "dim x Int32? = null\n",
+            "x\n",
+            "dim s = \"hi\"\n",
+            "debug:print(s, \" \", math.twice(7), \"\\n\")\n",
+            ":type s\n",
+            ":type 300 as UInt8\n",
+            ":c math.twice(3)\n",
+            ":c dim y UInt8 = 2\n",
+            "y\n",
+            "nope\n",
+            ":what\n",
+            "process:exit(4)\n",
+            "unreached\n",
+        );
+        let (output, status) = run(content, input.as_bytes());
+        assert_eq!(
+            output,
+            concat!(
+                "null : dim Int32?\n",
+                "hi 7\n",
+                "dim String\n",
+                "dim UInt8\n",
+                "math__twice(3)\n",
+                "#include <stdint.h>\n",
+                "uint8_t y = 2;\n",
+                "could not find variable 'y'\n",
+                "could not find variable 'nope'\n",
+                "unknown command ':what', :help lists the commands\n",
+            )
+        );
+        assert_eq!(status, 4);
+    }
+
+    #[test]
+    fn test_repl_checked_cast_null() {
+        let input = concat!(
+            "254 as? UInt8\n",
+            "255 as? UInt8\n",
+            "-127 as? Int8\n",
+            "-128 as? Int8\n",
+            "dim low Int8 = -128\n",
+            "low as? Int8\n",
+            "255.0 as? UInt8\n",
+        );
+        let (output, _) = run("", input.as_bytes());
+        assert_eq!(
+            output,
+            concat!(
+                "254 : dim UInt8?\n",
+                "null : dim UInt8?\n",
+                "-127 : dim Int8?\n",
+                "null : dim Int8?\n",
+                "null : dim Int8?\n",
+                "null : dim UInt8?\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_repl_input_errors() {
+        let (output, status) = run("", b"dim s = \"\xff\"\n7\n");
+        assert_eq!(
+            output,
+            concat!(
+                "could not parse the entry\n",
+                "the line is not valid UTF-8\n",
+                "7 : dim Int32\n",
+            )
+        );
+        assert_eq!(status, 0);
+
+        struct FailingInput;
+        impl io::Read for FailingInput {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("device gone"))
+            }
+        }
+        let mut out = vec![];
+        match repl(
+            Src::default(),
+            &mut io::BufReader::new(FailingInput),
+            &mut out,
+            false,
+        ) {
+            Of::Ok(_) => panic!("expected a read error"),
+            Of::Error(err) => assert_eq!(err.message(), "failed to read the input: device gone"),
+        }
+    }
+}
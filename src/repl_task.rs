@@ -0,0 +1,27 @@
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use crate::build_task::{BuildTask, load_src};
+use crate::core::Of;
+use crate::palel::Src;
+use crate::repl::repl;
+use crate::toolkit_c::CToolKit;
+use crate::transpiler_c::transpile;
+
+/// Starts the REPL on standard input. Inside a project the public procedures
+/// of its modules can be called.
+pub fn run_repl_task(task: &mut BuildTask) -> Of<i32> {
+    let src = match Path::new(&task.config.src_dir).is_dir() {
+        true => match load_src(task) {
+            Of::Ok(src) => src,
+            Of::Error(err) => return Of::Error(err),
+        },
+        false => Src::default(),
+    };
+    if let Of::Error(err) = transpile(&src, &CToolKit {}) {
+        return Of::Error(err);
+    }
+    let stdin = io::stdin();
+    let prompt = stdin.is_terminal();
+    repl(src, &mut stdin.lock(), &mut io::stdout(), prompt)
+}
@@ -0,0 +1,62 @@
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Command;
+
+use crate::build_task::{BuildTask, binary_names, execute, load_src};
+use crate::compilation_error::{AmbiguousProgram, FailedToRunProgram, UnknownProgram};
+use crate::core::Of;
+use crate::interpreter::interpret;
+
+pub struct RunTaskConfig {
+    /// Evaluate the program in palel instead of compiling it with the C
+    /// compiler
+    pub interpret: bool,
+    pub arguments: Vec<String>,
+}
+
+/// Builds and runs one program, the one selected with `--bin` when the project
+/// has several, and returns its exit status.
+pub fn run_run_task(task: &mut BuildTask, config: &RunTaskConfig) -> Of<i32> {
+    let src = match load_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Of::Error(err),
+    };
+    let names = match binary_names(&task.config, &src.programs) {
+        Of::Ok(names) => names,
+        Of::Error(err) => return Of::Error(err),
+    };
+    let selected = match &task.config.bin {
+        Some(bin) => match names.iter().position(|name| name == bin) {
+            Some(idx) => idx,
+            None => return Of::Error(Box::new(UnknownProgram { name: bin.clone() })),
+        },
+        None if names.len() == 1 => 0,
+        None => return Of::Error(Box::new(AmbiguousProgram { names })),
+    };
+    let name = names[selected].clone();
+
+    if config.interpret {
+        return interpret(
+            &src,
+            &src.programs[selected],
+            &name,
+            &config.arguments,
+            &mut io::stdout(),
+            &mut io::stderr(),
+        );
+    }
+
+    task.config.bin = Some(name.clone());
+    if let Some(err) = execute(task) {
+        return Of::Error(err);
+    }
+    let bin_file = format!("{}/{}", task.config.dest_dir, name);
+    match Command::new(&bin_file).args(&config.arguments).status() {
+        Ok(status) => match (status.code(), status.signal()) {
+            (Some(code), _) => Of::Ok(code),
+            (None, Some(signal)) => Of::Ok(128 + signal),
+            (None, None) => Of::Ok(1),
+        },
+        Err(_) => Of::Error(Box::new(FailedToRunProgram { file: bin_file })),
+    }
+}
@@ -0,0 +1,64 @@
+use crate::compilation_error::CompilationError;
+use crate::palel::*;
+
+/// An unnamed program of the root module.
+pub fn program(parameters: Vec<Parameter>, statements: Vec<Statement>) -> Program {
+    Program {
+        name: None,
+        doc: None,
+        module: "".to_string(),
+        parameters,
+        do_block: do_block(statements),
+    }
+}
+
+/// A private procedure without parameters, return type or statements.
+pub fn procedure(module: &str, identifier: &str) -> Procedure {
+    Procedure {
+        doc: None,
+        module: module.to_string(),
+        public: false,
+        export: false,
+        interface: "".to_string(),
+        identifier: identifier.to_string(),
+        parameters: vec![],
+        return_type: None,
+        do_block: do_block(vec![]),
+    }
+}
+
+pub fn module(name: &str, imports: Vec<&str>) -> Module {
+    Module {
+        name: name.to_string(),
+        file: file_from_module_name(name),
+        imports: imports.iter().map(|i| i.to_string()).collect(),
+    }
+}
+
+pub fn parameter(
+    identifier: &str,
+    identifier_type: SchemaIdentifier,
+    postfix: TypePostfix,
+) -> Parameter {
+    let mut schema = schema_type(identifier_type);
+    schema.postfix = postfix;
+    Parameter {
+        identifier: identifier.to_string(),
+        schema_type: schema,
+    }
+}
+
+pub fn do_block(statements: Vec<Statement>) -> DoBlock {
+    DoBlock {
+        statements,
+        lines: vec![],
+    }
+}
+
+/// The message of a check, empty when it passed.
+pub fn message(error: Option<Box<dyn CompilationError>>) -> String {
+    match error {
+        Some(err) => err.message(),
+        None => "".to_string(),
+    }
+}
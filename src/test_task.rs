@@ -0,0 +1,151 @@
+use std::fs;
+use std::process::Command;
+
+use walkdir::WalkDir;
+
+use crate::build_task::{BuildTask, build_tests, load_src, src_files, write};
+use crate::compilation_error::{CompilationError, FailedToRunProgram, TestsFailed};
+use crate::core::Of;
+use crate::coverage::{file_coverage, line_counts, render_lcov, render_summary, render_text};
+use crate::downstream_compiler_c::downstream_coverage;
+use crate::palel::Src;
+
+pub struct TestTaskConfig {
+    /// Only run the tests whose name contains it
+    pub filter: Option<String>,
+    /// Instrument the harness and report which statements the tests ran
+    pub coverage: bool,
+}
+
+/// Builds the test harness and runs every selected test in a process of its
+/// own, so a failing assert ends only its test. The failed tests are the
+/// error.
+pub fn run_test_task(
+    task: &mut BuildTask,
+    config: &TestTaskConfig,
+) -> Option<Box<dyn CompilationError>> {
+    let src = match load_src(task) {
+        Of::Ok(src) => src,
+        Of::Error(err) => return Some(err),
+    };
+    let names: Vec<String> = src
+        .tests
+        .iter()
+        .map(|test| format!("{}: {}", test.module, test.name))
+        .collect();
+    let selected: Vec<usize> = (0..names.len())
+        .filter(|idx| match &config.filter {
+            Some(filter) => names[*idx].contains(filter.as_str()),
+            None => true,
+        })
+        .collect();
+    if config.coverage {
+        task.config.compiler.flags.push("--coverage".to_string());
+        task.config.compiler.optimization = Some("0".to_string());
+        // counts of earlier runs would add up with those of this one
+        for data_file in coverage_data_files(&task.config.dest_dir) {
+            let _ = fs::remove_file(data_file);
+        }
+    }
+    let harness = match build_tests(task) {
+        Of::Ok(Some(harness)) => harness,
+        Of::Ok(None) => {
+            println!("running 0 tests");
+            return None;
+        }
+        Of::Error(err) => return Some(err),
+    };
+
+    println!("running {} test(s)", selected.len());
+    let mut failures = vec![];
+    for idx in &selected {
+        let output = match Command::new(&harness).arg(idx.to_string()).output() {
+            Ok(output) => output,
+            Err(_) => return Some(Box::new(FailedToRunProgram { file: harness })),
+        };
+        match output.status.success() {
+            true => println!("test {} ... ok", names[*idx]),
+            false => {
+                println!("test {} ... FAILED", names[*idx]);
+                failures.push((names[*idx].clone(), output));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, output) in &failures {
+            println!("\n---- {} ----", name);
+            for captured in [&output.stdout, &output.stderr] {
+                let text = String::from_utf8_lossy(captured);
+                match text.is_empty() || text.ends_with('\n') {
+                    true => print!("{}", text),
+                    false => println!("{}", text),
+                }
+            }
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} filtered out",
+        match failures.is_empty() {
+            true => "ok",
+            false => "FAILED",
+        },
+        selected.len() - failures.len(),
+        failures.len(),
+        names.len() - selected.len()
+    );
+    if config.coverage
+        && let Some(err) = report_coverage(task, &src)
+    {
+        return Some(err);
+    }
+    match failures.is_empty() {
+        true => None,
+        false => Some(Box::new(TestsFailed {
+            failed: failures.into_iter().map(|(name, _)| name).collect(),
+        })),
+    }
+}
+
+/// Writes the coverage of the tests that ran as text and as lcov tracefile
+/// into the coverage folder of the build directory and prints its summary.
+fn report_coverage(task: &BuildTask, src: &Src) -> Option<Box<dyn CompilationError>> {
+    let data_files = coverage_data_files(&task.config.dest_dir);
+    let report = match downstream_coverage(&task.config.compiler, &data_files) {
+        Of::Ok(report) => report,
+        Of::Error(err) => return Some(err),
+    };
+    let coverage = file_coverage(
+        src,
+        src_files(task),
+        &task.config.src_dir,
+        &line_counts(&report),
+    );
+    let coverage_dir = format!("{}/coverage", task.config.dest_dir);
+    let text_file = format!("{}/coverage.txt", coverage_dir);
+    if let Some(err) = write(&text_file, &render_text(&coverage)) {
+        return Some(err);
+    }
+    let lcov_file = format!("{}/lcov.info", coverage_dir);
+    if let Some(err) = write(&lcov_file, &render_lcov(&coverage)) {
+        return Some(err);
+    }
+    println!(
+        "
+coverage:"
+    );
+    print!("{}", render_summary(&coverage));
+    println!("written to {} and {}", text_file, lcov_file);
+    None
+}
+
+/// The `.gcda` files the instrumented harness writes next to its objects.
+fn coverage_data_files(dest_dir: &str) -> Vec<String> {
+    WalkDir::new(dest_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "gcda"))
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .collect()
+}
@@ -1,8 +1,20 @@
-use crate::c::{CExpression, CFunctionCall, CInclude, CLiteral, CSrcPatch, CType};
-use crate::compilation_error::{TypeNotNullable, UnknownInterface};
-use crate::palel::{ProcedureCall, SchemaIdentifier, Type};
-use crate::transpiler_c::{CTranspile, transpile_expressions};
+use crate::c::{
+    CAssignment, CBinary, CBlock, CCast, CConditional, CExpression, CFunction, CFunctionCall, CIf,
+    CInclude, CIndex, CLiteral, CMember, CParameter, CPrototype, CReturn, CSrcPatch, CStatement,
+    CType, CUnary, CVariableDeclaration, CWhile, int_type, void_type,
+};
+use crate::compilation_error::{
+    CouldNotTranspileType, TypeNotNullable, TypeNotPrintable, UnknownInterface, UnknownProcedure,
+    VariableTypeAmbiguous,
+};
+use crate::palel::{
+    CastMode, ExpressionType, Implementation, Parameter, ProcedureCall, ProcedureSignature,
+    SchemaIdentifier, SchemaType, Type, TypeFamily, TypePostfix, call_name, schema_type,
+};
+use crate::transpiler_c::{CTranspile, transpile_expression, transpile_expressions};
 use crate::transpiler_c_patch::merge_patch;
+use crate::transpiler_context::Context;
+use crate::type_checking::{check_arguments, type_of_expression};
 
 use CTranspile::*;
 use SchemaIdentifier::*;
@@ -10,18 +22,65 @@ use SchemaIdentifier::*;
 pub struct CToolKit {}
 
 impl CToolKit {
-    pub fn transpile_interface_call(&self, input: &ProcedureCall) -> CTranspile<CFunctionCall> {
-        if input.interface != "debug" {
-            return Error(Box::new(UnknownInterface {
+    /// The interfaces provided by the toolkit itself. Their procedures are
+    /// called like those of any other interface and transpiled by
+    /// `transpile_builtin_call`.
+    pub fn builtin_procedures(&self) -> Vec<ProcedureSignature> {
+        let builtin = |interface: &str,
+                       identifier: &str,
+                       parameters: Vec<Type>,
+                       return_type: Option<Type>| ProcedureSignature {
+            module: "".to_string(),
+            public: true,
+            export: false,
+            interface: interface.to_string(),
+            identifier: identifier.to_string(),
+            parameters,
+            return_type,
+            implementation: Implementation::Builtin,
+        };
+        let list = Type::Dim(schema_type(StringList));
+        let int32 = Type::Dim(schema_type(Int32));
+        let mut string = schema_type(CharSeq);
+        string.postfix = TypePostfix::Opt;
+        vec![
+            // takes any number of values of any type
+            builtin("debug", "print", vec![], None),
+            builtin("process", "exit", vec![int32.clone()], None),
+            builtin("strings", "count", vec![list.clone()], Some(int32.clone())),
+            builtin("strings", "get", vec![list, int32], Some(Type::Dim(string))),
+        ]
+    }
+
+    pub fn transpile_builtin_call(
+        &self,
+        input: &ProcedureCall,
+        ctx: &Context,
+    ) -> CTranspile<CFunctionCall> {
+        match input.interface.as_str() {
+            "debug" => self.transpile_debug_call(input, ctx),
+            "process" => self.transpile_process_call(input, ctx),
+            "strings" => self.transpile_strings_call(input, ctx),
+            _ => Error(Box::new(UnknownInterface {
                 interface: input.interface.clone(),
-            }));
+            })),
         }
+    }
+
+    fn transpile_debug_call(
+        &self,
+        input: &ProcedureCall,
+        ctx: &Context,
+    ) -> CTranspile<CFunctionCall> {
         let mut patch = CSrcPatch {
             includes: vec![CInclude {
                 file: "stdio.h".to_string(),
+                local: false,
             }],
+            prototypes: vec![],
+            helpers: vec![],
         };
-        let expressions = match transpile_expressions(&input.arguments, self) {
+        let expressions = match transpile_expressions(&input.arguments, ctx, self) {
             Ok(exprs, in_patch) => {
                 merge_patch(&mut patch, &in_patch);
                 exprs
@@ -31,70 +90,830 @@ impl CToolKit {
             }
         };
 
-        let function_call = CFunctionCall {
-            function_name: input.identifier.clone(),
-            arguments: expressions,
+        if input.identifier != "print" {
+            return Error(Box::new(UnknownProcedure {
+                name: call_name(&input.interface, &input.identifier),
+            }));
+        }
+        self.transpile_debug_print(input, expressions, ctx, patch)
+    }
+
+    /// `process:exit(status Int32)` ends the process right away with `status`.
+    fn transpile_process_call(
+        &self,
+        input: &ProcedureCall,
+        ctx: &Context,
+    ) -> CTranspile<CFunctionCall> {
+        let name = call_name(&input.interface, &input.identifier);
+        if input.identifier != "exit" {
+            return Error(Box::new(UnknownProcedure { name }));
+        }
+        let status = Type::Dim(schema_type(Int32));
+        if let Some(err) =
+            check_arguments(&name, std::slice::from_ref(&status), &input.arguments, ctx)
+        {
+            return Error(err);
+        }
+        let mut patch = CSrcPatch {
+            includes: vec![CInclude {
+                file: "stdlib.h".to_string(),
+                local: false,
+            }],
+            prototypes: vec![],
+            helpers: vec![],
+        };
+        let value = match transpile_expression(&input.arguments[0], &status, ctx, self) {
+            Ok(value, in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                value
+            }
+            Error(e) => return Error(e),
+        };
+        Ok(
+            CFunctionCall {
+                function_name: "exit".to_string(),
+                arguments: vec![value],
+            },
+            patch,
+        )
+    }
+
+    /// `strings:count(list StringList) Int32` and `strings:get(list StringList,
+    /// index Int32) String?` call a helper walking the null terminated list,
+    /// `get` yields null for an index outside of it.
+    fn transpile_strings_call(
+        &self,
+        input: &ProcedureCall,
+        ctx: &Context,
+    ) -> CTranspile<CFunctionCall> {
+        let name = call_name(&input.interface, &input.identifier);
+        let signature = match self
+            .builtin_procedures()
+            .into_iter()
+            .find(|s| s.interface == input.interface && s.identifier == input.identifier)
+        {
+            Some(signature) => signature,
+            None => return Error(Box::new(UnknownProcedure { name })),
+        };
+        if let Some(err) = check_arguments(&name, &signature.parameters, &input.arguments, ctx) {
+            return Error(err);
+        }
+        let helper = match input.identifier.as_str() {
+            "count" => strings_count_helper(),
+            _ => strings_get_helper(),
+        };
+        let mut patch = CSrcPatch::default();
+        let mut arguments = vec![];
+        for (argument, typ) in input.arguments.iter().zip(&signature.parameters) {
+            match transpile_expression(argument, typ, ctx, self) {
+                Ok(value, in_patch) => {
+                    merge_patch(&mut patch, &in_patch);
+                    arguments.push(value);
+                }
+                Error(e) => return Error(e),
+            }
+        }
+        merge_patch(
+            &mut patch,
+            &CSrcPatch {
+                includes: vec![CInclude {
+                    file: "stdint.h".to_string(),
+                    local: false,
+                }],
+                prototypes: vec![],
+                helpers: vec![helper.clone()],
+            },
+        );
+        Ok(
+            CFunctionCall {
+                function_name: helper.name,
+                arguments,
+            },
+            patch,
+        )
+    }
+
+    /// Prints `message` on stderr and ends the process with status 1 when
+    /// `condition` is false.
+    pub fn transpile_assert(
+        &self,
+        condition: CExpression,
+        message: &str,
+    ) -> CTranspile<CStatement> {
+        let patch = CSrcPatch {
+            includes: vec![
+                CInclude {
+                    file: "stdio.h".to_string(),
+                    local: false,
+                },
+                CInclude {
+                    file: "stdlib.h".to_string(),
+                    local: false,
+                },
+            ],
+            prototypes: vec![],
+            helpers: vec![],
+        };
+        let statement = CIf {
+            condition: binary(condition, "==", number(0)),
+            block: CBlock {
+                statements: vec![
+                    CFunctionCall {
+                        function_name: "fputs".to_string(),
+                        arguments: vec![
+                            CLiteral::String(format!("{}\n", message)).to_expression(),
+                            CExpression::Variable("stderr".to_string()),
+                        ],
+                    }
+                    .to_statement(),
+                    CFunctionCall {
+                        function_name: "exit".to_string(),
+                        arguments: vec![number(1)],
+                    }
+                    .to_statement(),
+                ],
+            },
+        };
+        Ok(statement.to_statement(), patch)
+    }
+
+    /// The `main` parameters and the statements binding each command-line
+    /// argument to a program parameter, a last `StringList` parameter takes
+    /// the arguments left over. When too few or too many arguments are given a
+    /// usage line is printed and the program exits with status 2.
+    pub fn transpile_program_arguments(
+        &self,
+        parameters: &[Parameter],
+    ) -> CTranspile<(Vec<CParameter>, Vec<CStatement>)> {
+        let mut patch = CSrcPatch {
+            includes: vec![CInclude {
+                file: "stdio.h".to_string(),
+                local: false,
+            }],
+            prototypes: vec![],
+            helpers: vec![],
+        };
+        let argc = || CExpression::Variable("argc".to_string());
+        let argv = |idx: usize| {
+            CIndex {
+                expression: Box::new(CExpression::Variable("argv".to_string())),
+                index: Box::new(number(idx)),
+            }
+            .to_expression()
+        };
+        let (strings, rest) = match parameters.split_last() {
+            Some((last, strings)) if last.schema_type.identifier == StringList => {
+                (strings, Some(last))
+            }
+            _ => (parameters, None),
+        };
+        let required = strings
+            .iter()
+            .filter(|p| p.schema_type.postfix != TypePostfix::Opt)
+            .count();
+
+        let mut usage = "usage: %s".to_string();
+        for parameter in parameters {
+            if parameter.schema_type.identifier == StringList {
+                usage.push_str(&format!(" [{}...]", parameter.identifier));
+            } else if parameter.schema_type.postfix == TypePostfix::Opt {
+                usage.push_str(&format!(" [{}]", parameter.identifier));
+            } else {
+                usage.push_str(&format!(" <{}>", parameter.identifier));
+            }
+        }
+        usage.push('\n');
+        let too_few = binary(argc(), "<", number(required + 1));
+        let wrong_count = match rest {
+            Some(_) => too_few,
+            None => binary(
+                too_few,
+                "||",
+                binary(argc(), ">", number(strings.len() + 1)),
+            ),
+        };
+        let mut statements = vec![
+            CIf {
+                condition: wrong_count,
+                block: CBlock {
+                    statements: vec![
+                        CFunctionCall {
+                            function_name: "fprintf".to_string(),
+                            arguments: vec![
+                                CExpression::Variable("stderr".to_string()),
+                                CLiteral::String(usage).to_expression(),
+                                argv(0),
+                            ],
+                        }
+                        .to_statement(),
+                        CReturn {
+                            value: Some(number(2)),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }
+            .to_statement(),
+        ];
+
+        for (idx, parameter) in strings.iter().enumerate() {
+            let position = idx + 1;
+            let value = if parameter.schema_type.postfix == TypePostfix::Opt {
+                let null = match self.transpile_null(&Type::Dim(parameter.schema_type.clone())) {
+                    Ok(null, in_patch) => {
+                        merge_patch(&mut patch, &in_patch);
+                        null
+                    }
+                    Error(e) => return Error(e),
+                };
+                conditional(binary(argc(), ">", number(position)), argv(position), null)
+            } else {
+                argv(position)
+            };
+            statements.push(
+                CVariableDeclaration {
+                    name: parameter.identifier.clone(),
+                    var_type: CType {
+                        name: "char".to_string(),
+                        is_pointer: true,
+                    },
+                    value,
+                }
+                .to_statement(),
+            );
+        }
+        // argv ends with a null pointer, the rest starts after the strings
+        // bound above or at that end when fewer were given
+        if let Some(rest) = rest {
+            let start = number(strings.len() + 1);
+            statements.push(
+                CVariableDeclaration {
+                    name: rest.identifier.clone(),
+                    var_type: CType {
+                        name: "char *".to_string(),
+                        is_pointer: true,
+                    },
+                    value: binary(
+                        CExpression::Variable("argv".to_string()),
+                        "+",
+                        conditional(binary(argc(), ">", start.clone()), start, argc()),
+                    ),
+                }
+                .to_statement(),
+            );
+        }
+
+        let main_parameters = vec![
+            CParameter {
+                name: "argc".to_string(),
+                typ: int_type(),
+            },
+            CParameter {
+                name: "argv".to_string(),
+                typ: CType {
+                    name: "char *".to_string(),
+                    is_pointer: true,
+                },
+            },
+        ];
+        Ok((main_parameters, statements), patch)
+    }
+
+    /// C symbol of a palel procedure, prefixed with its module so procedures of
+    /// the same name in different files do not collide. Interface procedures
+    /// bound to C keep the symbol they were bound to, those of an extern
+    /// interface their own name.
+    pub fn procedure_symbol(&self, signature: &ProcedureSignature) -> String {
+        let identifier = match (&signature.implementation, signature.interface.as_str()) {
+            (Implementation::C(symbol), _) => return symbol.clone(),
+            (Implementation::Header(_), _) => return signature.identifier.clone(),
+            _ if signature.export => return signature.identifier.clone(),
+            (_, "") => signature.identifier.clone(),
+            (_, interface) => format!("{}__{}", interface, signature.identifier),
+        };
+        if signature.module.is_empty() {
+            return identifier;
+        }
+        format!("{}__{}", mangle_module(&signature.module), identifier)
+    }
+
+    /// C symbol of the test at `idx` in the tests of the project.
+    pub fn test_symbol(&self, idx: usize) -> String {
+        format!("palel_test_{}", idx)
+    }
+
+    /// The `main` of the test harness. It calls the function of `tests` whose
+    /// index is its only argument and exits with status 2 for any other
+    /// arguments.
+    pub fn transpile_test_main(&self, tests: &[String]) -> CTranspile<CFunction> {
+        let patch = CSrcPatch {
+            includes: vec![
+                CInclude {
+                    file: "stdio.h".to_string(),
+                    local: false,
+                },
+                CInclude {
+                    file: "string.h".to_string(),
+                    local: false,
+                },
+            ],
+            prototypes: vec![],
+            helpers: vec![],
+        };
+        let argv = |idx: usize| {
+            CIndex {
+                expression: Box::new(CExpression::Variable("argv".to_string())),
+                index: Box::new(number(idx)),
+            }
+            .to_expression()
+        };
+        let mut statements = vec![
+            CIf {
+                condition: binary(CExpression::Variable("argc".to_string()), "!=", number(2)),
+                block: CBlock {
+                    statements: vec![
+                        CFunctionCall {
+                            function_name: "fprintf".to_string(),
+                            arguments: vec![
+                                CExpression::Variable("stderr".to_string()),
+                                CLiteral::String("usage: %s <test>\n".to_string()).to_expression(),
+                                argv(0),
+                            ],
+                        }
+                        .to_statement(),
+                        CReturn {
+                            value: Some(number(2)),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }
+            .to_statement(),
+        ];
+        for (idx, test) in tests.iter().enumerate() {
+            let selected = CFunctionCall {
+                function_name: "strcmp".to_string(),
+                arguments: vec![argv(1), CLiteral::String(idx.to_string()).to_expression()],
+            };
+            statements.push(
+                CIf {
+                    condition: binary(selected.to_expression(), "==", number(0)),
+                    block: CBlock {
+                        statements: vec![
+                            CFunctionCall {
+                                function_name: test.clone(),
+                                arguments: vec![],
+                            }
+                            .to_statement(),
+                            CReturn {
+                                value: Some(number(0)),
+                            }
+                            .to_statement(),
+                        ],
+                    },
+                }
+                .to_statement(),
+            );
+        }
+        statements.push(
+            CReturn {
+                value: Some(number(2)),
+            }
+            .to_statement(),
+        );
+        let main = CFunction {
+            name: "main".to_string(),
+            parameters: vec![
+                CParameter {
+                    name: "argc".to_string(),
+                    typ: int_type(),
+                },
+                CParameter {
+                    name: "argv".to_string(),
+                    typ: CType {
+                        name: "char *".to_string(),
+                        is_pointer: true,
+                    },
+                },
+            ],
+            return_type: int_type(),
+            block: CBlock { statements },
+        };
+        Ok(main, patch)
+    }
+
+    /// C symbol of the bench at `idx` in the benches of the project.
+    pub fn bench_symbol(&self, idx: usize) -> String {
+        format!("palel_bench_{}", idx)
+    }
+
+    /// `palel_bench_now`, the nanoseconds of the monotonic clock the bench
+    /// harness times the iterations with.
+    pub fn transpile_bench_clock(&self) -> CTranspile<CFunction> {
+        let patch = CSrcPatch {
+            includes: vec![
+                CInclude {
+                    file: "stdint.h".to_string(),
+                    local: false,
+                },
+                CInclude {
+                    file: "time.h".to_string(),
+                    local: false,
+                },
+            ],
+            prototypes: vec![],
+            helpers: vec![],
+        };
+        let now = || CExpression::Variable("now".to_string());
+        let member = |name: &str| {
+            CMember {
+                expression: Box::new(now()),
+                member: name.to_string(),
+            }
+            .to_expression()
+        };
+        let seconds = CCast {
+            typ: int64_type(),
+            expression: Box::new(member("tv_sec")),
+        }
+        .to_expression();
+        let clock = CFunction {
+            name: "palel_bench_now".to_string(),
+            parameters: vec![],
+            return_type: int64_type(),
+            block: CBlock {
+                statements: vec![
+                    CVariableDeclaration {
+                        name: "now".to_string(),
+                        var_type: CType {
+                            name: "struct timespec".to_string(),
+                            is_pointer: false,
+                        },
+                        value: CExpression::Initializer(vec![number(0)]),
+                    }
+                    .to_statement(),
+                    CFunctionCall {
+                        function_name: "clock_gettime".to_string(),
+                        arguments: vec![
+                            CExpression::Variable("CLOCK_MONOTONIC".to_string()),
+                            CUnary {
+                                operator: "&".to_string(),
+                                expression: Box::new(now()),
+                            }
+                            .to_expression(),
+                        ],
+                    }
+                    .to_statement(),
+                    CReturn {
+                        value: Some(binary(
+                            binary(seconds, "*", number(1_000_000_000)),
+                            "+",
+                            member("tv_nsec"),
+                        )),
+                    }
+                    .to_statement(),
+                ],
+            },
+        };
+        Ok(clock, patch)
+    }
+
+    /// The `main` of the bench harness, run as `<bench> <warmup> <samples>
+    /// <batch> <output>`. It calls the function of `benches` at the index
+    /// `bench` `warmup` times untimed, then times `samples` batches of `batch`
+    /// calls and writes the mean nanoseconds per call of each batch to the
+    /// file `output`, one per line. Other arguments exit with status 2.
+    pub fn transpile_bench_main(&self, benches: &[String]) -> CTranspile<CFunction> {
+        let mut patch = CSrcPatch {
+            includes: vec![],
+            prototypes: vec![],
+            helpers: vec![],
+        };
+        for file in ["stdint.h", "stdio.h", "stdlib.h", "string.h"] {
+            patch.includes.push(CInclude {
+                file: file.to_string(),
+                local: false,
+            });
+        }
+        let variable = |name: &str| CExpression::Variable(name.to_string());
+        let argv = |idx: usize| {
+            CIndex {
+                expression: Box::new(variable("argv")),
+                index: Box::new(number(idx)),
+            }
+            .to_expression()
+        };
+        let call = |function: &str, arguments: Vec<CExpression>| CFunctionCall {
+            function_name: function.to_string(),
+            arguments,
+        };
+        let count_down = |name: &str, mut statements: Vec<CStatement>| {
+            statements.push(
+                CAssignment {
+                    name: name.to_string(),
+                    value: binary(variable(name), "-", number(1)),
+                }
+                .to_statement(),
+            );
+            CWhile {
+                condition: binary(variable(name), ">", number(0)),
+                block: CBlock { statements },
+            }
+            .to_statement()
+        };
+        let count = |name: &str, idx: usize| {
+            CVariableDeclaration {
+                name: name.to_string(),
+                var_type: int64_type(),
+                value: call("atoll", vec![argv(idx)]).to_expression(),
+            }
+            .to_statement()
+        };
+        let mut statements = vec![
+            CIf {
+                condition: binary(variable("argc"), "!=", number(6)),
+                block: CBlock {
+                    statements: vec![
+                        call(
+                            "fprintf",
+                            vec![
+                                variable("stderr"),
+                                CLiteral::String(
+                                    "usage: %s <bench> <warmup> <samples> <batch> <output>\n"
+                                        .to_string(),
+                                )
+                                .to_expression(),
+                                argv(0),
+                            ],
+                        )
+                        .to_statement(),
+                        CReturn {
+                            value: Some(number(2)),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }
+            .to_statement(),
+            count("warmup", 2),
+            count("samples", 3),
+            count("batch", 4),
+            CVariableDeclaration {
+                name: "output".to_string(),
+                var_type: CType {
+                    name: "FILE".to_string(),
+                    is_pointer: true,
+                },
+                value: call(
+                    "fopen",
+                    vec![argv(5), CLiteral::String("w".to_string()).to_expression()],
+                )
+                .to_expression(),
+            }
+            .to_statement(),
+            CIf {
+                condition: binary(variable("output"), "==", variable("NULL")),
+                block: CBlock {
+                    statements: vec![
+                        CReturn {
+                            value: Some(number(2)),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }
+            .to_statement(),
+        ];
+        for (idx, bench) in benches.iter().enumerate() {
+            let selected = call(
+                "strcmp",
+                vec![argv(1), CLiteral::String(idx.to_string()).to_expression()],
+            );
+            let elapsed = binary(
+                call("palel_bench_now", vec![]).to_expression(),
+                "-",
+                variable("start"),
+            );
+            // the clock is read once per batch, so its cost is spread over
+            // the iterations of the batch instead of added to every one
+            let per_iteration = binary(
+                CCast {
+                    typ: CType {
+                        name: "double".to_string(),
+                        is_pointer: false,
+                    },
+                    expression: Box::new(elapsed),
+                }
+                .to_expression(),
+                "/",
+                variable("batch"),
+            );
+            let sample = vec![
+                CVariableDeclaration {
+                    name: "start".to_string(),
+                    var_type: int64_type(),
+                    value: call("palel_bench_now", vec![]).to_expression(),
+                }
+                .to_statement(),
+                CVariableDeclaration {
+                    name: "left".to_string(),
+                    var_type: int64_type(),
+                    value: variable("batch"),
+                }
+                .to_statement(),
+                count_down("left", vec![call(bench, vec![]).to_statement()]),
+                call(
+                    "fprintf",
+                    vec![
+                        variable("output"),
+                        CLiteral::String("%.3f\n".to_string()).to_expression(),
+                        per_iteration,
+                    ],
+                )
+                .to_statement(),
+            ];
+            statements.push(
+                CIf {
+                    condition: binary(selected.to_expression(), "==", number(0)),
+                    block: CBlock {
+                        statements: vec![
+                            count_down("warmup", vec![call(bench, vec![]).to_statement()]),
+                            count_down("samples", sample),
+                            call("fclose", vec![variable("output")]).to_statement(),
+                            CReturn {
+                                value: Some(number(0)),
+                            }
+                            .to_statement(),
+                        ],
+                    },
+                }
+                .to_statement(),
+            );
+        }
+        statements.push(
+            CReturn {
+                value: Some(number(2)),
+            }
+            .to_statement(),
+        );
+        let main = CFunction {
+            name: "main".to_string(),
+            parameters: vec![
+                CParameter {
+                    name: "argc".to_string(),
+                    typ: int_type(),
+                },
+                CParameter {
+                    name: "argv".to_string(),
+                    typ: CType {
+                        name: "char *".to_string(),
+                        is_pointer: true,
+                    },
+                },
+            ],
+            return_type: int_type(),
+            block: CBlock { statements },
+        };
+        Ok(main, patch)
+    }
+
+    /// Declares the C function an interface procedure is bound to with the
+    /// C types of the interface signature.
+    pub fn transpile_prototype(&self, signature: &ProcedureSignature) -> CTranspile<CPrototype> {
+        let mut patch = CSrcPatch::default();
+        let mut transpile = |typ: &Type| match self.transpile_type(typ) {
+            Ok(Some(t), in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                Some(t)
+            }
+            _ => None,
+        };
+        let mut parameters = vec![];
+        for (idx, typ) in signature.parameters.iter().enumerate() {
+            match transpile(typ) {
+                Some(t) => parameters.push(CParameter {
+                    name: format!("p{}", idx),
+                    typ: t,
+                }),
+                None => return Error(Box::new(CouldNotTranspileType {})),
+            }
+        }
+        let return_type = match &signature.return_type {
+            Some(typ) => match transpile(typ) {
+                Some(t) => t,
+                None => return Error(Box::new(CouldNotTranspileType {})),
+            },
+            None => void_type(),
         };
+        let prototype = CPrototype {
+            name: self.procedure_symbol(signature),
+            parameters,
+            return_type,
+        };
+        Ok(prototype, patch)
+    }
 
-        Ok(function_call, patch)
+    /// `debug:print` writes its arguments through a format string derived from
+    /// their types, so text containing `%` is printed verbatim.
+    fn transpile_debug_print(
+        &self,
+        input: &ProcedureCall,
+        arguments: Vec<CExpression>,
+        ctx: &Context,
+        patch: CSrcPatch,
+    ) -> CTranspile<CFunctionCall> {
+        let mut format = String::new();
+        let mut printed = vec![];
+        for (argument, value) in input.arguments.iter().zip(arguments) {
+            let schema = match type_of_expression(argument, ctx) {
+                Some(ExpressionType::Dim(schema) | ExpressionType::Ref(schema)) => schema,
+                Some(ExpressionType::Addr(_)) => {
+                    format.push_str("%p");
+                    printed.push(value);
+                    continue;
+                }
+                _ => return Error(Box::new(VariableTypeAmbiguous {})),
+            };
+            let (specifier, promoted) = match schema.family {
+                TypeFamily::Int => ("%lld", Some("long long")),
+                TypeFamily::UInt => ("%llu", Some("unsigned long long")),
+                TypeFamily::Float => ("%g", None),
+                TypeFamily::None => match schema.identifier {
+                    Char => ("%c", None),
+                    CharSeq => ("%s", None),
+                    StringList => {
+                        return Error(Box::new(TypeNotPrintable {
+                            received_type: schema,
+                        }));
+                    }
+                    _ => ("%d", None),
+                },
+            };
+            format.push_str(specifier);
+            printed.push(match promoted {
+                Some(name) => CCast {
+                    typ: CType {
+                        name: name.to_string(),
+                        is_pointer: false,
+                    },
+                    expression: Box::new(value),
+                }
+                .to_expression(),
+                None => value,
+            });
+        }
+        let mut arguments = vec![CLiteral::String(format).to_expression()];
+        arguments.extend(printed);
+        Ok(
+            CFunctionCall {
+                function_name: "printf".to_string(),
+                arguments,
+            },
+            patch,
+        )
     }
 
     pub fn transpile_type(&self, typ: &Type) -> CTranspile<Option<CType>> {
-        fn patch(stdint: bool) -> CSrcPatch {
-            let mut p = CSrcPatch { includes: vec![] };
-            if stdint {
+        fn patch(include: Option<&str>) -> CSrcPatch {
+            let mut p = CSrcPatch {
+                includes: vec![],
+                prototypes: vec![],
+                helpers: vec![],
+            };
+            if let Some(file) = include {
                 p.includes.push(CInclude {
-                    file: "stdint.h".to_string(),
+                    file: file.to_string(),
+                    local: false,
                 });
             }
             p
-        };
+        }
         fn map_type(type_name: &SchemaIdentifier, pointer: bool) -> CTranspile<Option<CType>> {
-            match type_name {
-                Int32 => Ok(
-                    Some(CType {
-                        name: "int32_t".to_string(),
-                        is_pointer: pointer,
-                    }),
-                    patch(true),
-                ),
-                Int64 => Ok(
-                    Some(CType {
-                        name: "int64_t".to_string(),
-                        is_pointer: pointer,
-                    }),
-                    patch(true),
-                ),
-                Float32 => Ok(
-                    Some(CType {
-                        name: "float".to_string(),
-                        is_pointer: pointer,
-                    }),
-                    patch(false),
-                ),
-                Float64 => Ok(
-                    Some(CType {
-                        name: "double".to_string(),
-                        is_pointer: pointer,
-                    }),
-                    patch(false),
-                ),
-                Bool => Ok(
-                    Some(CType {
-                        name: "int".to_string(),
-                        is_pointer: pointer,
-                    }),
-                    patch(false),
-                ),
-                Char => Ok(
-                    Some(CType {
-                        name: "char".to_string(),
-                        is_pointer: pointer,
-                    }),
-                    patch(false),
-                ),
-                _ => Ok(None, CSrcPatch::default()),
-            }
+            let (name, include) = match type_name {
+                Int8 => ("int8_t", Some("stdint.h")),
+                Int16 => ("int16_t", Some("stdint.h")),
+                Int32 => ("int32_t", Some("stdint.h")),
+                Int64 => ("int64_t", Some("stdint.h")),
+                UInt8 => ("uint8_t", Some("stdint.h")),
+                UInt16 => ("uint16_t", Some("stdint.h")),
+                UInt32 => ("uint32_t", Some("stdint.h")),
+                UInt64 => ("uint64_t", Some("stdint.h")),
+                Size => ("size_t", Some("stddef.h")),
+                Float32 => ("float", None),
+                Float64 => ("double", None),
+                Bool => ("int", None),
+                Char => ("char", None),
+                CharSeq => ("char", None),
+                StringList => ("char *", None),
+                _ => return Ok(None, CSrcPatch::default()),
+            };
+            Ok(
+                Some(CType {
+                    name: name.to_string(),
+                    is_pointer: pointer || matches!(type_name, CharSeq | StringList),
+                }),
+                patch(include),
+            )
         }
 
         match typ {
@@ -114,19 +933,32 @@ impl CToolKit {
         let stdint = CSrcPatch {
             includes: vec![CInclude {
                 file: "stdint.h".to_string(),
+                local: false,
             }],
+            prototypes: vec![],
+            helpers: vec![],
         };
         let float = CSrcPatch {
             includes: vec![CInclude {
                 file: "float.h".to_string(),
+                local: false,
             }],
+            prototypes: vec![],
+            helpers: vec![],
         };
         match typ {
             Type::Addr(_) => Ok(zero_literal().to_expression(), CSrcPatch::default()),
             Type::Ref(_) => Ok(zero_literal().to_expression(), CSrcPatch::default()),
             Type::Dim(dimtype) => match dimtype.identifier {
-                SchemaIdentifier::Int32 => Ok(int32_min_variable(), stdint),
-                SchemaIdentifier::Int64 => Ok(int64_min_variable(), stdint),
+                SchemaIdentifier::Int8 => Ok(limit_variable("INT8_MIN"), stdint),
+                SchemaIdentifier::Int16 => Ok(limit_variable("INT16_MIN"), stdint),
+                SchemaIdentifier::Int32 => Ok(limit_variable("INT32_MIN"), stdint),
+                SchemaIdentifier::Int64 => Ok(limit_variable("INT64_MIN"), stdint),
+                SchemaIdentifier::UInt8 => Ok(limit_variable("UINT8_MAX"), stdint),
+                SchemaIdentifier::UInt16 => Ok(limit_variable("UINT16_MAX"), stdint),
+                SchemaIdentifier::UInt32 => Ok(limit_variable("UINT32_MAX"), stdint),
+                SchemaIdentifier::UInt64 => Ok(limit_variable("UINT64_MAX"), stdint),
+                SchemaIdentifier::Size => Ok(limit_variable("SIZE_MAX"), stdint),
                 SchemaIdentifier::Float64 => {
                     Ok(CExpression::Variable("-DBL_MAX".to_string()), float)
                 }
@@ -135,25 +967,341 @@ impl CToolKit {
                     CSrcPatch::default(),
                 ),
                 SchemaIdentifier::Char => Ok(
-                    CLiteral::Char("\\0".to_string()).to_expression(),
+                    CLiteral::Char("\0".to_string()).to_expression(),
                     CSrcPatch::default(),
                 ),
+                SchemaIdentifier::CharSeq => {
+                    Ok(zero_literal().to_expression(), CSrcPatch::default())
+                }
                 _ => Error(Box::new(TypeNotNullable {
                     received_type: typ.clone(),
                 })),
             },
         }
     }
+
+    /// Converts `value`, already of type `from`, to `to`. Checked and saturating
+    /// casts only test the bounds the source type can actually exceed.
+    pub fn transpile_cast(
+        &self,
+        value: CExpression,
+        from: &SchemaType,
+        mode: &CastMode,
+        to: &SchemaType,
+    ) -> CTranspile<CExpression> {
+        let mut patch = CSrcPatch::default();
+        let typ = match self.transpile_type(&Type::Dim(schema_type(to.identifier.clone()))) {
+            Ok(Some(t), in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                t
+            }
+            Ok(None, _) => return Error(Box::new(CouldNotTranspileType {})),
+            Error(err) => return Error(err),
+        };
+        let cast = CCast {
+            typ,
+            expression: Box::new(value.clone()),
+        }
+        .to_expression();
+
+        let overflow = match mode {
+            CastMode::Checked => from.identifier.checked_cast_overflow(&to.identifier),
+            _ => from.identifier.cast_overflow(&to.identifier),
+        };
+        let (min, max) = match cast_limits(overflow, &to.identifier) {
+            Some((min, max, include)) => {
+                merge_patch(
+                    &mut patch,
+                    &CSrcPatch {
+                        includes: vec![CInclude {
+                            file: include.to_string(),
+                            local: false,
+                        }],
+                        prototypes: vec![],
+                        helpers: vec![],
+                    },
+                );
+                (min.map(limit_variable), max.map(limit_variable))
+            }
+            None => (None, None),
+        };
+        // the largest value of a 32 or 64 bit integer rounds up to the next
+        // power of two as a float, a float equal to it must not pass
+        let bound = match (&from.family, to.identifier.integer_bound()) {
+            (TypeFamily::Float, Some(bound)) if max.is_some() => {
+                Some(CLiteral::Number(format!("{}.0", bound)).to_expression())
+            }
+            _ => None,
+        };
+
+        match mode {
+            CastMode::Wrapping => Ok(cast, patch),
+            CastMode::Checked => {
+                // the limit that is the null of the result does not fit
+                let mut checks = vec![];
+                if let Some(min) = min {
+                    let operator = if to.family == TypeFamily::Int {
+                        ">"
+                    } else {
+                        ">="
+                    };
+                    checks.push(binary(value.clone(), operator, min));
+                }
+                match (bound, max) {
+                    (_, Some(max)) if to.family == TypeFamily::UInt => {
+                        checks.push(binary(value.clone(), "<", max))
+                    }
+                    (Some(bound), _) => checks.push(binary(value.clone(), "<", bound)),
+                    (None, Some(max)) => checks.push(binary(value.clone(), "<=", max)),
+                    (None, None) => {}
+                }
+                let condition = match checks.into_iter().reduce(|l, r| binary(l, "&&", r)) {
+                    Some(condition) => condition,
+                    None => return Ok(cast, patch),
+                };
+                let mut nullable = schema_type(to.identifier.clone());
+                nullable.postfix = TypePostfix::Opt;
+                let null = match self.transpile_null(&Type::Dim(nullable)) {
+                    Ok(expr, in_patch) => {
+                        merge_patch(&mut patch, &in_patch);
+                        expr
+                    }
+                    Error(err) => return Error(err),
+                };
+                Ok(conditional(condition, cast, null), patch)
+            }
+            CastMode::Saturating => {
+                let mut result = cast;
+                match (bound, max) {
+                    (Some(bound), Some(max)) => {
+                        result = conditional(binary(value.clone(), ">=", bound), max, result);
+                    }
+                    (None, Some(max)) => {
+                        result = conditional(binary(value.clone(), ">", max.clone()), max, result);
+                    }
+                    _ => {}
+                }
+                if let Some(min) = min {
+                    result = conditional(binary(value.clone(), "<", min.clone()), min, result);
+                }
+                if from.family == TypeFamily::Float && to.family != TypeFamily::Float {
+                    result = conditional(
+                        binary(value.clone(), "!=", value),
+                        zero_literal().to_expression(),
+                        result,
+                    );
+                }
+                Ok(result, patch)
+            }
+        }
+    }
 }
 
-fn zero_literal() -> CLiteral {
-    return CLiteral::Number("0".to_string());
+/// Bounds of `to` that `overflow` says a value can fall outside of, along
+/// with the header defining them. Returns `None` when no bound needs checking.
+fn cast_limits(
+    overflow: Option<(bool, bool)>,
+    to: &SchemaIdentifier,
+) -> Option<(Option<&'static str>, Option<&'static str>, &'static str)> {
+    let (min_name, max_name, include) = match to {
+        Int8 => ("INT8_MIN", "INT8_MAX", "stdint.h"),
+        Int16 => ("INT16_MIN", "INT16_MAX", "stdint.h"),
+        Int32 => ("INT32_MIN", "INT32_MAX", "stdint.h"),
+        Int64 => ("INT64_MIN", "INT64_MAX", "stdint.h"),
+        UInt8 => ("0", "UINT8_MAX", "stdint.h"),
+        UInt16 => ("0", "UINT16_MAX", "stdint.h"),
+        UInt32 => ("0", "UINT32_MAX", "stdint.h"),
+        UInt64 => ("0", "UINT64_MAX", "stdint.h"),
+        Size => ("0", "SIZE_MAX", "stdint.h"),
+        Float32 => ("-FLT_MAX", "FLT_MAX", "float.h"),
+        Float64 => ("-DBL_MAX", "DBL_MAX", "float.h"),
+        _ => return None,
+    };
+    let (below, above) = overflow?;
+    Some((
+        if below { Some(min_name) } else { None },
+        if above { Some(max_name) } else { None },
+        include,
+    ))
+}
+
+/// `module` as the prefix of a C symbol. Module names come from file paths,
+/// so every byte that may not appear there, or a leading digit, is written as
+/// `_` and its two hex digits. As `_` is escaped too no two modules share a
+/// prefix: `a_b` becomes `a_5fb` and `a.b` becomes `a_2eb`.
+fn mangle_module(module: &str) -> String {
+    let mut mangled = String::new();
+    for (idx, byte) in module.bytes().enumerate() {
+        if byte.is_ascii_alphabetic() || byte.is_ascii_digit() && idx > 0 {
+            mangled.push(byte as char);
+        } else {
+            mangled.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    mangled
+}
+
+/// `int32_t palel_strings_count(char **list)`, the number of strings before
+/// the null pointer ending `list`.
+fn strings_count_helper() -> CFunction {
+    let count = || CExpression::Variable("count".to_string());
+    CFunction {
+        name: "palel_strings_count".to_string(),
+        parameters: vec![string_list_parameter()],
+        return_type: int32_type(),
+        block: CBlock {
+            statements: vec![
+                CVariableDeclaration {
+                    name: "count".to_string(),
+                    var_type: int32_type(),
+                    value: number(0),
+                }
+                .to_statement(),
+                CWhile {
+                    condition: binary(list_item(count()), "!=", number(0)),
+                    block: CBlock {
+                        statements: vec![
+                            CAssignment {
+                                name: "count".to_string(),
+                                value: binary(count(), "+", number(1)),
+                            }
+                            .to_statement(),
+                        ],
+                    },
+                }
+                .to_statement(),
+                CReturn {
+                    value: Some(count()),
+                }
+                .to_statement(),
+            ],
+        },
+    }
+}
+
+/// `char *palel_strings_get(char **list, int32_t index)`, the string at
+/// `index` or the null pointer when `list` ends before it.
+fn strings_get_helper() -> CFunction {
+    let index = || CExpression::Variable("index".to_string());
+    let at = || CExpression::Variable("at".to_string());
+    let null = || CReturn {
+        value: Some(number(0)),
+    };
+    CFunction {
+        name: "palel_strings_get".to_string(),
+        parameters: vec![
+            string_list_parameter(),
+            CParameter {
+                name: "index".to_string(),
+                typ: int32_type(),
+            },
+        ],
+        return_type: CType {
+            name: "char".to_string(),
+            is_pointer: true,
+        },
+        block: CBlock {
+            statements: vec![
+                CIf {
+                    condition: binary(index(), "<", number(0)),
+                    block: CBlock {
+                        statements: vec![null().to_statement()],
+                    },
+                }
+                .to_statement(),
+                CVariableDeclaration {
+                    name: "at".to_string(),
+                    var_type: int32_type(),
+                    value: number(0),
+                }
+                .to_statement(),
+                CWhile {
+                    condition: binary(at(), "<", index()),
+                    block: CBlock {
+                        statements: vec![
+                            CIf {
+                                condition: binary(list_item(at()), "==", number(0)),
+                                block: CBlock {
+                                    statements: vec![null().to_statement()],
+                                },
+                            }
+                            .to_statement(),
+                            CAssignment {
+                                name: "at".to_string(),
+                                value: binary(at(), "+", number(1)),
+                            }
+                            .to_statement(),
+                        ],
+                    },
+                }
+                .to_statement(),
+                CReturn {
+                    value: Some(list_item(index())),
+                }
+                .to_statement(),
+            ],
+        },
+    }
+}
+
+fn string_list_parameter() -> CParameter {
+    CParameter {
+        name: "list".to_string(),
+        typ: CType {
+            name: "char *".to_string(),
+            is_pointer: true,
+        },
+    }
+}
+
+fn list_item(index: CExpression) -> CExpression {
+    CIndex {
+        expression: Box::new(CExpression::Variable("list".to_string())),
+        index: Box::new(index),
+    }
+    .to_expression()
+}
+
+fn int32_type() -> CType {
+    CType {
+        name: "int32_t".to_string(),
+        is_pointer: false,
+    }
 }
 
-fn int32_min_variable() -> CExpression {
-    return CExpression::Variable("INT32_MIN".to_string());
+fn binary(left: CExpression, operator: &str, right: CExpression) -> CExpression {
+    CBinary {
+        left: Box::new(left),
+        operator: operator.to_string(),
+        right: Box::new(right),
+    }
+    .to_expression()
+}
+
+fn conditional(condition: CExpression, then: CExpression, otherwise: CExpression) -> CExpression {
+    CConditional {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        otherwise: Box::new(otherwise),
+    }
+    .to_expression()
+}
+
+fn int64_type() -> CType {
+    CType {
+        name: "int64_t".to_string(),
+        is_pointer: false,
+    }
+}
+
+fn number(value: usize) -> CExpression {
+    CLiteral::Number(value.to_string()).to_expression()
+}
+
+fn zero_literal() -> CLiteral {
+    return CLiteral::Number("0".to_string());
 }
 
-fn int64_min_variable() -> CExpression {
-    return CExpression::Variable("INT64_MIN".to_string());
+fn limit_variable(name: &str) -> CExpression {
+    CExpression::Variable(name.to_string())
 }
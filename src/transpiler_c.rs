@@ -1,13 +1,20 @@
 use crate::c::*;
 use crate::compilation_error::{
-    CompilationError, CouldNotTranspileType, IncompatibleTypes, VariableTypeAmbiguous,
+    CompilationError, CouldNotTranspileType, DuplicateBench, DuplicateProgram, DuplicateTest,
+    IncompatibleTypes, InvalidProgramParameter, InvalidReturn, MissingReturn, UnknownVariable,
+    ValueSite, VariableTypeAmbiguous,
 };
 use crate::core::Of;
+use crate::formatter::assertion_failure;
+use crate::interface_resolution::{check_interfaces, declare_interfaces};
+use crate::module_resolution::{check_modules, module_context};
 use crate::palel::*;
 use crate::toolkit_c::CToolKit;
 use crate::transpiler_c_patch::{merge_patch, patch_src};
+use crate::transpiler_context::{Context, Returns};
 use crate::type_checking::{
-    determine_variable_type, is_valid_expression_assignment, type_of_expression,
+    check_declaration, check_value, determine_variable_type, is_valid_expression_assignment,
+    resolve_call, resolve_expression_type, type_of_expression,
 };
 
 pub enum CTranspile<T> {
@@ -17,55 +24,416 @@ pub enum CTranspile<T> {
 
 use CTranspile::*;
 
-pub fn transpile(input: &Src, toolkit: &CToolKit) -> Of<CSrc> {
-    let mut src = CSrc {
+/// The header declaring the shared procedures, included by every program.
+pub const SHARED_HEADER: &str = "palel.shared.h";
+
+pub fn transpile(input: &Src, toolkit: &CToolKit) -> Of<CProject> {
+    let mut shared = CSrc {
+        includes: vec![],
+        prototypes: vec![],
+        helpers: vec![],
+        functions: vec![],
+    };
+    let mut library = CSrc {
         includes: vec![],
+        prototypes: vec![],
+        helpers: vec![],
         functions: vec![],
     };
-    if let Some(program) = input.programs.get(0) {
-        match transpile_program(program, toolkit) {
+    if let Some(err) = check_modules(input) {
+        return Of::Error(err);
+    }
+    if let Some(err) = check_interfaces(input, &toolkit.builtin_procedures()) {
+        return Of::Error(err);
+    }
+    for (idx, program) in input.programs.iter().enumerate() {
+        if input.programs[..idx].iter().any(|p| p.name == program.name) {
+            return Of::Error(Box::new(DuplicateProgram {
+                name: program.name.clone(),
+            }));
+        }
+    }
+    for procedure in &input.procedures {
+        let ctx = context(input, &procedure.module, toolkit);
+        match transpile_procedure(procedure, &ctx, toolkit) {
             Error(err) => return Of::Error(err),
-            Ok(program, patch) => {
-                src.functions.push(program);
+            Ok(function, patch) => {
+                if procedure.export {
+                    match transpile_export(procedure, &function, toolkit) {
+                        Error(err) => return Of::Error(err),
+                        Ok(prototype, patch) => {
+                            library.prototypes.push(prototype);
+                            patch_src(&mut library, &patch);
+                        }
+                    }
+                }
+                shared.functions.push(function);
+                patch_src(&mut shared, &patch);
+            }
+        }
+    }
+    let mut programs = vec![];
+    for program in &input.programs {
+        let mut src = CSrc {
+            includes: vec![],
+            prototypes: vec![],
+            helpers: vec![],
+            functions: vec![],
+        };
+        if !shared.functions.is_empty() {
+            src.includes.push(CInclude {
+                file: SHARED_HEADER.to_string(),
+                local: true,
+            });
+        }
+        let ctx = context(input, &program.module, toolkit);
+        match transpile_program(program, &ctx, toolkit) {
+            Error(err) => return Of::Error(err),
+            Ok(function, patch) => {
+                src.functions.push(function);
                 patch_src(&mut src, &patch);
             }
         }
+        programs.push(CProgram {
+            name: program.name.clone(),
+            src,
+        });
+    }
+    let tests = match transpile_tests(input, !shared.functions.is_empty(), toolkit) {
+        Of::Ok(tests) => tests,
+        Of::Error(err) => return Of::Error(err),
+    };
+    let benches = match transpile_benches(input, !shared.functions.is_empty(), toolkit) {
+        Of::Ok(benches) => benches,
+        Of::Error(err) => return Of::Error(err),
+    };
+    Of::Ok(CProject {
+        shared,
+        library,
+        programs,
+        tests,
+        benches,
+    })
+}
+
+/// The test harness, a function for every test and a `main` running the test
+/// whose position in `input.tests` is its argument.
+fn transpile_tests(input: &Src, has_shared: bool, toolkit: &CToolKit) -> Of<Option<CSrc>> {
+    if input.tests.is_empty() {
+        return Of::Ok(None);
+    }
+    let mut src = harness_src(has_shared);
+    let mut symbols = vec![];
+    for (idx, test) in input.tests.iter().enumerate() {
+        let duplicate = input.tests[..idx]
+            .iter()
+            .any(|t| t.module == test.module && t.name == test.name);
+        if duplicate {
+            return Of::Error(Box::new(DuplicateTest {
+                module: test.module.clone(),
+                name: test.name.clone(),
+            }));
+        }
+        let symbol = toolkit.test_symbol(idx);
+        let function =
+            transpile_harness_function(input, &test.module, &test.do_block, &symbol, toolkit);
+        if let Some(err) = add_function(&mut src, function) {
+            return Of::Error(err);
+        }
+        symbols.push(symbol);
     }
-    Of::Ok(src)
+    if let Some(err) = add_function(&mut src, toolkit.transpile_test_main(&symbols)) {
+        return Of::Error(err);
+    }
+    Of::Ok(Some(src))
 }
 
-fn transpile_program(input: &Program, toolkit: &CToolKit) -> CTranspile<CFunction> {
+/// The bench harness, a function for every bench, the clock timing them and
+/// a `main` running the bench whose position in `input.benches` is its first
+/// argument.
+fn transpile_benches(input: &Src, has_shared: bool, toolkit: &CToolKit) -> Of<Option<CSrc>> {
+    if input.benches.is_empty() {
+        return Of::Ok(None);
+    }
+    let mut src = harness_src(has_shared);
+    let mut symbols = vec![];
+    for (idx, bench) in input.benches.iter().enumerate() {
+        let duplicate = input.benches[..idx]
+            .iter()
+            .any(|b| b.module == bench.module && b.name == bench.name);
+        if duplicate {
+            return Of::Error(Box::new(DuplicateBench {
+                module: bench.module.clone(),
+                name: bench.name.clone(),
+            }));
+        }
+        let symbol = toolkit.bench_symbol(idx);
+        let function =
+            transpile_harness_function(input, &bench.module, &bench.do_block, &symbol, toolkit);
+        if let Some(err) = add_function(&mut src, function) {
+            return Of::Error(err);
+        }
+        symbols.push(symbol);
+    }
+    if let Some(err) = add_function(&mut src, toolkit.transpile_bench_clock()) {
+        return Of::Error(err);
+    }
+    if let Some(err) = add_function(&mut src, toolkit.transpile_bench_main(&symbols)) {
+        return Of::Error(err);
+    }
+    Of::Ok(Some(src))
+}
+
+fn harness_src(has_shared: bool) -> CSrc {
+    let mut src = CSrc {
+        includes: vec![],
+        prototypes: vec![],
+        helpers: vec![],
+        functions: vec![],
+    };
+    if has_shared {
+        src.includes.push(CInclude {
+            file: SHARED_HEADER.to_string(),
+            local: true,
+        });
+    }
+    src
+}
+
+/// A function `symbol` without parameters or result running `do_block` of
+/// `module`.
+fn transpile_harness_function(
+    input: &Src,
+    module: &str,
+    do_block: &DoBlock,
+    symbol: &str,
+    toolkit: &CToolKit,
+) -> CTranspile<CFunction> {
+    let mut ctx = context(input, module, toolkit);
+    ctx.returns = Returns::Nothing;
+    match transpile_block(do_block, module, &mut ctx, toolkit) {
+        Ok(block, patch) => Ok(
+            CFunction {
+                name: symbol.to_string(),
+                parameters: vec![],
+                return_type: void_type(),
+                block,
+            },
+            patch,
+        ),
+        Error(err) => Error(err),
+    }
+}
+
+fn add_function(
+    src: &mut CSrc,
+    function: CTranspile<CFunction>,
+) -> Option<Box<dyn CompilationError>> {
+    match function {
+        Ok(function, patch) => {
+            patch_src(src, &patch);
+            src.functions.push(function);
+            None
+        }
+        Error(err) => Some(err),
+    }
+}
+
+/// Everything callable from `module`: its own procedures, those of the
+/// modules it imports and the procedures of every interface.
+pub fn context(input: &Src, module: &str, toolkit: &CToolKit) -> Context {
+    let mut ctx = module_context(input, module);
+    declare_interfaces(input, &mut ctx);
+    for signature in toolkit.builtin_procedures() {
+        ctx.declare_procedure(&signature.name(), signature);
+    }
+    ctx
+}
+
+fn transpile_program(
+    input: &Program,
+    globals: &Context,
+    toolkit: &CToolKit,
+) -> CTranspile<CFunction> {
     let mut patch = CSrcPatch::default();
-    let mut block = match transpile_block(&input.do_block, toolkit) {
+    let mut ctx = globals.clone();
+    ctx.returns = Returns::ExitStatus;
+
+    let mut optional = false;
+    for (idx, parameter) in input.parameters.iter().enumerate() {
+        let schema = &parameter.schema_type;
+        let rest = schema.identifier == SchemaIdentifier::StringList
+            && schema.postfix == TypePostfix::None
+            && idx + 1 == input.parameters.len();
+        optional = optional || schema.postfix == TypePostfix::Opt;
+        if !rest
+            && (schema.identifier != SchemaIdentifier::CharSeq
+                || schema.postfix == TypePostfix::Err
+                || (optional && schema.postfix != TypePostfix::Opt))
+        {
+            return Error(Box::new(InvalidProgramParameter {
+                name: parameter.identifier.clone(),
+            }));
+        }
+        if let Some(err) = check_declaration(&parameter.identifier, &ctx) {
+            return Error(err);
+        }
+        ctx.declare(&parameter.identifier, Type::Dim(schema.clone()));
+    }
+    let (parameters, mut statements) = if input.parameters.is_empty() {
+        (vec![], vec![])
+    } else {
+        match toolkit.transpile_program_arguments(&input.parameters) {
+            Error(err) => return Error(err),
+            Ok(arguments, in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                arguments
+            }
+        }
+    };
+
+    let block = match transpile_block(&input.do_block, &input.module, &mut ctx, toolkit) {
         Error(err) => return Error(err),
         Ok(block, in_patch) => {
             merge_patch(&mut patch, &in_patch);
             block
         }
     };
-    let ret_stmt = Return {
-        value: Some(Literal::Number("0".to_string()).to_expression()),
+    statements.extend(block.statements);
+    if !matches!(input.do_block.statements.last(), Some(Statement::Return(_))) {
+        let ret_stmt = Return {
+            value: Some(Literal::Number("0".to_string()).to_expression()),
+        };
+        match transpile_return(&ret_stmt, &ctx, toolkit) {
+            Error(err) => return Error(err),
+            Ok(ret, in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                statements.push(ret.to_statement());
+            }
+        };
+    }
+    let function = CFunction {
+        name: "main".to_string(),
+        parameters,
+        return_type: int_type(),
+        block: CBlock { statements },
     };
-    match transpile_return(&ret_stmt, toolkit) {
+    Ok(function, patch)
+}
+
+fn transpile_procedure(
+    input: &Procedure,
+    globals: &Context,
+    toolkit: &CToolKit,
+) -> CTranspile<CFunction> {
+    let mut patch = CSrcPatch::default();
+    let mut ctx = globals.clone();
+    let signature = input.signature();
+
+    let mut parameters: Vec<CParameter> = vec![];
+    for (parameter, typ) in input.parameters.iter().zip(&signature.parameters) {
+        if let Some(err) = check_declaration(&parameter.identifier, &ctx) {
+            return Error(err);
+        }
+        parameters.push(CParameter {
+            name: parameter.identifier.clone(),
+            typ: match transpile_type(typ, &mut patch, toolkit) {
+                Some(t) => t,
+                None => return Error(Box::new(CouldNotTranspileType {})),
+            },
+        });
+        ctx.declare(&parameter.identifier, typ.clone());
+    }
+
+    let return_type = match &signature.return_type {
+        Some(typ) => {
+            let last = input.do_block.statements.last();
+            if !matches!(last, Some(Statement::Return(_))) {
+                return Error(Box::new(MissingReturn {
+                    procedure: input.identifier.clone(),
+                }));
+            }
+            ctx.returns = Returns::Value(typ.clone());
+            match transpile_type(typ, &mut patch, toolkit) {
+                Some(t) => t,
+                None => return Error(Box::new(CouldNotTranspileType {})),
+            }
+        }
+        None => {
+            ctx.returns = Returns::Nothing;
+            void_type()
+        }
+    };
+
+    let block = match transpile_block(&input.do_block, &input.module, &mut ctx, toolkit) {
         Error(err) => return Error(err),
-        Ok(ret, in_patch) => {
+        Ok(block, in_patch) => {
             merge_patch(&mut patch, &in_patch);
-            block.statements.push(ret.to_statement());
+            block
         }
     };
     let function = CFunction {
-        name: "main".to_string(),
-        return_type: int_type(),
-        block: block,
+        name: toolkit.procedure_symbol(&signature),
+        parameters,
+        return_type,
+        block,
     };
     Ok(function, patch)
 }
 
-fn transpile_block(input: &DoBlock, toolkit: &CToolKit) -> CTranspile<CBlock> {
+/// The prototype of an exported procedure and the includes a C caller needs
+/// for its parameter and return types.
+fn transpile_export(
+    input: &Procedure,
+    function: &CFunction,
+    toolkit: &CToolKit,
+) -> CTranspile<CPrototype> {
+    let mut patch = CSrcPatch::default();
+    let signature = input.signature();
+    for typ in signature
+        .parameters
+        .iter()
+        .chain(signature.return_type.iter())
+    {
+        if transpile_type(typ, &mut patch, toolkit).is_none() {
+            return Error(Box::new(CouldNotTranspileType {}));
+        }
+    }
+    Ok(function.prototype(), patch)
+}
+
+fn transpile_type(typ: &Type, patch: &mut CSrcPatch, toolkit: &CToolKit) -> Option<CType> {
+    match toolkit.transpile_type(typ) {
+        Ok(Some(t), in_patch) => {
+            merge_patch(patch, &in_patch);
+            Some(t)
+        }
+        _ => None,
+    }
+}
+
+/// Transpiles the statements of a block of `module`, each after a `#line`
+/// of its source line when the block was parsed.
+fn transpile_block(
+    input: &DoBlock,
+    module: &str,
+    ctx: &mut Context,
+    toolkit: &CToolKit,
+) -> CTranspile<CBlock> {
     let mut statements: Vec<CStatement> = vec![];
     let mut patch = CSrcPatch::default();
-    for statement in &input.statements {
-        match transpile_statement(statement, toolkit) {
+    let file = file_from_module_name(module);
+    for (idx, statement) in input.statements.iter().enumerate() {
+        if let Some(line) = input.lines.get(idx) {
+            statements.push(
+                CLine {
+                    line: *line,
+                    file: file.clone(),
+                }
+                .to_statement(),
+            );
+        }
+        match transpile_statement(statement, ctx, toolkit) {
             Error(err) => return Error(err),
             Ok(statement, in_patch) => {
                 merge_patch(&mut patch, &in_patch);
@@ -79,55 +447,95 @@ fn transpile_block(input: &DoBlock, toolkit: &CToolKit) -> CTranspile<CBlock> {
     return Ok(block, patch);
 }
 
-fn transpile_statement(input: &Statement, toolkit: &CToolKit) -> CTranspile<CStatement> {
+pub fn transpile_statement(
+    input: &Statement,
+    ctx: &mut Context,
+    toolkit: &CToolKit,
+) -> CTranspile<CStatement> {
     match input {
         Statement::ProcedureCall(procedure_call) => {
-            match transpile_procedure_call(procedure_call, toolkit) {
+            match transpile_procedure_call(procedure_call, ctx, toolkit) {
                 Error(err) => Error(err),
                 Ok(function_call, in_patch) => Ok(function_call.to_statement(), in_patch),
             }
         }
-        Statement::Return(ret) => match transpile_return(ret, toolkit) {
+        Statement::Return(ret) => match transpile_return(ret, ctx, toolkit) {
             Error(err) => Error(err),
             Ok(ret, patch) => Ok(ret.to_statement(), patch),
         },
         Statement::Variable(variable_declaration) => {
-            match transpile_variable_declaration(variable_declaration, toolkit) {
+            match transpile_variable_declaration(variable_declaration, ctx, toolkit) {
                 Error(err) => Error(err),
                 Ok(var, patch) => Ok(var.to_statement(), patch),
             }
         }
+        Statement::Assert(assert) => transpile_assert(assert, ctx, toolkit),
+    }
+}
+
+fn transpile_assert(input: &Assert, ctx: &Context, toolkit: &CToolKit) -> CTranspile<CStatement> {
+    let condition_type = Type::Dim(schema_type(SchemaIdentifier::Bool));
+    let actual = match resolve_expression_type(&input.condition, ctx) {
+        Of::Ok(t) => t,
+        Of::Error(err) => return Error(err),
+    };
+    if !is_valid_expression_assignment(&condition_type, &actual) {
+        return Error(Box::new(IncompatibleTypes {
+            expected: condition_type,
+            actual,
+            site: ValueSite::Condition,
+        }));
+    }
+    let mut patch = CSrcPatch::default();
+    let condition = match transpile_expression(&input.condition, &condition_type, ctx, toolkit) {
+        Ok(expr, in_patch) => {
+            merge_patch(&mut patch, &in_patch);
+            expr
+        }
+        Error(err) => return Error(err),
+    };
+    match toolkit.transpile_assert(condition, &assertion_failure(input)) {
+        Ok(statement, in_patch) => {
+            merge_patch(&mut patch, &in_patch);
+            Ok(statement, patch)
+        }
+        Error(err) => Error(err),
     }
 }
 
 fn transpile_variable_declaration(
     input: &VariableDeclaration,
+    ctx: &mut Context,
     toolkit: &CToolKit,
 ) -> CTranspile<CVariableDeclaration> {
+    if let Some(err) = check_declaration(&input.identifier, ctx) {
+        return Error(err);
+    }
     let variable_type: Type = match determine_variable_type(
         input.memory.clone(),
         input.schema_type.clone(),
         &input.expression,
+        ctx,
     ) {
         Some(t) => t.clone(),
-        None => return Error(Box::new(VariableTypeAmbiguous {})),
-    };
-
-    let expression_type = match type_of_expression(&input.expression) {
-        Some(t) => t,
-        None => return Error(Box::new(VariableTypeAmbiguous {})),
+        None => match resolve_expression_type(&input.expression, ctx) {
+            Of::Error(err) => return Error(err),
+            Of::Ok(_) => return Error(Box::new(VariableTypeAmbiguous {})),
+        },
     };
 
-    if !is_valid_expression_assignment(&variable_type, &expression_type) {
-        return Error(Box::new(IncompatibleTypes {
-            expected: variable_type,
-            actual: expression_type,
-        }));
+    if let Some(err) = check_value(
+        &variable_type,
+        &input.expression,
+        ValueSite::Declaration(input.type_span),
+        ctx,
+    ) {
+        return Error(err);
     }
 
     let mut patch = CSrcPatch::default();
 
-    let expression = match transpile_expression(&input.expression, &variable_type, toolkit) {
+    let expression = match transpile_expression(&input.expression, &variable_type, ctx, toolkit) {
         Ok(expr, in_patch) => {
             merge_patch(&mut patch, &in_patch);
             expr
@@ -153,42 +561,97 @@ fn transpile_variable_declaration(
         },
         value: expression,
     };
+    ctx.declare(&input.identifier, variable_type);
     Ok(var, patch)
 }
 
-fn transpile_return(input: &Return, toolkit: &CToolKit) -> CTranspile<CReturn> {
-    match &input.value {
-        Some(value) => match transpile_expression_unknown_type(&value, toolkit) {
+fn transpile_return(input: &Return, ctx: &Context, toolkit: &CToolKit) -> CTranspile<CReturn> {
+    let expected = match &ctx.returns {
+        Returns::Unchecked => None,
+        Returns::Nothing => {
+            if input.value.is_some() {
+                return Error(Box::new(InvalidReturn { expected: None }));
+            }
+            None
+        }
+        Returns::Value(_) | Returns::ExitStatus => {
+            let (typ, site) = match &ctx.returns {
+                Returns::Value(typ) => (typ.clone(), ValueSite::Return),
+                _ => (
+                    Type::Dim(schema_type(SchemaIdentifier::Int32)),
+                    ValueSite::ExitStatus,
+                ),
+            };
+            let value = match &input.value {
+                Some(value) => value,
+                None => {
+                    return Error(Box::new(InvalidReturn {
+                        expected: Some(typ),
+                    }));
+                }
+            };
+            if let Some(err) = check_value(&typ, value, site, ctx) {
+                return Error(err);
+            }
+            Some(typ)
+        }
+    };
+    match (&input.value, expected) {
+        (Some(value), Some(typ)) => match transpile_expression(value, &typ, ctx, toolkit) {
+            Ok(expr, in_patch) => Ok(CReturn { value: Some(expr) }, in_patch),
+            Error(e) => Error(e),
+        },
+        (Some(value), None) => match transpile_expression_unknown_type(value, ctx, toolkit) {
             Ok(expr, in_patch) => Ok(CReturn { value: Some(expr) }, in_patch),
             Error(e) => {
                 return Error(e);
             }
         },
-        None => Ok(CReturn { value: None }, CSrcPatch::default()),
+        (None, _) => Ok(CReturn { value: None }, CSrcPatch::default()),
     }
 }
 
 fn transpile_procedure_call(
     input: &ProcedureCall,
+    ctx: &Context,
     toolkit: &CToolKit,
 ) -> CTranspile<CFunctionCall> {
-    if !input.interface.is_empty() {
-        return toolkit.transpile_interface_call(input);
-    }
+    let signature = match resolve_call(input, ctx) {
+        Of::Ok(signature) => signature,
+        Of::Error(err) => return Error(err),
+    };
 
     let mut patch = CSrcPatch::default();
-    let expressions = match transpile_expressions(&input.arguments, toolkit) {
-        Ok(exprs, in_patch) => {
-            merge_patch(&mut patch, &in_patch);
-            exprs
-        }
-        Error(e) => {
-            return Error(e);
-        }
-    };
+    match &signature.implementation {
+        Implementation::Builtin => return toolkit.transpile_builtin_call(input, ctx),
+        Implementation::C(_) => match toolkit.transpile_prototype(&signature) {
+            Ok(prototype, in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                patch.prototypes.push(prototype);
+            }
+            Error(e) => return Error(e),
+        },
+        Implementation::Header(header) => patch.includes.push(CInclude {
+            file: header.clone(),
+            local: false,
+        }),
+        Implementation::Palel => {}
+    }
+    let mut expressions: Vec<CExpression> = vec![];
+    for (argument, typ) in input.arguments.iter().zip(&signature.parameters) {
+        match transpile_expression(argument, typ, ctx, toolkit) {
+            Ok(expr, in_patch) => {
+                merge_patch(&mut patch, &in_patch);
+                expressions.push(expr);
+            }
+            Error(e) => {
+                return Error(e);
+            }
+        };
+    }
 
     let function_call = CFunctionCall {
-        function_name: input.identifier.clone(),
+        function_name: toolkit.procedure_symbol(&signature),
         arguments: expressions,
     };
 
@@ -197,12 +660,13 @@ fn transpile_procedure_call(
 
 pub fn transpile_expressions(
     input: &Vec<Expression>,
+    ctx: &Context,
     toolkit: &CToolKit,
 ) -> CTranspile<Vec<CExpression>> {
     let mut patch = CSrcPatch::default();
     let mut expressions: Vec<CExpression> = vec![];
     for argument in input {
-        let expr = match transpile_expression_unknown_type(argument, toolkit) {
+        let expr = match transpile_expression_unknown_type(argument, ctx, toolkit) {
             Ok(expr, in_patch) => {
                 merge_patch(&mut patch, &in_patch);
                 expr
@@ -216,28 +680,60 @@ pub fn transpile_expressions(
     Ok(expressions, patch)
 }
 
-fn transpile_expression_unknown_type(
+pub fn transpile_expression_unknown_type(
     input: &Expression,
+    ctx: &Context,
     toolkit: &CToolKit,
 ) -> CTranspile<CExpression> {
-    let typ = match type_of_expression(input).and_then(ExpressionType::to_type) {
-        Some(t) => t,
-        None => {
-            return Error(Box::new(VariableTypeAmbiguous {}));
-        }
+    let typ = match resolve_expression_type(input, ctx) {
+        Of::Ok(t) => match t.to_type() {
+            Some(t) => t,
+            None => return Error(Box::new(VariableTypeAmbiguous {})),
+        },
+        Of::Error(err) => return Error(err),
     };
-    match input {
-        Expression::Literal(literal) => transpile_literal(&literal, &typ, toolkit),
-    }
+    transpile_expression(input, &typ, ctx, toolkit)
 }
 
-fn transpile_expression(
+pub fn transpile_expression(
     input: &Expression,
     typ: &Type,
+    ctx: &Context,
     toolkit: &CToolKit,
 ) -> CTranspile<CExpression> {
     match input {
         Expression::Literal(literal) => transpile_literal(&literal, typ, toolkit),
+        Expression::Variable(name) => match ctx.lookup(name) {
+            Some(_) => Ok(CExpression::Variable(name.clone()), CSrcPatch::default()),
+            None => Error(Box::new(UnknownVariable { name: name.clone() })),
+        },
+        Expression::Cast(cast) => transpile_cast(cast, ctx, toolkit),
+        Expression::Call(call) => match transpile_procedure_call(call, ctx, toolkit) {
+            Ok(function_call, patch) => Ok(function_call.to_expression(), patch),
+            Error(err) => Error(err),
+        },
+    }
+}
+
+fn transpile_cast(input: &Cast, ctx: &Context, toolkit: &CToolKit) -> CTranspile<CExpression> {
+    let from = match type_of_expression(&input.expression, ctx) {
+        Some(ExpressionType::Dim(schema) | ExpressionType::Ref(schema)) => schema,
+        _ => return Error(Box::new(VariableTypeAmbiguous {})),
+    };
+    let mut patch = CSrcPatch::default();
+    let value = match transpile_expression_unknown_type(&input.expression, ctx, toolkit) {
+        Ok(expr, in_patch) => {
+            merge_patch(&mut patch, &in_patch);
+            expr
+        }
+        Error(err) => return Error(err),
+    };
+    match toolkit.transpile_cast(value, &from, &input.mode, &input.target) {
+        Ok(expr, in_patch) => {
+            merge_patch(&mut patch, &in_patch);
+            Ok(expr, patch)
+        }
+        Error(err) => Error(err),
     }
 }
 
@@ -249,16 +745,18 @@ fn transpile_literal(input: &Literal, typ: &Type, toolkit: &CToolKit) -> CTransp
             match typ {
                 Type::Dim(schema) | Type::Ref(schema) => {
                     if schema.identifier == SchemaIdentifier::Char {
-                        println!("is a Char");
                         Ok(CLiteral::Char(val).to_expression(), patch)
                     } else {
-                        println!("is a String");
                         Ok(CLiteral::String(val).to_expression(), patch)
                     }
                 }
                 _ => Ok(CLiteral::String(val).to_expression(), patch),
             }
         }
+        Literal::Char(value) => Ok(
+            CLiteral::Char(value.to_string()).to_expression(),
+            CSrcPatch::default(),
+        ),
         Literal::Number(num) => Ok(
             CLiteral::Number(num.clone()).to_expression(),
             CSrcPatch::default(),
@@ -285,11 +783,12 @@ fn false_literal() -> CLiteral {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{do_block, message, parameter, procedure, program};
     use pretty_assertions::assert_eq;
 
     const TOOLKIT: CToolKit = CToolKit {};
 
-    fn run(input: &Src) -> CSrc {
+    fn run_project(input: &Src) -> CProject {
         match transpile(&input, &TOOLKIT) {
             Of::Ok(value) => value,
             Of::Error(err) => {
@@ -298,32 +797,46 @@ mod tests {
         }
     }
 
+    fn run(input: &Src) -> CSrc {
+        run_project(input).programs.remove(0).src
+    }
+
     #[test]
     fn test_transpile_hello_world() {
         let src = Src {
             programs: vec![Program {
+                name: None,
+                doc: None,
+                module: "".to_string(),
+                parameters: vec![],
                 do_block: DoBlock {
                     statements: vec![
                         ProcedureCall {
                             interface: "debug".to_string(),
-                            identifier: "printf".to_string(),
+                            identifier: "print".to_string(),
                             arguments: vec![
                                 Literal::String("Hello World".to_string()).to_expression(),
                             ],
                         }
                         .to_statement(),
                     ],
+                    lines: vec![],
                 },
             }],
+            ..Src::default()
         };
 
         let actual = run(&src);
         let expected = CSrc {
             includes: vec![CInclude {
                 file: "stdio.h".to_string(),
+                local: false,
             }],
+            prototypes: vec![],
+            helpers: vec![],
             functions: vec![CFunction {
                 name: "main".to_string(),
+                parameters: vec![],
                 return_type: CType {
                     name: "int".to_string(),
                     is_pointer: false,
@@ -333,6 +846,7 @@ mod tests {
                         CFunctionCall {
                             function_name: "printf".to_string(),
                             arguments: vec![
+                                CLiteral::String("%s".to_string()).to_expression(),
                                 CLiteral::String("Hello World".to_string()).to_expression(),
                             ],
                         }
@@ -353,12 +867,17 @@ mod tests {
     fn test_transpile_variable_delcarations() {
         let src = Src {
             programs: vec![Program {
+                name: None,
+                doc: None,
+                module: "".to_string(),
+                parameters: vec![],
                 do_block: DoBlock {
                     statements: vec![
                         VariableDeclaration {
                             memory: MemoryModifier::Dim,
                             identifier: "a".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("1".to_string())),
                         }
                         .to_statement(),
@@ -366,6 +885,7 @@ mod tests {
                             memory: MemoryModifier::Ref,
                             identifier: "b".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("2".to_string())),
                         }
                         .to_statement(),
@@ -373,6 +893,7 @@ mod tests {
                             memory: MemoryModifier::Var,
                             identifier: "c".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("3".to_string())),
                         }
                         .to_statement(),
@@ -380,6 +901,7 @@ mod tests {
                             memory: MemoryModifier::Addr,
                             identifier: "d".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("4".to_string())),
                         }
                         .to_statement(),
@@ -392,6 +914,7 @@ mod tests {
                                 family: TypeFamily::Int,
                                 width: Some(32),
                             }),
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("-5".to_string())),
                         }
                         .to_statement(),
@@ -404,6 +927,7 @@ mod tests {
                                 family: TypeFamily::Float,
                                 width: Some(64),
                             }),
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("6.2".to_string())),
                         }
                         .to_statement(),
@@ -416,6 +940,7 @@ mod tests {
                                 family: TypeFamily::None,
                                 width: None,
                             }),
+                            type_span: None,
                             expression: Expression::Literal(Literal::Boolean(true)),
                         }
                         .to_statement(),
@@ -423,6 +948,7 @@ mod tests {
                             memory: MemoryModifier::Dim,
                             identifier: "h".to_string(),
                             schema_type: None,
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("3.14".to_string())),
                         }
                         .to_statement(),
@@ -435,6 +961,7 @@ mod tests {
                                 family: TypeFamily::Int,
                                 width: Some(64),
                             }),
+                            type_span: None,
                             expression: Expression::Literal(Literal::Number("0".to_string())),
                         }
                         .to_statement(),
@@ -447,6 +974,7 @@ mod tests {
                                 family: TypeFamily::None,
                                 width: None,
                             }),
+                            type_span: None,
                             expression: Expression::Literal(Literal::String("A".to_string())),
                         }
                         .to_statement(),
@@ -459,20 +987,27 @@ mod tests {
                                 family: TypeFamily::Int,
                                 width: Some(32),
                             }),
+                            type_span: None,
                             expression: Expression::Literal(Literal::Null),
                         }
                         .to_statement(),
                     ],
+                    lines: vec![],
                 },
             }],
+            ..Src::default()
         };
         let actual = run(&src);
         let expected = CSrc {
             includes: vec![CInclude {
                 file: "stdint.h".to_string(),
+                local: false,
             }],
+            prototypes: vec![],
+            helpers: vec![],
             functions: vec![CFunction {
                 name: "main".to_string(),
+                parameters: vec![],
                 return_type: CType {
                     name: "int".to_string(),
                     is_pointer: false,
@@ -589,4 +1124,982 @@ mod tests {
 
         assert_eq!(actual, expected)
     }
+    #[test]
+    fn test_transpile_fixed_width_integers() {
+        let src = Src {
+            programs: vec![Program {
+                name: None,
+                doc: None,
+                module: "".to_string(),
+                parameters: vec![],
+                do_block: DoBlock {
+                    statements: vec![
+                        VariableDeclaration {
+                            memory: MemoryModifier::Dim,
+                            identifier: "byte".to_string(),
+                            schema_type: Some(schema_type(SchemaIdentifier::UInt8)),
+                            type_span: None,
+                            expression: Expression::Literal(Literal::Number("255".to_string())),
+                        }
+                        .to_statement(),
+                        VariableDeclaration {
+                            memory: MemoryModifier::Dim,
+                            identifier: "len".to_string(),
+                            schema_type: Some(SchemaType {
+                                identifier: SchemaIdentifier::Size,
+                                postfix: TypePostfix::Opt,
+                                family: TypeFamily::UInt,
+                                width: None,
+                            }),
+                            type_span: None,
+                            expression: Expression::Literal(Literal::Null),
+                        }
+                        .to_statement(),
+                    ],
+                    lines: vec![],
+                },
+            }],
+            ..Src::default()
+        };
+        let actual = run(&src);
+        let expected = CSrc {
+            includes: vec![
+                CInclude {
+                    file: "stdint.h".to_string(),
+                    local: false,
+                },
+                CInclude {
+                    file: "stddef.h".to_string(),
+                    local: false,
+                },
+            ],
+            prototypes: vec![],
+            helpers: vec![],
+            functions: vec![CFunction {
+                name: "main".to_string(),
+                parameters: vec![],
+                return_type: CType {
+                    name: "int".to_string(),
+                    is_pointer: false,
+                },
+                block: CBlock {
+                    statements: vec![
+                        CVariableDeclaration {
+                            name: "byte".to_string(),
+                            var_type: CType {
+                                name: "uint8_t".to_string(),
+                                is_pointer: false,
+                            },
+                            value: CLiteral::Number("255".to_string()).to_expression(),
+                        }
+                        .to_statement(),
+                        CVariableDeclaration {
+                            name: "len".to_string(),
+                            var_type: CType {
+                                name: "size_t".to_string(),
+                                is_pointer: false,
+                            },
+                            value: CExpression::Variable("SIZE_MAX".to_string()),
+                        }
+                        .to_statement(),
+                        CReturn {
+                            value: Some(CLiteral::Number("0".to_string()).to_expression()),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }],
+        };
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn test_transpile_literal_out_of_range() {
+        let number = |value: &str| Literal::Number(value.to_string()).to_expression();
+        let declare = |typ: Option<SchemaType>, value: &str| {
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: "value".to_string(),
+                schema_type: typ,
+                type_span: None,
+                expression: number(value),
+            }
+            .to_statement()
+        };
+        let transpiled = |statement: Statement| {
+            let src = Src {
+                programs: vec![Program {
+                    name: None,
+                    doc: None,
+                    module: "".to_string(),
+                    parameters: vec![],
+                    do_block: DoBlock {
+                        statements: vec![statement],
+                        lines: vec![],
+                    },
+                }],
+                ..Src::default()
+            };
+            transpile(&src, &TOOLKIT)
+        };
+        let error = |statement: Statement| message(transpiled(statement).err());
+        let typed = |identifier: SchemaIdentifier| Some(schema_type(identifier));
+
+        assert_eq!(
+            error(declare(typed(SchemaIdentifier::UInt8), "-1")),
+            "integer literal -1 does not fit in UInt8, which holds 0 to 255"
+        );
+        assert_eq!(
+            error(declare(typed(SchemaIdentifier::Int16), "70000")),
+            "integer literal 70000 does not fit in Int16, which holds -32768 to 32767"
+        );
+        assert_eq!(
+            error(declare(typed(SchemaIdentifier::Int32), "300000000000")),
+            "integer literal 300000000000 does not fit in Int32, which holds -2147483648 to 2147483647"
+        );
+        assert_eq!(
+            error(declare(
+                typed(SchemaIdentifier::Int64),
+                "99999999999999999999"
+            )),
+            concat!(
+                "integer literal 99999999999999999999 does not fit in Int64, ",
+                "which holds -9223372036854775808 to 9223372036854775807"
+            )
+        );
+        assert_eq!(
+            error(declare(None, "99999999999999999999")),
+            error(declare(
+                typed(SchemaIdentifier::Int64),
+                "99999999999999999999"
+            ))
+        );
+        let mut nullable = schema_type(SchemaIdentifier::Int32);
+        nullable.postfix = TypePostfix::Opt;
+        assert_eq!(
+            error(declare(Some(nullable), "-2147483648")),
+            "integer literal -2147483648 does not fit in Int32?, which holds -2147483647 to 2147483647"
+        );
+        assert_eq!(
+            error(
+                ProcedureCall {
+                    interface: "process".to_string(),
+                    identifier: "exit".to_string(),
+                    arguments: vec![number("300000000000")],
+                }
+                .to_statement()
+            ),
+            "integer literal 300000000000 does not fit in Int32, which holds -2147483648 to 2147483647"
+        );
+        assert_eq!(
+            error(
+                Return {
+                    value: Some(number("300000000000")),
+                }
+                .to_statement()
+            ),
+            "integer literal 300000000000 does not fit in Int32, which holds -2147483648 to 2147483647"
+        );
+
+        let inferred = match transpiled(declare(None, "300000000000")) {
+            Of::Ok(project) => project.programs[0].src.functions[0].block.statements[0].clone(),
+            Of::Error(err) => panic!("{}", err.message()),
+        };
+        assert_eq!(
+            inferred,
+            CVariableDeclaration {
+                name: "value".to_string(),
+                var_type: CType {
+                    name: "int64_t".to_string(),
+                    is_pointer: false,
+                },
+                value: CLiteral::Number("300000000000".to_string()).to_expression(),
+            }
+            .to_statement()
+        );
+    }
+
+    #[test]
+    fn test_transpile_casts() {
+        let cast = |mode: CastMode, target: SchemaIdentifier| {
+            Cast {
+                expression: Box::new(Expression::Variable("wide".to_string())),
+                mode,
+                target: schema_type(target),
+            }
+            .to_expression()
+        };
+        let declare = |name: &str, expression: Expression| {
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: name.to_string(),
+                schema_type: None,
+                type_span: None,
+                expression,
+            }
+            .to_statement()
+        };
+        let src = Src {
+            programs: vec![Program {
+                name: None,
+                doc: None,
+                module: "".to_string(),
+                parameters: vec![],
+                do_block: DoBlock {
+                    statements: vec![
+                        VariableDeclaration {
+                            memory: MemoryModifier::Dim,
+                            identifier: "wide".to_string(),
+                            schema_type: Some(schema_type(SchemaIdentifier::Int64)),
+                            type_span: None,
+                            expression: Literal::Number("300".to_string()).to_expression(),
+                        }
+                        .to_statement(),
+                        declare("wrapped", cast(CastMode::Wrapping, SchemaIdentifier::UInt8)),
+                        declare("checked", cast(CastMode::Checked, SchemaIdentifier::UInt8)),
+                        declare(
+                            "clamped",
+                            cast(CastMode::Saturating, SchemaIdentifier::UInt8),
+                        ),
+                    ],
+                    lines: vec![],
+                },
+            }],
+            ..Src::default()
+        };
+        let actual = run(&src);
+        let value = || Box::new(CExpression::Variable("wide".to_string()));
+        let limit = |name: &str| Box::new(CExpression::Variable(name.to_string()));
+        let binary = |left, operator: &str, right| {
+            Box::new(
+                CBinary {
+                    left,
+                    operator: operator.to_string(),
+                    right,
+                }
+                .to_expression(),
+            )
+        };
+        let uint8 = CType {
+            name: "uint8_t".to_string(),
+            is_pointer: false,
+        };
+        let converted = CCast {
+            typ: uint8.clone(),
+            expression: value(),
+        }
+        .to_expression();
+        let statements = &actual.functions[0].block.statements;
+        assert_eq!(
+            statements[1],
+            CVariableDeclaration {
+                name: "wrapped".to_string(),
+                var_type: uint8.clone(),
+                value: converted.clone(),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            statements[2],
+            CVariableDeclaration {
+                name: "checked".to_string(),
+                var_type: uint8.clone(),
+                value: CConditional {
+                    condition: binary(
+                        binary(value(), ">=", limit("0")),
+                        "&&",
+                        binary(value(), "<", limit("UINT8_MAX")),
+                    ),
+                    then: Box::new(converted.clone()),
+                    otherwise: limit("UINT8_MAX"),
+                }
+                .to_expression(),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            statements[3],
+            CVariableDeclaration {
+                name: "clamped".to_string(),
+                var_type: uint8.clone(),
+                value: CConditional {
+                    condition: binary(value(), "<", limit("0")),
+                    then: limit("0"),
+                    otherwise: Box::new(
+                        CConditional {
+                            condition: binary(value(), ">", limit("UINT8_MAX")),
+                            then: limit("UINT8_MAX"),
+                            otherwise: Box::new(converted.clone()),
+                        }
+                        .to_expression()
+                    ),
+                }
+                .to_expression(),
+            }
+            .to_statement()
+        );
+    }
+
+    #[test]
+    fn test_transpile_invalid_cast() {
+        let src = Src {
+            programs: vec![Program {
+                name: None,
+                doc: None,
+                module: "".to_string(),
+                parameters: vec![],
+                do_block: DoBlock {
+                    statements: vec![
+                        VariableDeclaration {
+                            memory: MemoryModifier::Dim,
+                            identifier: "flag".to_string(),
+                            schema_type: None,
+                            type_span: None,
+                            expression: Cast {
+                                expression: Box::new(
+                                    Literal::Number("1".to_string()).to_expression(),
+                                ),
+                                mode: CastMode::Wrapping,
+                                target: schema_type(SchemaIdentifier::Bool),
+                            }
+                            .to_expression(),
+                        }
+                        .to_statement(),
+                    ],
+                    lines: vec![],
+                },
+            }],
+            ..Src::default()
+        };
+        assert!(matches!(transpile(&src, &TOOLKIT), Of::Error(_)));
+
+        let mut float = src;
+        let float_cast = |mode: CastMode| {
+            Cast {
+                expression: Box::new(Literal::Number("3000000000.5".to_string()).to_expression()),
+                mode,
+                target: schema_type(SchemaIdentifier::Int32),
+            }
+            .to_expression()
+        };
+        let declaration = |expression: Expression| {
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: "number".to_string(),
+                schema_type: None,
+                type_span: None,
+                expression,
+            }
+            .to_statement()
+        };
+        float.programs[0].do_block.statements = vec![declaration(float_cast(CastMode::Wrapping))];
+        match transpile(&float, &TOOLKIT) {
+            Of::Ok(_) => panic!("a float may only become an integer with as? or as!"),
+            Of::Error(err) => assert_eq!(
+                err.message(),
+                "cannot cast Float64 to Int32 with `as`, the value may not fit"
+            ),
+        }
+        for mode in [CastMode::Checked, CastMode::Saturating] {
+            float.programs[0].do_block.statements = vec![declaration(float_cast(mode))];
+            assert!(matches!(transpile(&float, &TOOLKIT), Of::Ok(_)));
+        }
+    }
+
+    #[test]
+    fn test_transpile_debug_print() {
+        let src = Src {
+            programs: vec![Program {
+                name: None,
+                doc: None,
+                module: "".to_string(),
+                parameters: vec![],
+                do_block: DoBlock {
+                    statements: vec![
+                        ProcedureCall {
+                            interface: "debug".to_string(),
+                            identifier: "print".to_string(),
+                            arguments: vec![
+                                Literal::String("100%".to_string()).to_expression(),
+                                Literal::Char('!').to_expression(),
+                                Literal::Number("7".to_string()).to_expression(),
+                            ],
+                        }
+                        .to_statement(),
+                    ],
+                    lines: vec![],
+                },
+            }],
+            ..Src::default()
+        };
+        let actual = run(&src);
+        assert_eq!(
+            actual.functions[0].block.statements[0],
+            CFunctionCall {
+                function_name: "printf".to_string(),
+                arguments: vec![
+                    CLiteral::String("%s%c%lld".to_string()).to_expression(),
+                    CLiteral::String("100%".to_string()).to_expression(),
+                    CLiteral::Char("!".to_string()).to_expression(),
+                    CCast {
+                        typ: CType {
+                            name: "long long".to_string(),
+                            is_pointer: false,
+                        },
+                        expression: Box::new(CLiteral::Number("7".to_string()).to_expression()),
+                    }
+                    .to_expression(),
+                ],
+            }
+            .to_statement()
+        );
+    }
+
+    fn identity_procedure() -> Procedure {
+        Procedure {
+            parameters: vec![parameter(
+                "value",
+                SchemaIdentifier::Int64,
+                TypePostfix::None,
+            )],
+            return_type: Some(schema_type(SchemaIdentifier::Int64)),
+            do_block: do_block(vec![
+                Return {
+                    value: Some(Expression::Variable("value".to_string())),
+                }
+                .to_statement(),
+            ]),
+            ..procedure("", "identity")
+        }
+    }
+
+    fn call_identity(arguments: Vec<Expression>) -> Src {
+        let result = VariableDeclaration {
+            memory: MemoryModifier::Dim,
+            identifier: "result".to_string(),
+            schema_type: None,
+            type_span: None,
+            expression: ProcedureCall {
+                interface: "".to_string(),
+                identifier: "identity".to_string(),
+                arguments,
+            }
+            .to_expression(),
+        };
+        Src {
+            programs: vec![program(vec![], vec![result.to_statement()])],
+            procedures: vec![identity_procedure()],
+            ..Src::default()
+        }
+    }
+
+    #[test]
+    fn test_transpile_procedures() {
+        let actual = run_project(&call_identity(vec![
+            Literal::Number("5".to_string()).to_expression(),
+        ]));
+        let program = &actual.programs[0].src;
+        assert_eq!(
+            program.includes[0],
+            CInclude {
+                file: SHARED_HEADER.to_string(),
+                local: true,
+            }
+        );
+        let int64 = CType {
+            name: "int64_t".to_string(),
+            is_pointer: false,
+        };
+        assert_eq!(
+            program.functions[0].block.statements[0],
+            CVariableDeclaration {
+                name: "result".to_string(),
+                var_type: int64.clone(),
+                value: CFunctionCall {
+                    function_name: "identity".to_string(),
+                    arguments: vec![CLiteral::Number("5".to_string()).to_expression()],
+                }
+                .to_expression(),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            actual.shared.functions[0],
+            CFunction {
+                name: "identity".to_string(),
+                parameters: vec![CParameter {
+                    name: "value".to_string(),
+                    typ: int64.clone(),
+                }],
+                return_type: int64.clone(),
+                block: CBlock {
+                    statements: vec![
+                        CReturn {
+                            value: Some(CExpression::Variable("value".to_string())),
+                        }
+                        .to_statement(),
+                    ],
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_transpile_procedure_errors() {
+        let wrong_count = call_identity(vec![]);
+        assert!(matches!(transpile(&wrong_count, &TOOLKIT), Of::Error(_)));
+
+        let wrong_type = call_identity(vec![Literal::String("5".to_string()).to_expression()]);
+        assert!(matches!(transpile(&wrong_type, &TOOLKIT), Of::Error(_)));
+
+        let mut missing_return =
+            call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        missing_return.procedures[0].do_block.statements.clear();
+        assert!(matches!(transpile(&missing_return, &TOOLKIT), Of::Error(_)));
+
+        let mut duplicate = call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        duplicate.procedures.push(identity_procedure());
+        assert!(matches!(transpile(&duplicate, &TOOLKIT), Of::Error(_)));
+    }
+
+    #[test]
+    fn test_transpile_multiple_programs() {
+        let mut input = call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        let mut server = call_identity(vec![Literal::Number("6".to_string()).to_expression()]);
+        server.programs[0].name = Some("server".to_string());
+        input.programs.push(server.programs.remove(0));
+
+        let actual = run_project(&input);
+        assert_eq!(actual.shared.functions.len(), 1);
+        let names: Vec<Option<String>> = actual.programs.iter().map(|p| p.name.clone()).collect();
+        assert_eq!(names, vec![None, Some("server".to_string())]);
+        assert_eq!(actual.programs[1].src.functions[0].name, "main");
+
+        let mut duplicate = input;
+        duplicate.programs[0].name = Some("server".to_string());
+        match transpile(&duplicate, &TOOLKIT) {
+            Of::Ok(_) => panic!("duplicate program names must be rejected"),
+            Of::Error(err) => {
+                assert_eq!(err.message(), "program 'server' is defined more than once")
+            }
+        }
+    }
+
+    #[test]
+    fn test_transpile_module_symbols() {
+        let file = crate::build_task::SrcFile {
+            file: "my_app.palel".to_string(),
+            content: "proc helper() do\nend\n\nprogram do\n    helper()\nend\n".to_string(),
+        };
+        let mut input = Src::default();
+        assert!(crate::parser::parse(&mut input, &file).is_none());
+        let actual = run_project(&input);
+        assert_eq!(actual.shared.functions[0].name, "my_5fapp__helper");
+
+        let mut underscore = call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        underscore.procedures[0].module = "a_b".to_string();
+        underscore.programs[0].module = "a_b".to_string();
+        let mut nested = call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        nested.procedures[0].module = "a.b".to_string();
+        nested.programs[0].module = "a.b".to_string();
+        assert_eq!(
+            run_project(&underscore).shared.functions[0].name,
+            "a_5fb__identity"
+        );
+        assert_eq!(
+            run_project(&nested).shared.functions[0].name,
+            "a_2eb__identity"
+        );
+    }
+
+    #[test]
+    fn test_transpile_library_exports() {
+        let mut input = call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        input.procedures[0].module = "math".to_string();
+        input.procedures[0].public = true;
+        input.programs[0].module = "math".to_string();
+        let internal = run_project(&input);
+        assert_eq!(internal.shared.functions[0].name, "math__identity");
+        assert!(internal.library.prototypes.is_empty());
+
+        input.procedures[0].export = true;
+        let actual = run_project(&input);
+        assert_eq!(actual.shared.functions[0].name, "identity");
+        let int64 = CType {
+            name: "int64_t".to_string(),
+            is_pointer: false,
+        };
+        assert_eq!(
+            actual.library,
+            CSrc {
+                includes: vec![CInclude {
+                    file: "stdint.h".to_string(),
+                    local: false,
+                }],
+                prototypes: vec![CPrototype {
+                    name: "identity".to_string(),
+                    parameters: vec![CParameter {
+                        name: "value".to_string(),
+                        typ: int64.clone(),
+                    }],
+                    return_type: int64,
+                }],
+                helpers: vec![],
+                functions: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_transpile_program_arguments_and_exit() {
+        let exit = ProcedureCall {
+            interface: "process".to_string(),
+            identifier: "exit".to_string(),
+            arguments: vec![Literal::Number("7".to_string()).to_expression()],
+        };
+        let actual = run(&Src {
+            programs: vec![program(
+                vec![
+                    parameter("input", SchemaIdentifier::CharSeq, TypePostfix::None),
+                    parameter("mode", SchemaIdentifier::CharSeq, TypePostfix::Opt),
+                ],
+                vec![
+                    exit.to_statement(),
+                    Return {
+                        value: Some(Literal::Number("3".to_string()).to_expression()),
+                    }
+                    .to_statement(),
+                ],
+            )],
+            ..Src::default()
+        });
+        let main = &actual.functions[0];
+        let names: Vec<&str> = main.parameters.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["argc", "argv"]);
+        assert!(matches!(main.block.statements[0], CStatement::If(_)));
+        assert_eq!(
+            main.block.statements[2],
+            CVariableDeclaration {
+                name: "mode".to_string(),
+                var_type: CType {
+                    name: "char".to_string(),
+                    is_pointer: true,
+                },
+                value: CConditional {
+                    condition: Box::new(
+                        CBinary {
+                            left: Box::new(CExpression::Variable("argc".to_string())),
+                            operator: ">".to_string(),
+                            right: Box::new(CLiteral::Number("2".to_string()).to_expression()),
+                        }
+                        .to_expression()
+                    ),
+                    then: Box::new(
+                        CIndex {
+                            expression: Box::new(CExpression::Variable("argv".to_string())),
+                            index: Box::new(CLiteral::Number("2".to_string()).to_expression()),
+                        }
+                        .to_expression()
+                    ),
+                    otherwise: Box::new(CLiteral::Number("0".to_string()).to_expression()),
+                }
+                .to_expression(),
+            }
+            .to_statement()
+        );
+        assert_eq!(
+            main.block.statements[3],
+            CFunctionCall {
+                function_name: "exit".to_string(),
+                arguments: vec![CLiteral::Number("7".to_string()).to_expression()],
+            }
+            .to_statement()
+        );
+        assert_eq!(main.block.statements.len(), 5);
+        assert!(actual.includes.contains(&CInclude {
+            file: "stdlib.h".to_string(),
+            local: false,
+        }));
+    }
+
+    #[test]
+    fn test_transpile_program_errors() {
+        let empty_return = Src {
+            programs: vec![program(vec![], vec![Return { value: None }.to_statement()])],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(transpile(&empty_return, &TOOLKIT).err()),
+            "expected a return value of type dim Int32"
+        );
+
+        let text_return = Src {
+            programs: vec![program(
+                vec![],
+                vec![
+                    Return {
+                        value: Some(Literal::String("1".to_string()).to_expression()),
+                    }
+                    .to_statement(),
+                ],
+            )],
+            ..Src::default()
+        };
+        assert!(message(transpile(&text_return, &TOOLKIT).err()).starts_with("incompatible types"));
+
+        let number_parameter = Src {
+            programs: vec![program(
+                vec![parameter(
+                    "count",
+                    SchemaIdentifier::Int32,
+                    TypePostfix::None,
+                )],
+                vec![],
+            )],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(transpile(&number_parameter, &TOOLKIT).err()),
+            "program parameter 'count' must be a String, a String? placed after the required ones, or a StringList placed last"
+        );
+
+        let required_after_optional = Src {
+            programs: vec![program(
+                vec![
+                    parameter("mode", SchemaIdentifier::CharSeq, TypePostfix::Opt),
+                    parameter("input", SchemaIdentifier::CharSeq, TypePostfix::None),
+                ],
+                vec![],
+            )],
+            ..Src::default()
+        };
+        assert!(message(transpile(&required_after_optional, &TOOLKIT).err()).contains("'input'"));
+
+        let list_before_string = Src {
+            programs: vec![program(
+                vec![
+                    parameter("files", SchemaIdentifier::StringList, TypePostfix::None),
+                    parameter("input", SchemaIdentifier::CharSeq, TypePostfix::None),
+                ],
+                vec![],
+            )],
+            ..Src::default()
+        };
+        assert!(message(transpile(&list_before_string, &TOOLKIT).err()).contains("'files'"));
+
+        let exit_text = Src {
+            programs: vec![program(
+                vec![],
+                vec![
+                    ProcedureCall {
+                        interface: "process".to_string(),
+                        identifier: "exit".to_string(),
+                        arguments: vec![Literal::String("1".to_string()).to_expression()],
+                    }
+                    .to_statement(),
+                ],
+            )],
+            ..Src::default()
+        };
+        assert!(message(transpile(&exit_text, &TOOLKIT).err()).starts_with("incompatible types"));
+    }
+
+    #[test]
+    fn test_transpile_redeclaration() {
+        let declare = |name: &str| {
+            VariableDeclaration {
+                memory: MemoryModifier::Dim,
+                identifier: name.to_string(),
+                schema_type: None,
+                type_span: None,
+                expression: Literal::Number("1".to_string()).to_expression(),
+            }
+            .to_statement()
+        };
+        let twice = Src {
+            programs: vec![program(vec![], vec![declare("count"), declare("count")])],
+            ..Src::default()
+        };
+        assert_eq!(
+            message(transpile(&twice, &TOOLKIT).err()),
+            "'count' is already declared, give the new variable another name"
+        );
+
+        let over_parameter = Src {
+            programs: vec![program(
+                vec![parameter(
+                    "input",
+                    SchemaIdentifier::CharSeq,
+                    TypePostfix::None,
+                )],
+                vec![declare("input")],
+            )],
+            ..Src::default()
+        };
+        assert!(
+            message(transpile(&over_parameter, &TOOLKIT).err())
+                .starts_with("'input' is already declared")
+        );
+
+        let mut parameters = call_identity(vec![Literal::Number("5".to_string()).to_expression()]);
+        parameters.procedures[0]
+            .do_block
+            .statements
+            .insert(0, declare("value"));
+        assert!(
+            message(transpile(&parameters, &TOOLKIT).err())
+                .starts_with("'value' is already declared")
+        );
+
+        let distinct = Src {
+            programs: vec![program(vec![], vec![declare("first"), declare("second")])],
+            ..Src::default()
+        };
+        assert_eq!(message(transpile(&distinct, &TOOLKIT).err()), "");
+    }
+
+    #[test]
+    fn test_transpile_tests() {
+        let holds = |message: &str| Test {
+            module: "".to_string(),
+            name: "holds".to_string(),
+            do_block: DoBlock {
+                statements: vec![
+                    Assert {
+                        condition: Literal::Boolean(true).to_expression(),
+                        message: Some(message.to_string()),
+                    }
+                    .to_statement(),
+                ],
+                lines: vec![],
+            },
+        };
+        let mut input = Src {
+            programs: vec![program(vec![], vec![])],
+            ..Src::default()
+        };
+        input.tests.push(holds("always"));
+        let project = run_project(&input);
+        assert_eq!(project.programs.len(), 1);
+        let tests = project.tests.expect("a test harness");
+        let names: Vec<&str> = tests.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["palel_test_0", "main"]);
+
+        input.tests.push(holds("again"));
+        assert_eq!(
+            message(transpile(&input, &TOOLKIT).err()),
+            "test 'holds' is defined more than once in module ''"
+        );
+
+        let not_bool = Src {
+            programs: vec![program(
+                vec![],
+                vec![
+                    Assert {
+                        condition: Literal::Number("1".to_string()).to_expression(),
+                        message: None,
+                    }
+                    .to_statement(),
+                ],
+            )],
+            ..Src::default()
+        };
+        assert!(message(transpile(&not_bool, &TOOLKIT).err()).starts_with("incompatible types"));
+    }
+
+    #[test]
+    fn test_transpile_benches() {
+        let calls = || Bench {
+            module: "".to_string(),
+            name: "calls".to_string(),
+            do_block: DoBlock {
+                statements: vec![
+                    ProcedureCall {
+                        interface: "debug".to_string(),
+                        identifier: "print".to_string(),
+                        arguments: vec![],
+                    }
+                    .to_statement(),
+                ],
+                lines: vec![],
+            },
+        };
+        let mut input = Src {
+            programs: vec![program(vec![], vec![])],
+            ..Src::default()
+        };
+        input.benches.push(calls());
+        let project = run_project(&input);
+        assert!(project.tests.is_none());
+        let benches = project.benches.expect("a bench harness");
+        let names: Vec<&str> = benches.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["palel_bench_0", "palel_bench_now", "main"]);
+
+        input.benches.push(calls());
+        assert_eq!(
+            message(transpile(&input, &TOOLKIT).err()),
+            "bench 'calls' is defined more than once in module ''"
+        );
+    }
+
+    #[test]
+    fn test_transpile_bound_interface() {
+        let mut input = Src {
+            programs: vec![program(
+                vec![],
+                vec![
+                    ProcedureCall {
+                        interface: "term".to_string(),
+                        identifier: "put".to_string(),
+                        arguments: vec![Literal::Number("65".to_string()).to_expression()],
+                    }
+                    .to_statement(),
+                ],
+            )],
+            ..Src::default()
+        };
+        input.interfaces.push(Interface {
+            doc: None,
+            module: "".to_string(),
+            identifier: "term".to_string(),
+            procedures: vec![InterfaceProcedure {
+                identifier: "put".to_string(),
+                parameters: vec![parameter("c", SchemaIdentifier::Int32, TypePostfix::None)],
+                return_type: None,
+            }],
+            header: None,
+            links: vec![],
+        });
+        input.bindings.push(Binding {
+            module: "".to_string(),
+            interface: "term".to_string(),
+            identifier: "put".to_string(),
+            symbol: "putchar".to_string(),
+        });
+        let int32 = CType {
+            name: "int32_t".to_string(),
+            is_pointer: false,
+        };
+        let actual = run(&input);
+        assert_eq!(
+            actual.prototypes,
+            vec![CPrototype {
+                name: "putchar".to_string(),
+                parameters: vec![CParameter {
+                    name: "p0".to_string(),
+                    typ: int32,
+                }],
+                return_type: void_type(),
+            }]
+        );
+        assert_eq!(
+            actual.functions[0].block.statements[0],
+            CFunctionCall {
+                function_name: "putchar".to_string(),
+                arguments: vec![CLiteral::Number("65".to_string()).to_expression()],
+            }
+            .to_statement()
+        );
+    }
 }
@@ -1,15 +1,27 @@
-use crate::c::{CInclude, CSrc, CSrcPatch};
+use crate::c::{CFunction, CInclude, CPrototype, CSrc, CSrcPatch};
 
 pub fn patch_src(src: &mut CSrc, patch: &CSrcPatch) {
     for include in &patch.includes {
         patch_include(&mut src.includes, include);
     }
+    for prototype in &patch.prototypes {
+        patch_prototype(&mut src.prototypes, prototype);
+    }
+    for helper in &patch.helpers {
+        patch_helper(&mut src.helpers, helper);
+    }
 }
 
 pub fn merge_patch(patch: &mut CSrcPatch, other: &CSrcPatch) {
     for include in &other.includes {
         patch_include(&mut patch.includes, include);
     }
+    for prototype in &other.prototypes {
+        patch_prototype(&mut patch.prototypes, prototype);
+    }
+    for helper in &other.helpers {
+        patch_helper(&mut patch.helpers, helper);
+    }
 }
 
 fn patch_include(includes: &mut Vec<CInclude>, include: &CInclude) {
@@ -20,3 +32,17 @@ fn patch_include(includes: &mut Vec<CInclude>, include: &CInclude) {
     }
     includes.push(include.clone())
 }
+
+fn patch_prototype(prototypes: &mut Vec<CPrototype>, prototype: &CPrototype) {
+    if prototypes.iter().any(|p| p.name == prototype.name) {
+        return;
+    }
+    prototypes.push(prototype.clone())
+}
+
+fn patch_helper(helpers: &mut Vec<CFunction>, helper: &CFunction) {
+    if helpers.iter().any(|h| h.name == helper.name) {
+        return;
+    }
+    helpers.push(helper.clone())
+}
@@ -1,5 +1,60 @@
+use crate::palel::{ProcedureSignature, Type};
 
-
+#[derive(Default, Clone)]
 pub struct Context {
-    
+    variables: Vec<(String, Type)>,
+    procedures: Vec<(String, ProcedureSignature)>,
+    pub returns: Returns,
+}
+
+/// What a `return` inside the block being transpiled must provide.
+#[derive(Default, Clone)]
+pub enum Returns {
+    #[default]
+    Unchecked,
+    Nothing,
+    Value(Type),
+    /// The exit status of a program, an Int32
+    ExitStatus,
+}
+
+impl Context {
+    pub fn declare(&mut self, name: &str, typ: Type) {
+        self.variables.push((name.to_string(), typ));
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Type> {
+        self.variables
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+            .map(|(_, typ)| typ)
+    }
+
+    /// Makes a procedure callable by `name`, which is qualified by the module
+    /// alias for procedures of imported modules and by the interface name for
+    /// interface procedures.
+    pub fn declare_procedure(&mut self, name: &str, signature: ProcedureSignature) {
+        self.procedures.push((name.to_string(), signature));
+    }
+
+    pub fn lookup_procedure(&self, name: &str) -> Option<&ProcedureSignature> {
+        self.procedures
+            .iter()
+            .find(|(procedure, _)| procedure == name)
+            .map(|(_, signature)| signature)
+    }
+
+    /// The procedures callable by name, in the order they were declared.
+    pub fn procedures(&self) -> impl Iterator<Item = (&str, &ProcedureSignature)> {
+        self.procedures
+            .iter()
+            .map(|(name, signature)| (name.as_str(), signature))
+    }
+
+    pub fn has_interface(&self, interface: &str) -> bool {
+        self.procedures
+            .iter()
+            .any(|(_, signature)| signature.interface == interface)
+    }
 }
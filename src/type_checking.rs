@@ -1,7 +1,16 @@
+use crate::compilation_error::{
+    ArgumentCountMismatch, CharNotAscii, CompilationError, IncompatibleTypes,
+    IntegerLiteralOutOfRange, InvalidCast, ProcedureHasNoValue, ProcedureNotPublic,
+    UncheckedFloatCast, UnknownInterface, UnknownProcedure, UnknownVariable, ValueSite,
+    VariableRedeclared,
+};
+use crate::core::Of;
 use crate::palel::{
-    Expression, ExpressionType, Literal, MemoryModifier, SchemaIdentifier, SchemaType, Type,
-    TypeFamily, TypePostfix, charseq_type, schema_type,
+    Cast, CastMode, Expression, ExpressionType, Implementation, Literal, MemoryModifier,
+    ProcedureCall, ProcedureSignature, SchemaIdentifier, SchemaType, Type, TypeFamily, TypePostfix,
+    call_name, charseq_type, schema_type,
 };
+use crate::transpiler_context::Context;
 
 use SchemaIdentifier::*;
 
@@ -9,6 +18,7 @@ pub fn determine_variable_type(
     memory: MemoryModifier,
     provided_schema: Option<SchemaType>,
     expr: &Expression,
+    ctx: &Context,
 ) -> Option<Type> {
     fn as_ref(typ: Type) -> Option<Type> {
         match typ {
@@ -23,7 +33,7 @@ pub fn determine_variable_type(
     match memory {
         MemoryModifier::Dim | MemoryModifier::Var => match provided_schema {
             Some(schema) => Some(Type::Dim(schema)),
-            None => match type_of_expression(expr) {
+            None => match type_of_expression(expr, ctx) {
                 Some(t) => t.to_type(),
                 None => {
                     return None;
@@ -33,7 +43,7 @@ pub fn determine_variable_type(
         MemoryModifier::Addr => Some(Type::Addr(provided_schema)),
         MemoryModifier::Ref => match provided_schema {
             Some(schema) => Some(Type::Ref(schema)),
-            None => match type_of_expression(expr) {
+            None => match type_of_expression(expr, ctx) {
                 Some(t) => t.to_type().and_then(as_ref),
                 None => {
                     return None;
@@ -43,20 +53,213 @@ pub fn determine_variable_type(
     }
 }
 
-pub fn type_of_expression(expr: &Expression) -> Option<ExpressionType> {
+pub fn type_of_expression(expr: &Expression, ctx: &Context) -> Option<ExpressionType> {
+    match resolve_expression_type(expr, ctx) {
+        Of::Ok(t) => Some(t),
+        Of::Error(_) => None,
+    }
+}
+
+pub fn resolve_expression_type(expr: &Expression, ctx: &Context) -> Of<ExpressionType> {
     match expr {
-        Expression::Literal(literal) => match literal {
-            Literal::Boolean(_) => Some(ExpressionType::Dim(schema_type(Bool))),
-            Literal::Null => Some(ExpressionType::Null),
-            Literal::Number(value) => {
-                if value.contains(".") {
-                    Some(ExpressionType::Dim(schema_type(Float64)))
-                } else {
-                    Some(ExpressionType::Dim(schema_type(Int32)))
-                }
-            }
-            Literal::String(_) => Some(ExpressionType::Dim(charseq_type())),
+        Expression::Literal(Literal::Char(value)) if !value.is_ascii() => {
+            Of::Error(Box::new(CharNotAscii { value: *value }))
+        }
+        Expression::Literal(literal) => type_of_literal(literal),
+        Expression::Variable(name) => match ctx.lookup(name) {
+            Some(t) => Of::Ok(t.clone().to_expression_type()),
+            None => Of::Error(Box::new(UnknownVariable { name: name.clone() })),
         },
+        Expression::Cast(cast) => resolve_cast_type(cast, ctx),
+        Expression::Call(call) => match resolve_call(call, ctx) {
+            Of::Ok(signature) => match signature.return_type {
+                Some(typ) => Of::Ok(typ.to_expression_type()),
+                None => Of::Error(Box::new(ProcedureHasNoValue {
+                    procedure: signature.name(),
+                })),
+            },
+            Of::Error(err) => Of::Error(err),
+        },
+    }
+}
+
+/// Finds the procedure being called and checks the arguments against its
+/// parameters.
+pub fn resolve_call(call: &ProcedureCall, ctx: &Context) -> Of<ProcedureSignature> {
+    let name = call_name(&call.interface, &call.identifier);
+    let signature = match ctx.lookup_procedure(&name) {
+        Some(signature) => signature.clone(),
+        None if !call.interface.is_empty() && !ctx.has_interface(&call.interface) => {
+            return Of::Error(Box::new(UnknownInterface {
+                interface: call.interface.clone(),
+            }));
+        }
+        None => return Of::Error(Box::new(UnknownProcedure { name })),
+    };
+    if call.identifier.contains('.') && !signature.public {
+        return Of::Error(Box::new(ProcedureNotPublic { name }));
+    }
+    // built-in procedures such as `debug:print` check their own arguments
+    if signature.implementation == Implementation::Builtin {
+        return Of::Ok(signature);
+    }
+    if let Some(err) = check_arguments(&name, &signature.parameters, &call.arguments, ctx) {
+        return Of::Error(err);
+    }
+    Of::Ok(signature)
+}
+
+/// Checks that `name` can be declared. The parameters and variables of a
+/// procedure all live in its one block, so a name is declared only once.
+pub fn check_declaration(name: &str, ctx: &Context) -> Option<Box<dyn CompilationError>> {
+    match ctx.lookup(name) {
+        Some(_) => Some(Box::new(VariableRedeclared {
+            name: name.to_string(),
+        })),
+        None => None,
+    }
+}
+
+/// Checks that `arguments` can be passed to a procedure named `procedure`
+/// taking `parameters`.
+pub fn check_arguments(
+    procedure: &str,
+    parameters: &[Type],
+    arguments: &[Expression],
+    ctx: &Context,
+) -> Option<Box<dyn CompilationError>> {
+    if parameters.len() != arguments.len() {
+        return Some(Box::new(ArgumentCountMismatch {
+            procedure: procedure.to_string(),
+            expected: parameters.len(),
+            received: arguments.len(),
+        }));
+    }
+    for (parameter, argument) in parameters.iter().zip(arguments) {
+        if let Some(err) = check_value(parameter, argument, ValueSite::Argument, ctx) {
+            return Some(err);
+        }
+    }
+    None
+}
+
+/// Checks that `value` can be given to a value of type `to` at `site`. An
+/// integer literal given to an integer type is checked against the range of
+/// that type rather than by its own type.
+pub fn check_value(
+    to: &Type,
+    value: &Expression,
+    site: ValueSite,
+    ctx: &Context,
+) -> Option<Box<dyn CompilationError>> {
+    if let (Type::Dim(schema) | Type::Ref(schema), Expression::Literal(Literal::Number(number))) =
+        (to, value)
+        && let Some((min, max)) = schema.value_range()
+        && !number.contains(".")
+    {
+        return match number.parse::<i128>() {
+            Ok(n) if n >= min && n <= max => None,
+            _ => Some(Box::new(IntegerLiteralOutOfRange {
+                value: number.clone(),
+                expected: schema.clone(),
+            })),
+        };
+    }
+    let actual = match resolve_expression_type(value, ctx) {
+        Of::Ok(t) => t,
+        Of::Error(err) => return Some(err),
+    };
+    if is_valid_expression_assignment(to, &actual) || is_valid_literal_assignment(to, value) {
+        return None;
+    }
+    Some(Box::new(IncompatibleTypes {
+        expected: to.clone(),
+        actual,
+        site,
+    }))
+}
+
+/// An integer literal is an `Int32` when it fits in one and an `Int64`
+/// otherwise, a literal too large for an `Int64` needs a type that holds it.
+fn type_of_literal(literal: &Literal) -> Of<ExpressionType> {
+    let typ = match literal {
+        Literal::Boolean(_) => ExpressionType::Dim(schema_type(Bool)),
+        Literal::Null => ExpressionType::Null,
+        Literal::Number(value) if value.contains(".") => ExpressionType::Dim(schema_type(Float64)),
+        Literal::Number(value) => {
+            let fits = |typ: &SchemaType| match (typ.value_range(), value.parse::<i128>()) {
+                (Some((min, max)), Ok(number)) => number >= min && number <= max,
+                _ => false,
+            };
+            let int32 = schema_type(Int32);
+            let int64 = schema_type(Int64);
+            if fits(&int32) {
+                ExpressionType::Dim(int32)
+            } else if fits(&int64) {
+                ExpressionType::Dim(int64)
+            } else {
+                return Of::Error(Box::new(IntegerLiteralOutOfRange {
+                    value: value.clone(),
+                    expected: int64,
+                }));
+            }
+        }
+        Literal::String(_) => ExpressionType::Dim(charseq_type()),
+        Literal::Char(_) => ExpressionType::Dim(schema_type(Char)),
+    };
+    Of::Ok(typ)
+}
+
+fn resolve_cast_type(cast: &Cast, ctx: &Context) -> Of<ExpressionType> {
+    let from = match resolve_expression_type(&cast.expression, ctx) {
+        Of::Ok(t) => t,
+        err @ Of::Error(_) => return err,
+    };
+    let valid = match &from {
+        ExpressionType::Dim(schema) | ExpressionType::Ref(schema) => {
+            is_valid_cast(&cast.target, schema)
+        }
+        _ => false,
+    };
+    if !valid {
+        return Of::Error(Box::new(InvalidCast {
+            from,
+            to: cast.target.clone(),
+        }));
+    }
+    if let ExpressionType::Dim(schema) | ExpressionType::Ref(schema) = &from
+        && cast.mode == CastMode::Wrapping
+        && schema.family == TypeFamily::Float
+        && matches!(cast.target.family, TypeFamily::Int | TypeFamily::UInt)
+    {
+        return Of::Error(Box::new(UncheckedFloatCast {
+            from: schema.clone(),
+            to: cast.target.clone(),
+        }));
+    }
+    let mut target = schema_type(cast.target.identifier.clone());
+    if cast.mode == CastMode::Checked {
+        target.postfix = TypePostfix::Opt;
+    }
+    Of::Ok(ExpressionType::Dim(target))
+}
+
+/// Explicit casts may convert between any of the numeric types, from `Bool` to
+/// an integer, and between `Char` and the 8 bit integers. Nullable values must
+/// be unwrapped before they can be cast. A floating point value only becomes
+/// an integer with `as?` or `as!`, which `resolve_cast_type` checks.
+pub fn is_valid_cast(to: &SchemaType, from: &SchemaType) -> bool {
+    if from.postfix != TypePostfix::None {
+        return false;
+    }
+    if to.identifier == from.identifier {
+        return true;
+    }
+    let numeric = |family: &TypeFamily| *family != TypeFamily::None;
+    match (&to.identifier, &from.identifier) {
+        (_, Bool) => matches!(to.family, TypeFamily::Int | TypeFamily::UInt),
+        (Char, Int8 | UInt8) | (Int8 | UInt8, Char) => true,
+        _ => numeric(&to.family) && numeric(&from.family),
     }
 }
 
@@ -92,19 +295,58 @@ pub fn is_valid_expression_assignment(to: &Type, from: &ExpressionType) -> bool
     }
 }
 
+/// Numeric literals take on the declared type when their value fits in it,
+/// so `dim b UInt8 = 200` is accepted even though the literal is an `Int32`.
+/// Likewise a string literal holding a single ASCII character may be a `Char`.
+pub fn is_valid_literal_assignment(to: &Type, expr: &Expression) -> bool {
+    let schema = match to {
+        Type::Dim(schema) | Type::Ref(schema) => schema,
+        Type::Addr(_) => return false,
+    };
+    let value = match expr {
+        Expression::Literal(Literal::Number(value)) => value,
+        Expression::Literal(Literal::String(value)) => {
+            return schema.identifier == Char && value.len() == 1 && value.is_ascii();
+        }
+        _ => return false,
+    };
+    if value.contains(".") {
+        return schema.family == TypeFamily::Float;
+    }
+    if schema.family == TypeFamily::Float {
+        return true;
+    }
+    match (schema.identifier.integer_range(), value.parse::<i128>()) {
+        (Some((min, max)), Ok(number)) => number >= min && number <= max,
+        _ => false,
+    }
+}
+
 pub fn can_implicitly_convert(to: &SchemaType, from: &SchemaType) -> bool {
     if to.family == TypeFamily::None || from.family == TypeFamily::None {
         return to == from;
     }
 
+    // signed and unsigned integers never convert silently, in either direction
     if to.family != from.family {
         return false;
     }
 
-    let to_width = to.width.unwrap_or(0);
-    let from_width = from.width.unwrap_or(0);
+    if to.identifier == from.identifier {
+        return true;
+    }
+
+    // Size is only known to hold 32 bits and to fit in 64 bits
+    let to_width = match to.identifier {
+        Size => 32,
+        _ => to.width.unwrap_or(0),
+    };
+    let from_width = match from.identifier {
+        Size => 64,
+        _ => from.width.unwrap_or(0),
+    };
 
-    return to_width >= from_width;
+    to_width >= from_width
 }
 
 #[cfg(test)]
@@ -157,6 +399,89 @@ mod test {
         ));
     }
 
+    #[test]
+    fn test_integer_widths() {
+        assert!(can_implicitly_convert(
+            &schema_type(Int16),
+            &schema_type(Int8)
+        ));
+        assert!(can_implicitly_convert(
+            &schema_type(UInt64),
+            &schema_type(UInt8)
+        ));
+        assert!(can_implicitly_convert(
+            &schema_type(Size),
+            &schema_type(UInt32)
+        ));
+        assert!(can_implicitly_convert(
+            &schema_type(UInt64),
+            &schema_type(Size)
+        ));
+
+        assert!(!can_implicitly_convert(
+            &schema_type(Int8),
+            &schema_type(Int16)
+        ));
+        assert!(!can_implicitly_convert(
+            &schema_type(Size),
+            &schema_type(UInt64)
+        ));
+        assert!(!can_implicitly_convert(
+            &schema_type(UInt32),
+            &schema_type(Size)
+        ));
+    }
+
+    #[test]
+    fn test_sign_changes() {
+        assert!(!can_implicitly_convert(
+            &schema_type(Int64),
+            &schema_type(UInt8)
+        ));
+        assert!(!can_implicitly_convert(
+            &schema_type(UInt64),
+            &schema_type(Int8)
+        ));
+        assert!(!can_implicitly_convert(
+            &schema_type(Size),
+            &schema_type(Int32)
+        ));
+    }
+
+    #[test]
+    fn test_literal_assignment() {
+        let number = |value: &str| Literal::Number(value.to_string()).to_expression();
+        assert!(is_valid_literal_assignment(
+            &Type::Dim(schema_type(UInt8)),
+            &number("255")
+        ));
+        assert!(is_valid_literal_assignment(
+            &Type::Dim(schema_type(Int8)),
+            &number("-128")
+        ));
+        assert!(is_valid_literal_assignment(
+            &Type::Dim(schema_type(Float32)),
+            &number("1.5")
+        ));
+
+        assert!(!is_valid_literal_assignment(
+            &Type::Dim(schema_type(UInt8)),
+            &number("256")
+        ));
+        assert!(!is_valid_literal_assignment(
+            &Type::Dim(schema_type(UInt32)),
+            &number("-1")
+        ));
+        assert!(!is_valid_literal_assignment(
+            &Type::Dim(schema_type(Size)),
+            &number("5000000000")
+        ));
+        assert!(!is_valid_literal_assignment(
+            &Type::Dim(schema_type(Int16)),
+            &number("1.5")
+        ));
+    }
+
     #[test]
     fn test_incompatible() {
         assert!(!can_implicitly_convert(
@@ -176,4 +501,40 @@ mod test {
             &schema_type(Bool)
         ));
     }
+
+    #[test]
+    fn test_explicit_casts() {
+        assert!(is_valid_cast(&schema_type(Int32), &schema_type(Int64)));
+        assert!(is_valid_cast(&schema_type(UInt8), &schema_type(Float64)));
+        assert!(is_valid_cast(&schema_type(Int64), &schema_type(Bool)));
+        assert!(is_valid_cast(&schema_type(UInt8), &schema_type(Char)));
+
+        assert!(!is_valid_cast(&schema_type(Bool), &schema_type(Int32)));
+        assert!(!is_valid_cast(&schema_type(Float32), &schema_type(Char)));
+        let mut nullable = schema_type(Int32);
+        nullable.postfix = TypePostfix::Opt;
+        assert!(!is_valid_cast(&schema_type(Int64), &nullable));
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let ctx = Context::default();
+        assert!(matches!(
+            resolve_expression_type(&Literal::Char('a').to_expression(), &ctx),
+            Of::Ok(ExpressionType::Dim(schema)) if schema == schema_type(Char)
+        ));
+        assert!(matches!(
+            resolve_expression_type(&Literal::Char('\u{e9}').to_expression(), &ctx),
+            Of::Error(_)
+        ));
+        assert!(is_valid_literal_assignment(
+            &Type::Dim(schema_type(Char)),
+            &Literal::String("A".to_string()).to_expression()
+        ));
+        assert!(!is_valid_literal_assignment(
+            &Type::Dim(schema_type(Char)),
+            &Literal::String("AB".to_string()).to_expression()
+        ));
+        assert!(!can_implicitly_convert(&schema_type(Char), &charseq_type()));
+    }
 }
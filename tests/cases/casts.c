@@ -0,0 +1,46 @@
+// casts.c
+#include <stdio.h>
+#include <stdint.h>
+#include <stddef.h>
+#include <float.h>
+int main()
+{
+#line 2 "main.palel"
+double big = 3000000000.5;
+#line 3 "main.palel"
+double small = -3000000000.5;
+#line 4 "main.palel"
+printf("%lld%s%lld%s%llu%s%lld%s",((long long)(((big > INT32_MIN) && (big < 2147483648.0)) ? ((int32_t)big) : INT32_MIN))," ",((long long)((big != big) ? 0 : ((big < INT32_MIN) ? INT32_MIN : ((big >= 2147483648.0) ? INT32_MAX : ((int32_t)big)))))," ",((unsigned long long)((small != small) ? 0 : ((small < 0) ? 0 : ((small >= 256.0) ? UINT8_MAX : ((uint8_t)small)))))," ",((long long)((big != big) ? 0 : ((big < INT64_MIN) ? INT64_MIN : ((big >= 9223372036854775808.0) ? INT64_MAX : ((int64_t)big))))),"\n");
+#line 5 "main.palel"
+int64_t wide = 5000000000;
+#line 6 "main.palel"
+printf("%lld%s%lld%s%lld%s%llu%s",((long long)((int32_t)wide))," ",((long long)(((wide > INT32_MIN) && (wide <= INT32_MAX)) ? ((int32_t)wide) : INT32_MIN))," ",((long long)((wide < INT16_MIN) ? INT16_MIN : ((wide > INT16_MAX) ? INT16_MAX : ((int16_t)wide))))," ",((unsigned long long)((size_t)wide)),"\n");
+#line 7 "main.palel"
+int32_t negative = -5;
+#line 8 "main.palel"
+printf("%llu%s%llu%s%llu%s%g%s",((unsigned long long)((uint8_t)negative))," ",((unsigned long long)((negative >= 0) ? ((size_t)negative) : SIZE_MAX))," ",((unsigned long long)((negative < 0) ? 0 : ((uint32_t)negative)))," ",((float)negative),"\n");
+#line 9 "main.palel"
+double huge = 1000000000000000000000000000000000000000000.5;
+#line 10 "main.palel"
+printf("%g%s%g%s%llu%s",((float)huge)," ",((huge < -FLT_MAX) ? -FLT_MAX : ((huge > FLT_MAX) ? FLT_MAX : ((float)huge)))," ",((unsigned long long)((huge != huge) ? 0 : ((huge < 0) ? 0 : ((huge >= 18446744073709551616.0) ? UINT64_MAX : ((uint64_t)huge))))),"\n");
+#line 11 "main.palel"
+uint64_t count = 18446744073709551615;
+#line 12 "main.palel"
+size_t size = ((size_t)count);
+#line 13 "main.palel"
+printf("%llu%s%lld%s%llu%s%lld%s",((unsigned long long)size)," ",((long long)((int64_t)size))," ",((unsigned long long)((size < UINT32_MAX) ? ((uint32_t)size) : UINT32_MAX))," ",((long long)((size > INT8_MAX) ? INT8_MAX : ((int8_t)size))),"\n");
+#line 14 "main.palel"
+float edge32 = 2147483648.0;
+#line 15 "main.palel"
+double edge63 = 9223372036854775808.0;
+#line 16 "main.palel"
+double edge64 = 18446744073709551616.0;
+#line 17 "main.palel"
+printf("%lld%s%lld%s%lld%s%lld%s",((long long)(((edge32 > INT32_MIN) && (edge32 < 2147483648.0)) ? ((int32_t)edge32) : INT32_MIN))," ",((long long)((edge32 != edge32) ? 0 : ((edge32 < INT32_MIN) ? INT32_MIN : ((edge32 >= 2147483648.0) ? INT32_MAX : ((int32_t)edge32)))))," ",((long long)(((edge63 > INT64_MIN) && (edge63 < 9223372036854775808.0)) ? ((int64_t)edge63) : INT64_MIN))," ",((long long)((edge63 != edge63) ? 0 : ((edge63 < INT64_MIN) ? INT64_MIN : ((edge63 >= 9223372036854775808.0) ? INT64_MAX : ((int64_t)edge63))))),"\n");
+#line 18 "main.palel"
+printf("%llu%s%llu%s%llu%s%llu%s",((unsigned long long)(((edge64 >= 0) && (edge64 < UINT64_MAX)) ? ((uint64_t)edge64) : UINT64_MAX))," ",((unsigned long long)((edge64 != edge64) ? 0 : ((edge64 < 0) ? 0 : ((edge64 >= 18446744073709551616.0) ? UINT64_MAX : ((uint64_t)edge64)))))," ",((unsigned long long)((edge64 != edge64) ? 0 : ((edge64 < 0) ? 0 : ((edge64 >= 18446744073709551616.0) ? SIZE_MAX : ((size_t)edge64)))))," ",((unsigned long long)((edge63 != edge63) ? 0 : ((edge63 < 0) ? 0 : ((edge63 >= 18446744073709551616.0) ? UINT64_MAX : ((uint64_t)edge63))))),"\n");
+#line 19 "main.palel"
+printf("%llu%s%llu%s%llu%s",((unsigned long long)((count < SIZE_MAX) ? ((size_t)count) : SIZE_MAX))," ",((unsigned long long)((size < UINT64_MAX) ? ((uint64_t)size) : UINT64_MAX))," ",((unsigned long long)((count > SIZE_MAX) ? SIZE_MAX : ((size_t)count))),"\n");
+#line 20 "main.palel"
+return 0;
+}
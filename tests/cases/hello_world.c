@@ -0,0 +1,9 @@
+// hello_world.c
+#include <stdio.h>
+int main()
+{
+#line 2 "main.palel"
+printf("%s","Hello World\n");
+#line 3 "main.palel"
+return 0;
+}
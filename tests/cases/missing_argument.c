@@ -0,0 +1,15 @@
+// missing_argument.c
+#include <stdio.h>
+int main(int argc, char * *argv)
+{
+if (((argc < 2) || (argc > 3)))
+{
+fprintf(stderr,"usage: %s <name> [greeting]\n",argv[0]);
+return 2;
+}
+char *name = argv[1];
+char *greeting = ((argc > 2) ? argv[2] : 0);
+#line 2 "main.palel"
+printf("%s%s%s%s",greeting," ",name,"\n");
+return 0;
+}
@@ -0,0 +1,37 @@
+// palel.shared.c
+#include <stdint.h>
+#include <stdio.h>
+int32_t main__pick(int32_t a, int32_t b);
+void main__greet(char *name);
+int32_t main__pick(int32_t a, int32_t b)
+{
+#line 3 "main.palel"
+return b;
+}
+void main__greet(char *name)
+{
+#line 7 "main.palel"
+printf("%s%s%s","hello ",name,"\n");
+#line 8 "main.palel"
+return;
+}
+// palel.shared.h
+#ifndef PALEL_SHARED_H
+#define PALEL_SHARED_H
+#include <stdint.h>
+#include <stdio.h>
+int32_t main__pick(int32_t a, int32_t b);
+void main__greet(char *name);
+#endif
+// procedures.c
+#include "palel.shared.h"
+#include <stdio.h>
+int main()
+{
+#line 12 "main.palel"
+main__greet("palel");
+#line 13 "main.palel"
+printf("%lld%s",((long long)main__pick(1,2)),"\n");
+#line 14 "main.palel"
+return 0;
+}
@@ -0,0 +1,12 @@
+// process_exit.c
+#include <stdio.h>
+#include <stdlib.h>
+int main()
+{
+#line 2 "main.palel"
+printf("%s","leaving\n");
+#line 3 "main.palel"
+exit(7);
+#line 4 "main.palel"
+return 0;
+}
@@ -0,0 +1,74 @@
+// palel.shared.c
+#include <stdint.h>
+static char *palel_strings_get(char * *list, int32_t index)
+{
+if ((index < 0))
+{
+return 0;
+}
+int32_t at = 0;
+while ((at < index))
+{
+if ((list[at] == 0))
+{
+return 0;
+}
+at = (at + 1);
+}
+return list[index];
+}
+char *main__first(char * *files);
+char *main__first(char * *files)
+{
+#line 2 "main.palel"
+return palel_strings_get(files,0);
+}
+// palel.shared.h
+#ifndef PALEL_SHARED_H
+#define PALEL_SHARED_H
+#include <stdint.h>
+char *main__first(char * *files);
+#endif
+// rest_arguments.c
+#include "palel.shared.h"
+#include <stdio.h>
+#include <stdint.h>
+static int32_t palel_strings_count(char * *list)
+{
+int32_t count = 0;
+while ((list[count] != 0))
+{
+count = (count + 1);
+}
+return count;
+}
+static char *palel_strings_get(char * *list, int32_t index)
+{
+if ((index < 0))
+{
+return 0;
+}
+int32_t at = 0;
+while ((at < index))
+{
+if ((list[at] == 0))
+{
+return 0;
+}
+at = (at + 1);
+}
+return list[index];
+}
+int main(int argc, char * *argv)
+{
+if ((argc < 1))
+{
+fprintf(stderr,"usage: %s [files...]\n",argv[0]);
+return 2;
+}
+char * *files = (argv + ((argc > 1) ? 1 : argc));
+#line 6 "main.palel"
+printf("%lld%s%s%s%s%s",((long long)palel_strings_count(files))," ",main__first(files)," ",palel_strings_get(files,-1),"\n");
+#line 7 "main.palel"
+return palel_strings_count(files);
+}
@@ -0,0 +1,20 @@
+// variables_and_casts.c
+#include <stdint.h>
+#include <stdio.h>
+int main()
+{
+#line 2 "main.palel"
+uint8_t small = 200;
+#line 3 "main.palel"
+uint64_t wide = small;
+#line 4 "main.palel"
+double half = 0.5;
+#line 5 "main.palel"
+int32_t missing = INT32_MIN;
+#line 6 "main.palel"
+printf("%llu%s%g%s%lld%s",((unsigned long long)wide)," ",half," ",((long long)missing),"\n");
+#line 7 "main.palel"
+printf("%llu%s%llu%s%lld%s",((unsigned long long)((uint8_t)300))," ",((unsigned long long)(((-1 >= 0) && (-1 < UINT8_MAX)) ? ((uint8_t)-1) : UINT8_MAX))," ",((long long)((1000 < INT8_MIN) ? INT8_MIN : ((1000 > INT8_MAX) ? INT8_MAX : ((int8_t)1000)))),"\n");
+#line 8 "main.palel"
+return 0;
+}
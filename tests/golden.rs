@@ -0,0 +1,176 @@
+//! Runs `palel build` on every `tests/cases/<case>.palel` and compares what
+//! it produces with the expectations stored next to the case:
+//!
+//! - `<case>.c` the generated C sources, when the build succeeds
+//! - `<case>.diagnostics` what the compiler reported, when it fails
+//! - `<case>.stdout` what the built program printed
+//! - `<case>.status` the exit status of the program, or of the compiler when
+//!   the build fails
+//!
+//! An expectation without its file is not checked. With `PALEL_BLESS=1` the
+//! files are written from the actual results instead.
+//!
+//! Every program that builds is also run with `palel run --interpret`, which
+//! must print the same and exit with the same status as the compiled program.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::{env, process};
+
+use pretty_assertions::StrComparison;
+
+/// The outcome of one case, each part `None` when the case did not get to it.
+#[derive(Default)]
+struct Actual {
+    c: Option<String>,
+    diagnostics: Option<String>,
+    stdout: Option<String>,
+    status: Option<String>,
+    /// What the interpreter printed and its exit status
+    interpreted: Option<(String, String)>,
+}
+
+#[test]
+fn golden_cases() {
+    let cases_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases");
+    let work_dir = env::temp_dir().join(format!("palel-golden-{}", process::id()));
+    let bless = env::var("PALEL_BLESS").is_ok_and(|value| value == "1");
+
+    let mut cases: Vec<PathBuf> = fs::read_dir(&cases_dir)
+        .expect("tests/cases is readable")
+        .map(|entry| entry.expect("a case").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "palel"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no cases in {}", cases_dir.display());
+
+    let mut failures = vec![];
+    for case in &cases {
+        let name = case.file_stem().unwrap().to_string_lossy().to_string();
+        let actual = run_case(case, &work_dir.join(&name));
+        let expectations = [
+            ("c", &actual.c),
+            ("diagnostics", &actual.diagnostics),
+            ("stdout", &actual.stdout),
+            ("status", &actual.status),
+        ];
+        if let (Some(stdout), Some(status), Some(interpreted)) =
+            (&actual.stdout, &actual.status, &actual.interpreted)
+        {
+            let compiled = format!("{}status {}", stdout, status);
+            let interpreted = format!("{}status {}", interpreted.0, interpreted.1);
+            if compiled != interpreted {
+                failures.push(format!(
+                    "{} runs differently when interpreted:\n{}",
+                    name,
+                    StrComparison::new(&compiled, &interpreted)
+                ));
+            }
+        }
+        for (extension, actual) in expectations {
+            let file = case.with_extension(extension);
+            if bless {
+                match actual {
+                    Some(actual) => fs::write(&file, actual).expect("expectation is writable"),
+                    None if file.exists() => fs::remove_file(&file).expect("removable"),
+                    None => {}
+                }
+                continue;
+            }
+            let expected = match fs::read_to_string(&file) {
+                Ok(expected) => expected,
+                Err(_) => continue,
+            };
+            let actual = actual.as_deref().unwrap_or("");
+            if expected != actual {
+                failures.push(format!(
+                    "{}.{} differs:\n{}",
+                    name,
+                    extension,
+                    StrComparison::new(&expected, actual)
+                ));
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(&work_dir);
+    if !failures.is_empty() {
+        panic!(
+            "{} expectation(s) differ, run with PALEL_BLESS=1 to update them\n\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Builds the case as the only source file of a project of its own, named
+/// after the case, and runs the program when that works.
+fn run_case(case: &Path, project_dir: &Path) -> Actual {
+    let _ = fs::remove_dir_all(project_dir);
+    fs::create_dir_all(project_dir.join("src")).expect("project is writable");
+    fs::copy(case, project_dir.join("src/main.palel")).expect("case is copied");
+
+    let mut actual = Actual::default();
+    let build = palel(project_dir, &["build"]);
+    if !build.status.success() {
+        actual.diagnostics = Some(captured(&build));
+        actual.status = Some(status(&build));
+        return actual;
+    }
+    actual.c = Some(generated_c(&project_dir.join("build/code")));
+
+    let name = project_dir.file_name().unwrap();
+    let program = Command::new(project_dir.join("build").join(name))
+        .current_dir(project_dir)
+        .output()
+        .expect("the program runs");
+    actual.stdout = Some(String::from_utf8_lossy(&program.stdout).to_string());
+    actual.status = Some(status(&program));
+
+    let interpreted = palel(project_dir, &["run", "--interpret"]);
+    actual.interpreted = Some((
+        String::from_utf8_lossy(&interpreted.stdout).to_string(),
+        status(&interpreted),
+    ));
+    actual
+}
+
+fn palel(project_dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_palel"))
+        .args(args)
+        .current_dir(project_dir)
+        .output()
+        .expect("palel runs")
+}
+
+/// The C sources and headers of the build, each after a comment naming it.
+fn generated_c(code_dir: &Path) -> String {
+    let mut files: Vec<PathBuf> = fs::read_dir(code_dir)
+        .expect("the build wrote its code")
+        .map(|entry| entry.expect("a file").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c" || ext == "h"))
+        .collect();
+    files.sort();
+    let mut output = String::new();
+    for file in files {
+        let name = file.file_name().unwrap().to_string_lossy();
+        output.push_str(&format!("// {}\n", name));
+        output.push_str(&fs::read_to_string(&file).expect("C is readable"));
+    }
+    output
+}
+
+fn captured(output: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+fn status(output: &Output) -> String {
+    match output.status.code() {
+        Some(code) => format!("{}\n", code),
+        None => "signal\n".to_string(),
+    }
+}